@@ -16,9 +16,10 @@ use std::time::Duration;
 use tokio::sync::mpsc;
 
 use crate::config::Config;
-use crate::screens::{Screen, BrowserScreen, GeneratorScreen, ModelsScreen};
+use crate::screens::{Screen, BrowserScreen, GeneratorScreen, GraphicsProtocol, ModelsScreen};
 use crate::services::{
-    EsaService, JwstApiService, TunnelManager, OllamaService, ComfyUiService, WallustService,
+    self, CollectionsService, DownloadManager, FavoritesService, LibraryIndex, TunnelManager, OllamaService, ComfyUiService,
+    WallustService,
 };
 
 /// Application state.
@@ -28,8 +29,6 @@ pub struct App {
     should_quit: bool,
 
     // Services
-    esa_service: Arc<EsaService>,
-    api_service: Arc<JwstApiService>,
     tunnel_manager: Arc<tokio::sync::Mutex<TunnelManager>>,
     ollama_service: Arc<OllamaService>,
     comfyui_service: Arc<ComfyUiService>,
@@ -64,18 +63,24 @@ impl App {
         let config = Arc::new(config);
 
         // Initialize services
-        let esa_service = Arc::new(EsaService::new(config.clone()));
-        let api_service = Arc::new(JwstApiService::new(config.clone()));
         let tunnel_manager = Arc::new(tokio::sync::Mutex::new(TunnelManager::new(config.clone())));
-        let ollama_service = Arc::new(OllamaService::new());
-        let comfyui_service = Arc::new(ComfyUiService::new());
+        let ollama_service = Arc::new(OllamaService::new(&config)?);
+        let comfyui_service = Arc::new(ComfyUiService::new(&config)?);
         let wallust_service = Arc::new(WallustService::new((*config).clone()));
 
         // Initialize screens
+        let sources = services::build_sources(config.clone())?;
+        let favorites_service = Arc::new(FavoritesService::new(&config));
+        let collections_service = Arc::new(CollectionsService::new(&config));
+        let library = Arc::new(LibraryIndex::new(&config, &sources));
         let browser_screen = BrowserScreen::new(
-            esa_service.clone(),
-            api_service.clone(),
+            sources,
             wallust_service.clone(),
+            favorites_service.clone(),
+            collections_service.clone(),
+            library.clone(),
+            GraphicsProtocol::from_config(&config.ui.preview),
+            DownloadManager::new(config.jwst.download_concurrency, library),
         );
         let generator_screen = GeneratorScreen::new(
             comfyui_service.clone(),
@@ -92,8 +97,6 @@ impl App {
             config,
             current_screen: AppScreen::Browser,
             should_quit: false,
-            esa_service,
-            api_service,
             tunnel_manager,
             ollama_service,
             comfyui_service,
@@ -121,7 +124,8 @@ impl App {
         // Main event loop
         let result = self.event_loop(&mut terminal).await;
 
-        // Restore terminal
+        // Remove any preview graphics, then restore terminal
+        let _ = self.browser_screen.flush_graphics(terminal.backend_mut(), false);
         disable_raw_mode()?;
         execute!(
             terminal.backend_mut(),
@@ -136,6 +140,7 @@ impl App {
     /// Load initial data for all screens.
     async fn load_initial_data(&mut self) {
         self.status_message = "Loading images...".to_string();
+        self.browser_screen.maintain_library(true);
 
         // Load ESA images
         if let Err(e) = self.browser_screen.load_images(false).await {
@@ -148,14 +153,28 @@ impl App {
     /// Main event loop.
     async fn event_loop(&mut self, terminal: &mut Terminal<CrosstermBackend<io::Stdout>>) -> Result<()> {
         loop {
-            // Draw UI
+            // Draw UI, then any preview graphics on top
             terminal.draw(|f| self.draw(f))?;
+            let visible = self.current_screen == AppScreen::Browser;
+            if self.browser_screen.flush_graphics(terminal.backend_mut(), visible)? {
+                // A stale sixel image only goes away with a full redraw
+                terminal.clear()?;
+                terminal.draw(|f| self.draw(f))?;
+                self.browser_screen.flush_graphics(terminal.backend_mut(), visible)?;
+            }
 
             // Poll for events with timeout
             if event::poll(Duration::from_millis(100))? {
                 if let Event::Key(key) = event::read()? {
                     // Global key handlers
                     match (key.modifiers, key.code) {
+                        (modifiers, _) if modifiers != KeyModifiers::CONTROL
+                            && self.current_screen == AppScreen::Browser
+                            && self.browser_screen.is_text_input() =>
+                        {
+                            // Browser search box gets every key except Ctrl shortcuts
+                            self.browser_screen.handle_key(key).await;
+                        }
                         (KeyModifiers::CONTROL, KeyCode::Char('c')) |
                         (KeyModifiers::CONTROL, KeyCode::Char('q')) |
                         (_, KeyCode::Char('q')) if self.current_screen == AppScreen::Browser => {
@@ -277,8 +296,15 @@ impl App {
             Span::styled("🔗 Disconnected", Style::default().fg(Color::Red))
         };
 
-        let status = Paragraph::new(Line::from(vec![
-            Span::raw(" "),
+        let mut spans = vec![Span::raw(" ")];
+        if let Some(banner) = self.browser_screen.network_status() {
+            spans.push(Span::styled(
+                format!(" {} ", banner),
+                Style::default().fg(Color::Black).bg(Color::Yellow),
+            ));
+            spans.push(Span::raw(" "));
+        }
+        spans.extend([
             Span::styled(&self.status_message, Style::default().fg(Color::Gray)),
             Span::raw(" │ "),
             tunnel_indicator,
@@ -291,7 +317,8 @@ impl App {
             Span::raw(" │ "),
             Span::styled("[Q]", Style::default().fg(Color::DarkGray)),
             Span::styled(" Quit", Style::default().fg(Color::Gray)),
-        ]));
+        ]);
+        let status = Paragraph::new(Line::from(spans));
         f.render_widget(status, chunks[2]);
     }
 
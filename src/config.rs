@@ -5,17 +5,33 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::PathBuf;
 
+use crate::utils::{FitMode, SizePreset};
+
+/// Source keys taken by the built-in sources (and generated images), which
+/// extra feeds can't use.
+const RESERVED_FEED_KEYS: &[&str] = &["esa", "jwst_api", "apod", "local", "generated"];
+
+/// Download file name prefixes of the built-in sources (ESA/Webb, the JWST
+/// API and APOD), which extra feeds can't use.
+const RESERVED_FILE_PREFIXES: &[&str] = &["webb", "jwst", "apod"];
+
 /// Main configuration structure.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub jwst: JwstConfig,
     #[serde(default)]
+    pub apod: ApodConfig,
+    #[serde(default)]
     pub remote: RemoteConfig,
     #[serde(default)]
     pub generation: GenerationConfig,
     #[serde(default)]
     pub wallust: WallustConfig,
+    #[serde(default)]
+    pub ui: UiConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
 }
 
 /// JWST image source configuration.
@@ -33,10 +49,41 @@ pub struct JwstConfig {
     #[serde(default = "default_cache_dir")]
     pub cache_dir: String,
 
+    /// Data directory for persistent state (favorites, ...)
+    #[serde(default = "default_data_dir")]
+    pub data_dir: String,
+
     /// Cache TTL in seconds
     #[serde(default = "default_cache_ttl")]
     pub cache_ttl: u64,
 
+    /// Never touch the network: serve cached listings and downloaded images
+    /// only (also set by `--offline`)
+    #[serde(default)]
+    pub offline: bool,
+
+    /// Maximum parallel image downloads
+    #[serde(default = "default_download_concurrency")]
+    pub download_concurrency: usize,
+
+    /// Size cap for cached thumbnails in MB (0 = unlimited)
+    #[serde(default = "default_thumbnail_cache_mb")]
+    pub thumbnail_cache_mb: u64,
+
+    /// Size cap for downloaded images in MB, not counting favorites and the
+    /// current wallpaper (0 = unlimited)
+    #[serde(default)]
+    pub download_cache_mb: u64,
+
+    /// Monitor profiles to write sized variants for after each download
+    /// (preset names like "ultrawide" or "laptop", or "WIDTHxHEIGHT")
+    #[serde(default = "default_monitor_profiles")]
+    pub monitor_profiles: Vec<String>,
+
+    /// How variants reach the monitor's aspect: "crop" or "fit" (letterbox)
+    #[serde(default = "default_variant_mode")]
+    pub variant_mode: String,
+
     /// ESA RSS feed URL
     #[serde(default = "default_esa_rss_url")]
     pub esa_rss_url: String,
@@ -45,11 +92,104 @@ pub struct JwstConfig {
     #[serde(default = "default_esa_cdn_base")]
     pub esa_cdn_base: String,
 
+    /// ESA/Webb gallery page URL pattern ({id} is replaced by the image ID)
+    #[serde(default = "default_esa_gallery_url")]
+    pub esa_gallery_url: String,
+
+    /// ESA/Webb archive listing URL pattern ({page} is replaced by the page number)
+    #[serde(default = "default_esa_archive_url")]
+    pub esa_archive_url: String,
+
+    /// Additional ESA-style gallery feeds (ESA/Hubble, ESO, ...)
+    #[serde(default)]
+    pub feeds: Vec<EsaFeedConfig>,
+
     /// JWST API base URL
     #[serde(default = "default_api_base")]
     pub api_base: String,
 }
 
+/// An ESA-style gallery feed (same RSS layout and CDN renditions as ESA/Webb).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EsaFeedConfig {
+    /// Stable key for the feed (used for cache files and image records)
+    pub key: String,
+
+    /// Display name
+    pub name: String,
+
+    /// RSS feed URL
+    pub rss_url: String,
+
+    /// CDN base URL for image renditions
+    pub cdn_base: String,
+
+    /// Gallery page URL pattern ({id} is replaced by the image ID)
+    pub gallery_url: String,
+
+    /// Prefix for downloaded filenames (e.g. "webb" -> webb-<id>.jpg)
+    pub file_prefix: String,
+
+    /// Archive listing page URL pattern ({page} is replaced by the page number)
+    #[serde(default)]
+    pub archive_url: Option<String>,
+
+    /// License of the gallery's images, embedded in downloads for attribution
+    #[serde(default = "default_feed_license")]
+    pub license: String,
+}
+
+/// NASA Astronomy Picture of the Day configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApodConfig {
+    /// Path to NASA API key file (falls back to DEMO_KEY)
+    #[serde(default = "default_apod_api_key_file")]
+    pub api_key_file: String,
+
+    /// APOD API endpoint
+    #[serde(default = "default_apod_api_base")]
+    pub api_base: String,
+
+    /// Number of days to fetch, ending today
+    #[serde(default = "default_apod_days")]
+    pub days: u32,
+}
+
+/// Terminal UI configuration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UiConfig {
+    /// Image preview: "auto", "kitty", "sixel", "halfblocks" or "off"
+    #[serde(default = "default_preview")]
+    pub preview: String,
+}
+
+/// HTTP settings shared by every service.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NetworkConfig {
+    /// Retries for transient failures (timeouts, dropped connections, 429
+    /// and 5xx responses)
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+
+    /// Requests per second to any one image host (0 = unlimited)
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+
+    /// Proxy for HTTP and HTTPS requests, e.g. "http://proxy.example:3128"
+    /// (unset: the HTTP_PROXY / HTTPS_PROXY environment variables apply)
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Hosts and domains that bypass `proxy`
+    #[serde(default = "default_no_proxy")]
+    pub no_proxy: Vec<String>,
+
+    /// Extra PEM files of trusted root certificates, added to the built-in
+    /// roots (for proxies that intercept TLS)
+    #[serde(default)]
+    pub ca_certificates: Vec<String>,
+}
+
 /// Remote server configuration.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RemoteConfig {
@@ -122,10 +262,34 @@ fn default_cache_dir() -> String {
     "~/.cache/jwst-cosmos".to_string()
 }
 
+fn default_data_dir() -> String {
+    "~/.local/share/jwst-cosmos".to_string()
+}
+
 fn default_cache_ttl() -> u64 {
     3600
 }
 
+fn default_download_concurrency() -> usize {
+    3
+}
+
+fn default_thumbnail_cache_mb() -> u64 {
+    256
+}
+
+fn default_feed_license() -> String {
+    "CC BY 4.0".to_string()
+}
+
+fn default_monitor_profiles() -> Vec<String> {
+    vec!["ultrawide".to_string(), "laptop".to_string()]
+}
+
+fn default_variant_mode() -> String {
+    "crop".to_string()
+}
+
 fn default_esa_rss_url() -> String {
     "https://feeds.feedburner.com/esawebb/images/".to_string()
 }
@@ -134,10 +298,47 @@ fn default_esa_cdn_base() -> String {
     "https://cdn.esawebb.org/archives/images".to_string()
 }
 
+fn default_esa_gallery_url() -> String {
+    "https://esawebb.org/images/{id}/".to_string()
+}
+
+fn default_esa_archive_url() -> String {
+    "https://esawebb.org/images/list/{page}/".to_string()
+}
+
 fn default_api_base() -> String {
     "https://api.jwstapi.com".to_string()
 }
 
+fn default_apod_api_key_file() -> String {
+    "/run/agenix/nasa-api-key".to_string()
+}
+
+fn default_apod_api_base() -> String {
+    "https://api.nasa.gov/planetary/apod".to_string()
+}
+
+fn default_apod_days() -> u32 {
+    30
+}
+
+fn default_preview() -> String {
+    "auto".to_string()
+}
+
+fn default_retries() -> u32 {
+    3
+}
+
+fn default_requests_per_second() -> f64 {
+    4.0
+}
+
+fn default_no_proxy() -> Vec<String> {
+    // The SSH tunnels to Ollama and ComfyUI end on localhost
+    vec!["localhost".to_string(), "127.0.0.1".to_string(), "::1".to_string()]
+}
+
 fn default_remote_host() -> String {
     "192.168.0.27".to_string()
 }
@@ -188,14 +389,72 @@ impl Default for JwstConfig {
             api_key_file: default_api_key_file(),
             wallpaper_dir: default_wallpaper_dir(),
             cache_dir: default_cache_dir(),
+            data_dir: default_data_dir(),
             cache_ttl: default_cache_ttl(),
+            offline: false,
+            download_concurrency: default_download_concurrency(),
+            thumbnail_cache_mb: default_thumbnail_cache_mb(),
+            download_cache_mb: 0,
+            monitor_profiles: default_monitor_profiles(),
+            variant_mode: default_variant_mode(),
             esa_rss_url: default_esa_rss_url(),
             esa_cdn_base: default_esa_cdn_base(),
+            esa_gallery_url: default_esa_gallery_url(),
+            esa_archive_url: default_esa_archive_url(),
+            feeds: Vec::new(),
             api_base: default_api_base(),
         }
     }
 }
 
+impl JwstConfig {
+    /// All ESA-style feeds: ESA/Webb first, then any configured extras.
+    pub fn esa_feeds(&self) -> Vec<EsaFeedConfig> {
+        let mut feeds = vec![EsaFeedConfig {
+            key: "esa".to_string(),
+            name: "ESA/Webb Gallery".to_string(),
+            rss_url: self.esa_rss_url.clone(),
+            cdn_base: self.esa_cdn_base.clone(),
+            gallery_url: self.esa_gallery_url.clone(),
+            file_prefix: "webb".to_string(),
+            archive_url: Some(self.esa_archive_url.clone()),
+            license: default_feed_license(),
+        }];
+        feeds.extend(self.feeds.iter().cloned());
+        feeds
+    }
+}
+
+impl Default for ApodConfig {
+    fn default() -> Self {
+        Self {
+            api_key_file: default_apod_api_key_file(),
+            api_base: default_apod_api_base(),
+            days: default_apod_days(),
+        }
+    }
+}
+
+impl Default for UiConfig {
+    fn default() -> Self {
+        Self {
+            preview: default_preview(),
+        }
+    }
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        Self {
+            retries: default_retries(),
+            requests_per_second: default_requests_per_second(),
+            proxy: None,
+            no_proxy: default_no_proxy(),
+            ca_certificates: Vec::new(),
+        }
+    }
+}
+
 impl Default for RemoteConfig {
     fn default() -> Self {
         Self {
@@ -233,9 +492,12 @@ impl Default for Config {
     fn default() -> Self {
         Self {
             jwst: JwstConfig::default(),
+            apod: ApodConfig::default(),
             remote: RemoteConfig::default(),
             generation: GenerationConfig::default(),
             wallust: WallustConfig::default(),
+            ui: UiConfig::default(),
+            network: NetworkConfig::default(),
         }
     }
 }
@@ -265,9 +527,65 @@ impl Config {
             .with_context(|| format!("Failed to read config file: {}", path))?;
         let config: Config = toml::from_str(&content)
             .with_context(|| format!("Failed to parse config file: {}", path))?;
+        config
+            .validate()
+            .with_context(|| format!("Invalid config file: {}", path))?;
         Ok(config)
     }
 
+    /// Check settings that deserialize fine but can't be used.
+    fn validate(&self) -> Result<()> {
+        for name in &self.jwst.monitor_profiles {
+            if SizePreset::parse(name).is_none() {
+                anyhow::bail!("Unknown monitor profile: {} (use a preset name or WIDTHxHEIGHT)", name);
+            }
+        }
+        if FitMode::from_config(&self.jwst.variant_mode).is_none() {
+            anyhow::bail!("Unknown variant_mode: {} (use \"crop\" or \"fit\")", self.jwst.variant_mode);
+        }
+
+        // Feed keys name cache files and tag every image record; file
+        // prefixes tell whose downloads are whose
+        let valid = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        };
+        let mut keys: Vec<&str> = Vec::new();
+        let mut prefixes: Vec<&str> = Vec::new();
+        for feed in &self.jwst.feeds {
+            let key = feed.key.as_str();
+            if !valid(key) {
+                anyhow::bail!("Invalid feed key: {:?} (use lowercase letters, digits, _ and -)", key);
+            }
+            if RESERVED_FEED_KEYS.contains(&key) {
+                anyhow::bail!("Feed key {:?} is used by a built-in source", key);
+            }
+            if keys.contains(&key) {
+                anyhow::bail!("Duplicate feed key: {:?}", key);
+            }
+            keys.push(key);
+
+            let prefix = feed.file_prefix.as_str();
+            if !valid(prefix) {
+                anyhow::bail!(
+                    "Invalid file_prefix for feed {:?}: {:?} (use lowercase letters, digits, _ and -)",
+                    key,
+                    prefix
+                );
+            }
+            if RESERVED_FILE_PREFIXES.contains(&prefix) {
+                anyhow::bail!("file_prefix {:?} of feed {:?} is used by a built-in source", prefix, key);
+            }
+            if prefixes.contains(&prefix) {
+                anyhow::bail!("Duplicate file_prefix: {:?}", prefix);
+            }
+            prefixes.push(prefix);
+        }
+        Ok(())
+    }
+
     /// Expand ~ in paths to home directory.
     pub fn expand_path(&self, path: &str) -> PathBuf {
         if path.starts_with("~/") {
@@ -288,6 +606,11 @@ impl Config {
         self.expand_path(&self.jwst.cache_dir)
     }
 
+    /// Get the data directory path.
+    pub fn data_dir(&self) -> PathBuf {
+        self.expand_path(&self.jwst.data_dir)
+    }
+
     /// Get the thumbnail cache directory.
     pub fn thumbnail_dir(&self) -> PathBuf {
         self.cache_dir().join("thumbnails")
@@ -300,6 +623,13 @@ impl Config {
             .map(|s| s.trim().to_string())
     }
 
+    /// Read the NASA APOD API key from file.
+    pub fn apod_api_key(&self) -> Option<String> {
+        fs::read_to_string(&self.apod.api_key_file)
+            .ok()
+            .map(|s| s.trim().to_string())
+    }
+
     /// Parse size string like "5120x2160" into (width, height).
     pub fn parse_size(&self, size_str: &str) -> (u32, u32) {
         // Check presets
@@ -320,3 +650,80 @@ impl Config {
             .unwrap_or((5120, 2160))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> Result<Config> {
+        let config: Config = toml::from_str(toml)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_monitor_profile() {
+        parse("[jwst]\nmonitor_profiles = [\"ultrawide\", \"2560x1440\"]").unwrap();
+        let error = parse("[jwst]\nmonitor_profiles = [\"ultrawide\", \"bogus\"]").unwrap_err();
+        assert!(error.to_string().contains("bogus"));
+    }
+
+    fn feed(key: &str) -> String {
+        feed_with_prefix(key, key)
+    }
+
+    fn feed_with_prefix(key: &str, prefix: &str) -> String {
+        format!(
+            "[[jwst.feeds]]\nkey = \"{}\"\nname = \"Feed\"\nrss_url = \"https://example.org/rss\"\n\
+             cdn_base = \"https://cdn.example.org\"\ngallery_url = \"https://example.org/{{id}}/\"\n\
+             file_prefix = \"{}\"\n",
+            key, prefix
+        )
+    }
+
+    #[test]
+    fn accepts_distinct_feed_keys() {
+        let config = parse(&(feed("hubble") + &feed("eso_2-x"))).unwrap();
+        assert_eq!(config.jwst.esa_feeds().len(), 3);
+    }
+
+    #[test]
+    fn rejects_bad_feed_keys() {
+        for key in ["Hubble", "hub ble", "../hubble", ""] {
+            assert!(parse(&feed(key)).is_err(), "{:?} accepted", key);
+        }
+    }
+
+    #[test]
+    fn rejects_reserved_and_duplicate_feed_keys() {
+        for key in RESERVED_FEED_KEYS {
+            assert!(parse(&feed(key)).is_err(), "{:?} accepted", key);
+        }
+        let error = parse(&(feed("hubble") + &feed("hubble"))).unwrap_err();
+        assert!(error.to_string().contains("Duplicate"));
+    }
+
+    #[test]
+    fn rejects_reserved_duplicate_and_bad_file_prefixes() {
+        for prefix in RESERVED_FILE_PREFIXES {
+            let error = parse(&feed_with_prefix("hubble", prefix)).unwrap_err();
+            assert!(error.to_string().contains("built-in"), "{:?}: {}", prefix, error);
+        }
+        let error = parse(&(feed_with_prefix("hubble", "hst") + &feed_with_prefix("hubble2", "hst"))).unwrap_err();
+        assert!(error.to_string().contains("Duplicate file_prefix"));
+        for prefix in ["", "../hst", "Hubble", "hst/x"] {
+            assert!(parse(&feed_with_prefix("hubble", prefix)).is_err(), "{:?} accepted", prefix);
+        }
+        parse(&(feed_with_prefix("hubble", "hst") + &feed_with_prefix("eso", "eso"))).unwrap();
+    }
+
+    #[test]
+    fn rejects_unknown_variant_mode() {
+        assert!(parse("[jwst]\nvariant_mode = \"stretch\"").is_err());
+    }
+}
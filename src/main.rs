@@ -10,7 +10,9 @@ mod services;
 mod utils;
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+use std::sync::Arc;
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
 
 /// JWST Cosmos - Space Image Browser and AI Image Generator
@@ -24,6 +26,331 @@ struct Args {
     /// Config file path (default: ~/.config/jwst-cosmos/config.toml)
     #[arg(short, long)]
     config: Option<String>,
+
+    /// Don't touch the network: browse cached listings and downloaded images
+    #[arg(long)]
+    offline: bool,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Non-interactive commands (the TUI runs when none is given).
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Manage image collections
+    #[command(subcommand)]
+    Collection(CollectionCommand),
+
+    /// Apply the next wallpaper from a collection's rotation
+    Wallpaper {
+        /// Collection to rotate through
+        collection: String,
+    },
+
+    /// Check downloaded images, quarantining corrupt or truncated files
+    Verify,
+
+    /// Show or purge cached thumbnails, feeds and downloads
+    #[command(subcommand)]
+    Cache(CacheCommand),
+
+    /// Index and search downloaded and generated images
+    #[command(subcommand)]
+    Library(LibraryCommand),
+}
+
+#[derive(Subcommand, Debug)]
+enum CollectionCommand {
+    /// List collections
+    List,
+    /// Export a collection to a TOML file
+    Export { name: String, file: PathBuf },
+    /// Import a collection from a TOML file (merges into an existing one)
+    Import { file: PathBuf },
+    /// Delete a collection
+    Delete { name: String },
+}
+
+#[derive(Subcommand, Debug)]
+enum CacheCommand {
+    /// Show disk usage per category and per source
+    Usage,
+    /// Remove cached files, least recently used first (favorites and the
+    /// current wallpaper are always kept)
+    Purge {
+        /// Only this category: thumbnails, feeds or downloads (default:
+        /// thumbnails and downloads)
+        #[arg(long)]
+        category: Option<String>,
+        /// Only files unused for this many days
+        #[arg(long)]
+        older_than: Option<u64>,
+        /// Only downloads from this source (e.g. esa, apod)
+        #[arg(long)]
+        source: Option<String>,
+        /// Evict until the category fits in this size (e.g. 500M, 2G)
+        #[arg(long)]
+        max_size: Option<String>,
+        /// Purge everything unprotected
+        #[arg(long)]
+        all: bool,
+        /// Show what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum LibraryCommand {
+    /// Re-index the wallpaper directory, picking up files added, changed or
+    /// removed outside the app
+    Rescan,
+    /// Search the library, e.g. `carina src:esa tag:nircam color:#c06030`
+    /// (terms: src, tag, rendition, color, gen, width)
+    Search {
+        #[arg(required = true)]
+        query: Vec<String>,
+    },
+    /// Remove duplicate copies of the same picture (across sources and
+    /// renditions), keeping the highest-resolution one; favorites and
+    /// collection items are relinked to it
+    Dedupe {
+        /// Also remove monitor variants (crops) of a kept image
+        #[arg(long)]
+        include_variants: bool,
+        /// Show what would be removed without removing it
+        #[arg(long)]
+        dry_run: bool,
+    },
+}
+
+/// Print a library entry: its path, then what's known about it.
+fn print_library_entry(entry: &services::library::LibraryEntry) {
+    println!("{}", entry.path.display());
+    let mut details = vec![format!("{} {}", entry.source, entry.id)];
+    details.extend(entry.rendition.clone());
+    details.extend(entry.dimensions.map(|(w, h)| format!("{}x{}", w, h)));
+    details.push(utils::human_size(entry.size));
+    if let Some(palette) = entry.palette.as_ref().filter(|p| !p.is_empty()) {
+        details.push(palette.join(" "));
+    }
+    println!("    {}", details.join(" · "));
+    if !entry.tags.is_empty() {
+        println!("    tags: {}", entry.tags.join(", "));
+    }
+    if let Some(generation) = &entry.generation {
+        println!("    generated from {}", generation.reference.display());
+        if let Some(prompt) = &generation.prompt {
+            println!("    prompt: {}", prompt);
+        }
+    }
+}
+
+/// Print cache usage per category, with downloads broken down by source.
+fn print_cache_usage(config: &config::Config, usage: &services::cache::CacheUsage) {
+    use services::cache::CacheCategory;
+    use utils::human_size;
+
+    for category in CacheCategory::ALL {
+        let (count, bytes) = usage.total(category);
+        let cap = match category {
+            CacheCategory::Thumbnails => Some(config.jwst.thumbnail_cache_mb),
+            CacheCategory::Downloads => Some(config.jwst.download_cache_mb),
+            CacheCategory::Feeds => None,
+        };
+        let mut line = format!("{:<12}{:>7} files{:>11}", category.label(), count, human_size(bytes));
+        match cap {
+            Some(0) => line.push_str("  (no cap)"),
+            Some(mb) => line.push_str(&format!("  (cap {})", human_size(mb * 1024 * 1024))),
+            None => {}
+        }
+        let protected = usage.protected_bytes(category);
+        if protected > 0 {
+            line.push_str(&format!(", {} protected", human_size(protected)));
+        }
+        println!("{}", line);
+    }
+    for (source, count, bytes) in usage.by_source() {
+        println!("  {:<10}{:>7} files{:>11}", source, count, human_size(bytes));
+    }
+}
+
+/// Run a non-interactive command.
+async fn run_command(config: config::Config, command: Command) -> Result<()> {
+    let config = Arc::new(config);
+    let collections = services::CollectionsService::new(&config);
+
+    match command {
+        Command::Collection(CollectionCommand::List) => {
+            for collection in collections.list() {
+                println!("{} ({} images)", collection.name, collection.items.len());
+            }
+        }
+        Command::Collection(CollectionCommand::Export { name, file }) => {
+            collections.export(&name, &file)?;
+            println!("Exported '{}' to {}", name, file.display());
+        }
+        Command::Collection(CollectionCommand::Import { file }) => {
+            let name = collections.import(&file)?;
+            println!("Imported '{}'", name);
+        }
+        Command::Collection(CollectionCommand::Delete { name }) => {
+            collections.delete(&name)?;
+            println!("Deleted '{}'", name);
+        }
+        Command::Wallpaper { collection } => {
+            let sources = services::build_sources(config.clone())?;
+            let wallust = services::WallustService::new((*config).clone());
+            let image = services::collections::rotate_wallpaper(&collections, &collection, &sources, &wallust).await?;
+            println!("Applied {} ({})", image.title, image.id);
+        }
+        Command::Verify => {
+            let sources = services::build_sources(config.clone())?;
+            let library = services::LibraryIndex::new(&config, &sources);
+            let report = services::library::verify_library(&config)?;
+            for (path, reason) in &report.quarantined {
+                println!("Quarantined {}: {}", path.display(), reason);
+            }
+            let paths: Vec<PathBuf> = report.quarantined.iter().map(|(path, _)| path.clone()).collect();
+            library.remove(&paths);
+            println!("Verified {} files, {} quarantined", report.checked, report.quarantined.len());
+        }
+        Command::Cache(command) => {
+            use services::cache::{self, CacheCategory, PurgeFilter};
+
+            let sources = services::build_sources(config.clone())?;
+            let favorites = services::FavoritesService::new(&config);
+            let wallust = services::WallustService::new((*config).clone());
+            let protected = cache::protected_paths(&sources, &favorites, &wallust);
+            let usage = cache::scan(&config, &sources, &protected);
+
+            match command {
+                CacheCommand::Usage => print_cache_usage(&config, &usage),
+                CacheCommand::Purge {
+                    category,
+                    older_than,
+                    source,
+                    max_size,
+                    all,
+                    dry_run,
+                } => {
+                    if !all && category.is_none() && older_than.is_none() && source.is_none() && max_size.is_none() {
+                        anyhow::bail!("Nothing selected: give --category, --older-than, --source, --max-size or --all");
+                    }
+                    let categories = match category {
+                        Some(name) => vec![CacheCategory::parse(&name)
+                            .ok_or_else(|| anyhow::anyhow!("Unknown cache category: {}", name))?],
+                        None => vec![CacheCategory::Thumbnails, CacheCategory::Downloads],
+                    };
+                    let max_bytes = match max_size {
+                        Some(size) => Some(
+                            utils::parse_size(&size).ok_or_else(|| anyhow::anyhow!("Invalid size: {}", size))?,
+                        ),
+                        None => None,
+                    };
+                    let filter = PurgeFilter {
+                        categories,
+                        older_than: older_than.map(|days| std::time::Duration::from_secs(days * 86400)),
+                        source,
+                        max_bytes,
+                    };
+
+                    let report = cache::purge(&usage, &filter, dry_run);
+                    if !dry_run {
+                        services::LibraryIndex::new(&config, &sources).remove(&report.removed);
+                    }
+                    for path in &report.removed {
+                        println!("{} {}", if dry_run { "Would remove" } else { "Removed" }, path.display());
+                    }
+                    println!(
+                        "{} {} files ({})",
+                        if dry_run { "Would free" } else { "Freed" },
+                        report.removed.len(),
+                        utils::human_size(report.freed)
+                    );
+                    for (path, e) in &report.failed {
+                        println!("Failed to remove {}: {}", path.display(), e);
+                    }
+                    if !report.failed.is_empty() {
+                        anyhow::bail!("{} files could not be removed", report.failed.len());
+                    }
+                }
+            }
+        }
+        Command::Library(command) => {
+            use services::library::LibraryQuery;
+
+            let sources = services::build_sources(config.clone())?;
+            let library = services::LibraryIndex::new(&config, &sources);
+
+            match command {
+                LibraryCommand::Rescan => {
+                    let report = library.rescan();
+                    let analyzed = library.analyze();
+                    println!(
+                        "Indexed {} files ({} added, {} updated, {} removed, {} analyzed)",
+                        report.total, report.added, report.updated, report.removed, analyzed
+                    );
+                }
+                LibraryCommand::Search { query } => {
+                    let query = LibraryQuery::parse(&query.join(" ")).map_err(anyhow::Error::msg)?;
+                    // color: terms match against the palettes analysis works out
+                    library.rescan();
+                    library.analyze();
+                    let found = library.search(&query);
+                    for entry in &found {
+                        print_library_entry(entry);
+                    }
+                    println!("{} matching files", found.len());
+                }
+                LibraryCommand::Dedupe { include_variants, dry_run } => {
+                    library.rescan();
+                    library.analyze();
+                    let favorites = services::FavoritesService::new(&config);
+                    let collections = services::CollectionsService::new(&config);
+                    let wallust = services::WallustService::new((*config).clone());
+                    let wallpaper = wallust.current_wallpaper().map(PathBuf::from);
+
+                    let report =
+                        library.dedupe(&favorites, &collections, wallpaper.as_deref(), include_variants, dry_run)?;
+                    for copy in &report.removed {
+                        let distance = match copy.distance {
+                            Some(distance) => format!("distance {}", distance),
+                            None => "same image".to_string(),
+                        };
+                        println!(
+                            "{} {} (keeping {}, {})",
+                            if dry_run { "Would remove" } else { "Removed" },
+                            copy.path.display(),
+                            copy.kept.display(),
+                            distance
+                        );
+                    }
+                    for path in &report.kept_variants {
+                        println!("Keeping variant {} (--include-variants removes it)", path.display());
+                    }
+                    println!(
+                        "{} {} files ({}), {} {} favorites and {} collection items",
+                        if dry_run { "Would free" } else { "Freed" },
+                        report.removed.len(),
+                        utils::human_size(report.freed),
+                        if dry_run { "would relink" } else { "relinked" },
+                        report.relinked,
+                        report.relinked_items
+                    );
+                    for (path, e) in &report.failed {
+                        println!("Failed to remove {}: {}", path.display(), e);
+                    }
+                    if !report.failed.is_empty() {
+                        anyhow::bail!("{} files could not be removed", report.failed.len());
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
 }
 
 #[tokio::main]
@@ -43,11 +370,17 @@ async fn main() -> Result<()> {
         .init();
 
     // Load configuration
-    let config = if let Some(path) = args.config {
+    let mut config = if let Some(path) = args.config {
         config::Config::from_file(&path)?
     } else {
         config::Config::load()?
     };
+    config.jwst.offline |= args.offline;
+    services::http::check_config(&config)?;
+
+    if let Some(command) = args.command {
+        return run_command(config, command).await;
+    }
 
     // Run the TUI application
     let mut app = app::App::new(config)?;
@@ -2,66 +2,271 @@
 
 use async_trait::async_trait;
 use crossterm::event::{KeyCode, KeyEvent};
+use image::DynamicImage;
 use ratatui::{
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap},
+    widgets::{Block, Borders, Gauge, List, ListItem, ListState, Paragraph, Wrap},
     Frame,
 };
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::Arc;
+use tokio::sync::{mpsc, oneshot, Semaphore};
 
+use super::filters::{DownloadFilter, FilterSet, SortOrder};
+use super::grid::{GridCell, ThumbnailGrid};
+use super::preview::{GraphicsProtocol, ImagePreview};
+use super::renditions::RenditionPicker;
 use super::Screen;
-use crate::services::{EsaService, EsaImage, JwstApiService, WallustService};
+use crate::services::cache::{self, PurgeReport};
+use crate::services::collections::rotate_wallpaper;
+use crate::services::downloads::{DownloadManager, DownloadState};
+use crate::services::library::{verify_library, DuplicateGroup, VerifyReport};
+use crate::services::{
+    CollectionsService, FavoritesService, ImageRecord, ImageSource, LibraryIndex, WallustService, DEFAULT_RENDITION,
+};
+use crate::utils::fuzzy::{fuzzy_match, word_match};
+use crate::utils::human_size;
+
+/// What a background library upkeep did.
+struct Upkeep {
+    evicted: PurgeReport,
+    duplicates: Vec<DuplicateGroup>,
+}
+
+/// A batch of older images fetched from a source's archive in the background.
+struct MoreImages {
+    source: String,
+    images: Vec<ImageRecord>,
+    next_page: u32,
+    more_available: bool,
+    error: Option<String>,
+}
+
+/// Fuzzy search hit for one image.
+#[derive(Debug, Clone, Default)]
+struct SearchHit {
+    score: i64,
+    id_positions: Vec<usize>,
+    title_positions: Vec<usize>,
+}
 
-/// Image source selection.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-pub enum ImageSource {
-    Esa,
-    JwstApi,
+/// What the image list is showing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum BrowserView {
+    /// A single provider (index into `sources`)
+    Source(usize),
+    /// All providers merged
+    All,
+    /// Starred images, from the favorites store
+    Favorites,
+    /// A named collection
+    Collection(String),
 }
 
+/// Maximum concurrent gallery page fetches for background detail loading.
+const DETAIL_FETCH_LIMIT: usize = 4;
+
 /// Browser screen state.
 pub struct BrowserScreen {
-    esa_service: Arc<EsaService>,
-    api_service: Arc<JwstApiService>,
+    sources: Vec<Arc<dyn ImageSource>>,
     wallust_service: Arc<WallustService>,
+    favorites: Arc<FavoritesService>,
+    collections: Arc<CollectionsService>,
+    library: Arc<LibraryIndex>,
 
     // State
-    source: ImageSource,
-    esa_images: Vec<EsaImage>,
+    view: BrowserView,
+    images: Vec<ImageRecord>,
     list_state: ListState,
     loading: bool,
     error: Option<String>,
 
-    // Selected image for detail view
-    show_detail: bool,
+    // Archive paging ("load more" past the end of the list), run in the
+    // background
+    next_page: u32,
+    more_available: bool,
+    more_rx: Option<oneshot::Receiver<MoreImages>>,
+
+    // Incremental search ("/"); the list shows `visible` (indices into `images`)
+    search_input: bool,
+    search_query: String,
+    visible: Vec<usize>,
+    hits: HashMap<usize, SearchHit>,
+
+    // Facet filters ("f") and sort order ("o"), kept across refreshes
+    filters: FilterSet,
+    filter_input: bool,
+    filter_buffer: String,
+    sort: SortOrder,
+
+    // Add-to-collection prompt ("c"); the last name used is offered again
+    collection_input: bool,
+    collection_buffer: String,
+    last_collection: Option<String>,
+
+    // Background detail loading (gallery page metadata)
+    details_tx: mpsc::UnboundedSender<ImageRecord>,
+    details_rx: mpsc::UnboundedReceiver<ImageRecord>,
+    details_requested: HashSet<String>,
+    details_limit: Arc<Semaphore>,
+
+    // Thumbnail preview in the detail pane, loaded in the background
+    preview: ImagePreview,
+    preview_tx: mpsc::UnboundedSender<(String, DynamicImage)>,
+    preview_rx: mpsc::UnboundedReceiver<(String, DynamicImage)>,
+    preview_requested: Option<String>,
+
+    // Thumbnail grid layout ("v")
+    grid_mode: bool,
+    grid: ThumbnailGrid,
+
+    // Multi-select: marked image keys, plus a visual range anchor ("V")
+    marked: HashSet<String>,
+    visual_anchor: Option<usize>,
+
+    // Background downloads, with a rendition picker ("d")
+    downloads: DownloadManager,
+    picker: Option<RenditionPicker>,
+
+    // Library verification ("L"), run in the background
+    verify_rx: Option<oneshot::Receiver<anyhow::Result<VerifyReport>>>,
+    notice: Option<String>,
+
+    // Near-duplicates from the library index: for each file, the other
+    // images ("source id") it duplicates. Refreshed after library upkeep.
+    duplicates: HashMap<PathBuf, Vec<String>>,
+    upkeep_rx: Option<oneshot::Receiver<Upkeep>>,
 
     // Track last downloaded image for wallpaper application
     last_downloaded: Option<PathBuf>,
+    // Downloads finished since the last upkeep; never evicted by it
+    fresh_downloads: Vec<PathBuf>,
 }
 
 impl BrowserScreen {
     pub fn new(
-        esa_service: Arc<EsaService>,
-        api_service: Arc<JwstApiService>,
+        sources: Vec<Arc<dyn ImageSource>>,
         wallust_service: Arc<WallustService>,
+        favorites: Arc<FavoritesService>,
+        collections: Arc<CollectionsService>,
+        library: Arc<LibraryIndex>,
+        preview: GraphicsProtocol,
+        downloads: DownloadManager,
     ) -> Self {
         let mut list_state = ListState::default();
         list_state.select(Some(0));
+        let (details_tx, details_rx) = mpsc::unbounded_channel();
+        let (preview_tx, preview_rx) = mpsc::unbounded_channel();
 
         Self {
-            esa_service,
-            api_service,
+            sources,
             wallust_service,
-            source: ImageSource::Esa,
-            esa_images: Vec::new(),
+            favorites,
+            collections,
+            library,
+            view: BrowserView::Source(0),
+            images: Vec::new(),
             list_state,
             loading: false,
             error: None,
-            show_detail: false,
+            next_page: 1,
+            more_available: false,
+            more_rx: None,
+            search_input: false,
+            search_query: String::new(),
+            visible: Vec::new(),
+            hits: HashMap::new(),
+            filters: FilterSet::default(),
+            filter_input: false,
+            filter_buffer: String::new(),
+            sort: SortOrder::default(),
+            collection_input: false,
+            collection_buffer: String::new(),
+            last_collection: None,
+            details_tx,
+            details_rx,
+            details_requested: HashSet::new(),
+            details_limit: Arc::new(Semaphore::new(DETAIL_FETCH_LIMIT)),
+            preview: ImagePreview::new(preview),
+            preview_tx,
+            preview_rx,
+            preview_requested: None,
+            grid_mode: false,
+            grid: ThumbnailGrid::new(),
+            marked: HashSet::new(),
+            visual_anchor: None,
+            downloads,
+            picker: None,
+            verify_rx: None,
+            notice: None,
+            duplicates: HashMap::new(),
+            upkeep_rx: None,
             last_downloaded: None,
+            fresh_downloads: Vec::new(),
+        }
+    }
+
+    /// Get the currently active image source (`None` for the merged views).
+    fn current_source(&self) -> Option<&Arc<dyn ImageSource>> {
+        match self.view {
+            BrowserView::Source(idx) => self.sources.get(idx),
+            _ => None,
+        }
+    }
+
+    /// Name of the collection being viewed, if any.
+    fn current_collection(&self) -> Option<&str> {
+        match &self.view {
+            BrowserView::Collection(name) => Some(name),
+            _ => None,
+        }
+    }
+
+    /// Get the provider an image came from.
+    fn source_for(&self, image: &ImageRecord) -> Option<Arc<dyn ImageSource>> {
+        self.sources.iter().find(|s| s.key() == image.source).cloned()
+    }
+
+    /// Where an image is downloaded, going by the library index.
+    fn downloaded_path(&self, image: &ImageRecord) -> Option<PathBuf> {
+        let source = self.source_for(image)?;
+        self.library.first_indexed(&source.download_candidates(image))
+    }
+
+    /// Other images the downloaded copy of an image duplicates.
+    fn duplicates_of(&self, image: &ImageRecord) -> Option<&Vec<String>> {
+        self.duplicates.get(&self.downloaded_path(image)?)
+    }
+
+    /// Display name of the current view.
+    fn source_name(&self) -> String {
+        match &self.view {
+            BrowserView::Source(_) => self.current_source().map(|s| s.name()).unwrap_or("?").to_string(),
+            BrowserView::All => "All sources".to_string(),
+            BrowserView::Favorites => "★ Favorites".to_string(),
+            BrowserView::Collection(name) => format!("▤ {}", name),
+        }
+    }
+
+    /// Banner for the status bar when listings come from the cache
+    /// because the network is unavailable (or offline mode is on).
+    pub fn network_status(&self) -> Option<String> {
+        let offline = self.sources.first().is_some_and(|s| s.config().jwst.offline);
+        let since = self
+            .sources
+            .iter()
+            .filter_map(|s| s.stale_since())
+            .min()
+            .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string());
+
+        match (offline, since) {
+            (true, Some(since)) => Some(format!("Offline mode · cached {}", since)),
+            (true, None) => Some("Offline mode".to_string()),
+            (false, Some(since)) => Some(format!("Offline · stale since {}", since)),
+            (false, None) => None,
         }
     }
 
@@ -69,72 +274,892 @@ impl BrowserScreen {
     pub async fn load_images(&mut self, force_refresh: bool) -> anyhow::Result<()> {
         self.loading = true;
         self.error = None;
+        self.next_page = 1;
+        self.more_rx = None;
+
+        let result = match self.view.clone() {
+            BrowserView::Source(_) => {
+                let source = self.current_source().cloned();
+                match source {
+                    Some(source) => {
+                        self.more_available = source.has_more();
+                        source.list_images(force_refresh).await
+                    }
+                    None => Ok(Vec::new()),
+                }
+            }
+            BrowserView::Favorites => {
+                self.more_available = false;
+                Ok(self.favorites.list().into_iter().map(|f| f.record).collect())
+            }
+            BrowserView::Collection(name) => {
+                self.more_available = false;
+                Ok(self.collections.get(&name).map(|c| c.items).unwrap_or_default())
+            }
+            BrowserView::All => {
+                // All sources: merge whatever loads, report what failed
+                self.more_available = false;
+                let mut images = Vec::new();
+                let mut failures = Vec::new();
+                for source in self.sources.clone() {
+                    match source.list_images(force_refresh).await {
+                        Ok(list) => images.extend(list),
+                        Err(e) => failures.push(format!("{}: {}", source.name(), e)),
+                    }
+                }
+                if images.is_empty() && !failures.is_empty() {
+                    Err(anyhow::anyhow!(failures.join("; ")))
+                } else {
+                    if !failures.is_empty() {
+                        self.error = Some(format!("Some sources failed: {}", failures.join("; ")));
+                    }
+                    Ok(images)
+                }
+            }
+        };
+
+        match result {
+            Ok(images) => {
+                self.images = images;
+                self.details_requested.clear();
+                if !self.images.is_empty() {
+                    self.list_state.select(Some(0));
+                }
+            }
+            Err(e) => {
+                self.images.clear();
+                self.error = Some(format!("Failed to load images: {}", e));
+            }
+        }
+        self.apply_view();
+
+        self.loading = false;
+        self.request_details();
+        if self.filters.needs_details() {
+            self.request_all_details();
+        }
+        Ok(())
+    }
+
+    /// Fetch extra metadata and the preview thumbnail for the selected image
+    /// in the background.
+    fn request_details(&mut self) {
+        if let Some(image) = self.selected_image().cloned() {
+            self.request_preview(&image);
+            self.request_details_for(image);
+        }
+    }
+
+    /// Download and decode the thumbnail of an image for the preview.
+    fn request_preview(&mut self, image: &ImageRecord) {
+        let key = image_key(image);
+        if !self.preview.enabled()
+            || self.preview.key() == Some(key.as_str())
+            || self.preview_requested.as_deref() == Some(key.as_str())
+        {
+            return;
+        }
+
+        let source = match self.source_for(image) {
+            Some(source) => source,
+            None => return,
+        };
+        self.preview_requested = Some(key.clone());
+        let image = image.clone();
+        let tx = self.preview_tx.clone();
+        tokio::spawn(async move {
+            let path = match source.download_thumbnail(&image).await {
+                Ok(path) => path,
+                Err(e) => {
+                    tracing::debug!("Failed to load thumbnail for {}: {}", image.id, e);
+                    return;
+                }
+            };
+            match tokio::task::spawn_blocking(move || image::open(path)).await {
+                Ok(Ok(thumbnail)) => {
+                    let _ = tx.send((key, thumbnail));
+                }
+                Ok(Err(e)) => tracing::debug!("Failed to decode thumbnail for {}: {}", image.id, e),
+                Err(e) => tracing::debug!("Thumbnail task failed: {}", e),
+            }
+        });
+    }
+
+    /// Show a thumbnail that finished loading if it's still selected.
+    fn poll_preview(&mut self) {
+        let selected = self.selected_image().map(image_key);
+        while let Ok((key, thumbnail)) = self.preview_rx.try_recv() {
+            if self.preview_requested.as_deref() == Some(key.as_str()) {
+                self.preview_requested = None;
+            }
+            if selected.as_deref() == Some(key.as_str()) {
+                self.preview.set_image(key, thumbnail);
+            }
+        }
+    }
+
+    /// Write preview graphics after a frame is drawn (see [`ImagePreview::flush`]).
+    pub fn flush_graphics(&mut self, out: &mut impl std::io::Write, visible: bool) -> anyhow::Result<bool> {
+        // The rendition picker pops up over the preview
+        self.preview.flush(out, visible && self.picker.is_none())
+    }
+
+    /// Fetch extra metadata for every loaded image (for category/instrument filters).
+    fn request_all_details(&mut self) {
+        for image in self.images.clone() {
+            self.request_details_for(image);
+        }
+    }
+
+    /// Fetch extra metadata for one image in the background.
+    fn request_details_for(&mut self, image: ImageRecord) {
+        if !self.details_requested.insert(image_key(&image)) {
+            return;
+        }
+
+        let source = match self.source_for(&image) {
+            Some(source) => source,
+            None => return,
+        };
+        let tx = self.details_tx.clone();
+        let limit = self.details_limit.clone();
+        tokio::spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            match source.load_details(&image).await {
+                Ok(Some(record)) => {
+                    let _ = tx.send(record);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::debug!("Failed to load details for {}: {}", image.id, e),
+            }
+        });
+    }
+
+    /// Merge any metadata that finished loading into the image list.
+    fn poll_details(&mut self) {
+        let mut updated = false;
+        while let Ok(record) = self.details_rx.try_recv() {
+            if let Some(image) = self
+                .images
+                .iter_mut()
+                .find(|i| i.source == record.source && i.id == record.id)
+            {
+                if self.favorites.is_favorite(&record.source, &record.id) {
+                    let _ = self.favorites.update(&record);
+                }
+                let _ = self.collections.update(&record);
+                *image = record;
+                updated = true;
+            }
+        }
+
+        // Newly loaded details can change search, filter and sort results
+        if updated
+            && (!self.search_query.is_empty()
+                || !self.filters.is_empty()
+                || self.sort != SortOrder::default())
+        {
+            self.apply_view();
+        }
+    }
+
+    /// Fetch older images from the source's archive in the background.
+    ///
+    /// Keeps paging until a page adds something new, since the first archive
+    /// pages overlap with the feed. Results are picked up by `poll_more`.
+    fn load_more_images(&mut self) {
+        if self.more_rx.is_some() || !self.more_available {
+            return;
+        }
+        let source = match self.current_source() {
+            Some(source) => source.clone(),
+            None => return,
+        };
+        self.error = None;
 
-        match self.source {
-            ImageSource::Esa => {
-                match self.esa_service.get_images(force_refresh).await {
-                    Ok(images) => {
-                        self.esa_images = images;
-                        if !self.esa_images.is_empty() {
-                            self.list_state.select(Some(0));
+        let mut seen: HashSet<String> = self.images.iter().map(|i| i.id.clone()).collect();
+        let mut page = self.next_page;
+        let (tx, rx) = oneshot::channel();
+        tokio::spawn(async move {
+            let mut more = MoreImages {
+                source: source.key().to_string(),
+                images: Vec::new(),
+                next_page: page,
+                more_available: true,
+                error: None,
+            };
+            while more.images.is_empty() {
+                match source.list_more(page).await {
+                    Ok(list) if list.is_empty() => {
+                        more.more_available = false;
+                        break;
+                    }
+                    Ok(list) => {
+                        page += 1;
+                        more.next_page = page;
+                        for image in list {
+                            if seen.insert(image.id.clone()) {
+                                more.images.push(image);
+                            }
                         }
                     }
                     Err(e) => {
-                        self.error = Some(format!("Failed to load images: {}", e));
+                        more.error = Some(format!("Failed to load more images: {}", e));
+                        break;
                     }
                 }
             }
-            ImageSource::JwstApi => {
-                // TODO: Implement JWST API loading
+            let _ = tx.send(more);
+        });
+        self.more_rx = Some(rx);
+    }
+
+    /// Append older images once a background archive fetch finishes.
+    fn poll_more(&mut self) {
+        let more = match self.more_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(more)) => more,
+            Some(Err(oneshot::error::TryRecvError::Empty)) | None => return,
+            Some(Err(oneshot::error::TryRecvError::Closed)) => {
+                self.more_rx = None;
+                return;
+            }
+        };
+        self.more_rx = None;
+
+        // The view may have moved on while the page was loading
+        if self.current_source().map(|s| s.key()) != Some(more.source.as_str()) {
+            return;
+        }
+        self.next_page = more.next_page;
+        self.more_available = more.more_available;
+        if more.error.is_some() {
+            self.error = more.error;
+        }
+        if !more.images.is_empty() {
+            self.images.extend(more.images);
+            self.apply_view();
+        }
+    }
+
+    /// Whether the browser is capturing text input (search, filter or
+    /// collection prompt) or has the rendition picker open.
+    pub fn is_text_input(&self) -> bool {
+        self.search_input || self.filter_input || self.collection_input || self.picker.is_some()
+    }
+
+    /// Recompute the visible list from the facet filters, search query and
+    /// sort order.
+    ///
+    /// Search matches against id, title and any loaded description. The
+    /// selected image stays selected if it is still visible.
+    fn apply_view(&mut self) {
+        let selected = self
+            .list_state
+            .selected()
+            .and_then(|i| self.visible.get(i).copied());
+
+        let candidates: Vec<usize> = (0..self.images.len())
+            .filter(|&idx| {
+                let image = &self.images[idx];
+                if self.filters.is_empty() {
+                    return true;
+                }
+                let downloaded = self.downloaded_path(image).is_some();
+                self.filters.matches(image, downloaded)
+            })
+            .collect();
+
+        self.hits.clear();
+        if self.search_query.trim().is_empty() {
+            self.visible = candidates;
+        } else {
+            self.visible.clear();
+            for idx in candidates {
+                let image = &self.images[idx];
+                let id_match = fuzzy_match(&self.search_query, &image.id);
+                let title_match = fuzzy_match(&self.search_query, &image.title);
+                let description_match = image
+                    .description
+                    .as_deref()
+                    .and_then(|d| word_match(&self.search_query, d));
+
+                let score = [&id_match, &title_match, &description_match]
+                    .iter()
+                    .filter_map(|m| m.as_ref().map(|m| m.score))
+                    .max();
+
+                if let Some(score) = score {
+                    self.visible.push(idx);
+                    self.hits.insert(
+                        idx,
+                        SearchHit {
+                            score,
+                            id_positions: id_match.map(|m| m.positions).unwrap_or_default(),
+                            title_positions: title_match.map(|m| m.positions).unwrap_or_default(),
+                        },
+                    );
+                }
+            }
+        }
+
+        let images = &self.images;
+        let sort = self.sort;
+        self.visible
+            .sort_by(|&a, &b| sort.compare(&images[a], &images[b]));
+
+        let position = selected
+            .and_then(|idx| self.visible.iter().position(|&v| v == idx))
+            .unwrap_or(0);
+        self.list_state.select(Some(position));
+    }
+
+    /// Jump to the next (or previous) best search match.
+    fn jump_match(&mut self, forward: bool) {
+        if self.hits.is_empty() {
+            return;
+        }
+
+        // Visible positions ordered best match first
+        let mut ranked: Vec<usize> = (0..self.visible.len()).collect();
+        ranked.sort_by_key(|&pos| {
+            std::cmp::Reverse(self.hits.get(&self.visible[pos]).map(|h| h.score).unwrap_or(0))
+        });
+
+        let current = self
+            .list_state
+            .selected()
+            .and_then(|sel| ranked.iter().position(|&pos| pos == sel));
+        let next = match current {
+            Some(i) if forward => (i + 1) % ranked.len(),
+            Some(i) => (i + ranked.len() - 1) % ranked.len(),
+            None => 0,
+        };
+        self.list_state.select(Some(ranked[next]));
+        self.request_details();
+    }
+
+    /// Handle a key while editing the filter line.
+    fn handle_filter_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.filter_input = false;
+            }
+            KeyCode::Enter => match FilterSet::parse(&self.filter_buffer) {
+                Ok(filters) => {
+                    self.filter_input = false;
+                    self.error = None;
+                    self.filters = filters;
+                    if self.filters.needs_details() {
+                        self.request_all_details();
+                    }
+                    self.apply_view();
+                    self.request_details();
+                }
+                Err(e) => {
+                    self.error = Some(format!("Invalid filter: {}", e));
+                }
+            },
+            KeyCode::Backspace => {
+                self.filter_buffer.pop();
+            }
+            KeyCode::Char(c) => {
+                self.filter_buffer.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a key while typing a collection name.
+    fn handle_collection_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.collection_input = false;
+            }
+            KeyCode::Enter => {
+                let name = self.collection_buffer.trim().to_string();
+                if name.is_empty() {
+                    return;
+                }
+                self.collection_input = false;
+                self.add_to_collection(&name);
             }
+            KeyCode::Tab => {
+                // Complete to the first collection starting with the input
+                let prefix = self.collection_buffer.to_lowercase();
+                if let Some(name) = self
+                    .collections
+                    .names()
+                    .into_iter()
+                    .find(|n| n.to_lowercase().starts_with(&prefix))
+                {
+                    self.collection_buffer = name;
+                }
+            }
+            KeyCode::Backspace => {
+                self.collection_buffer.pop();
+            }
+            KeyCode::Char(c) => {
+                self.collection_buffer.push(c);
+            }
+            _ => {}
+        }
+    }
+
+    /// Add the selected image to a collection (created if new).
+    fn add_to_collection(&mut self, name: &str) {
+        let image = match self.selected_image().cloned() {
+            Some(image) => image,
+            None => return,
+        };
+        match self.collections.add(name, &image) {
+            Ok(_) => {
+                self.error = None;
+                self.last_collection = Some(name.to_string());
+            }
+            Err(e) => self.error = Some(format!("Failed to save collection: {}", e)),
         }
+    }
 
+    /// Remove the selected image from the collection being viewed.
+    async fn remove_from_collection(&mut self) {
+        let name = match self.current_collection() {
+            Some(name) => name.to_string(),
+            None => return,
+        };
+        if let Some(image) = self.selected_image().cloned() {
+            if let Err(e) = self.collections.remove(&name, &image.source, &image.id) {
+                self.error = Some(format!("Failed to save collection: {}", e));
+                return;
+            }
+            let _ = self.load_images(false).await;
+        }
+    }
+
+    /// Apply the next wallpaper from the viewed collection's rotation.
+    async fn rotate_collection_wallpaper(&mut self) {
+        let name = match self.current_collection() {
+            Some(name) => name.to_string(),
+            None => {
+                self.error = Some("Switch to a collection (s) to rotate its wallpapers".to_string());
+                return;
+            }
+        };
+
+        self.loading = true;
+        match rotate_wallpaper(&self.collections, &name, &self.sources, &self.wallust_service).await {
+            Ok(image) => {
+                self.error = None;
+                if let Some(source) = self.source_for(&image) {
+                    self.library.add(&source.downloaded_files(&image), Some(&image));
+                }
+                if let Some(pos) = self
+                    .visible
+                    .iter()
+                    .position(|&idx| self.images[idx].source == image.source && self.images[idx].id == image.id)
+                {
+                    self.list_state.select(Some(pos));
+                }
+            }
+            Err(e) => self.error = Some(format!("Failed to apply wallpaper: {}", e)),
+        }
         self.loading = false;
-        Ok(())
+    }
+
+    /// Handle a key while typing in the search box.
+    fn handle_search_key(&mut self, key: KeyEvent) {
+        match key.code {
+            KeyCode::Esc => {
+                self.search_input = false;
+                self.search_query.clear();
+                self.apply_view();
+            }
+            KeyCode::Enter => {
+                self.search_input = false;
+            }
+            KeyCode::Backspace => {
+                self.search_query.pop();
+                self.apply_view();
+            }
+            KeyCode::Up => self.previous(),
+            KeyCode::Down => self.next(),
+            KeyCode::Char(c) => {
+                self.search_query.push(c);
+                self.apply_view();
+            }
+            _ => {}
+        }
+        self.request_details();
     }
 
     /// Get the currently selected image.
-    fn selected_image(&self) -> Option<&EsaImage> {
-        self.list_state.selected().and_then(|i| self.esa_images.get(i))
+    fn selected_image(&self) -> Option<&ImageRecord> {
+        self.list_state
+            .selected()
+            .and_then(|i| self.visible.get(i))
+            .and_then(|&idx| self.images.get(idx))
     }
 
-    /// Download the currently selected image (without applying as wallpaper).
-    async fn download_selected(&mut self) -> anyhow::Result<()> {
-        if let Some(image) = self.selected_image().cloned() {
-            self.loading = true;
-            self.error = None;
-            match self.esa_service.download_image(&image, "wallpaper_uhd").await {
-                Ok(path) => {
-                    self.last_downloaded = Some(path);
-                    // Don't auto-apply - user can press 'w' to apply
+    /// Queue the marked images (or the selected one) for background download.
+    fn download_selected(&mut self) {
+        let targets: Vec<ImageRecord> = if self.marked.is_empty() {
+            self.selected_image().cloned().into_iter().collect()
+        } else {
+            self.images
+                .iter()
+                .filter(|i| self.marked.contains(&image_key(i)))
+                .cloned()
+                .collect()
+        };
+
+        self.error = None;
+        for image in targets {
+            match self.source_for(&image) {
+                Some(source) => {
+                    self.downloads.enqueue(source, image, DEFAULT_RENDITION);
                 }
-                Err(e) => {
-                    self.error = Some(format!("Download failed: {}", e));
+                None => self.error = Some(format!("Unknown image source: {}", image.source)),
+            }
+        }
+        self.marked.clear();
+    }
+
+    /// Open the rendition picker for the selected image.
+    fn open_picker(&mut self) {
+        let Some(image) = self.selected_image().cloned() else {
+            return;
+        };
+        match self.source_for(&image) {
+            Some(source) => self.picker = Some(RenditionPicker::open(source, image)),
+            None => self.error = Some(format!("Unknown image source: {}", image.source)),
+        }
+    }
+
+    /// Handle keys while the rendition picker is open.
+    fn handle_picker_key(&mut self, key: KeyEvent) {
+        let Some(picker) = self.picker.as_mut() else {
+            return;
+        };
+        match key.code {
+            KeyCode::Up | KeyCode::Char('k') => picker.previous(),
+            KeyCode::Down | KeyCode::Char('j') => picker.next(),
+            KeyCode::Enter => {
+                if let Some(rendition) = picker.selected_rendition().map(str::to_string) {
+                    let picker = self.picker.take().unwrap();
+                    self.error = None;
+                    self.downloads.enqueue(picker.source, picker.image, &rendition);
                 }
             }
-            self.loading = false;
+            KeyCode::Esc | KeyCode::Char('q') => self.picker = None,
+            _ => {}
+        }
+    }
+
+    /// Record finished downloads, trimming the cache and analyzing the new
+    /// files once a batch is done (even if its last item failed).
+    fn poll_downloads(&mut self) {
+        let was_active = self.downloads.is_active();
+        let finished = self.downloads.poll();
+
+        // Don't auto-apply - user can press 'w' to apply
+        if let Some((_, path)) = finished.last() {
+            self.last_downloaded = Some(path.clone());
+        }
+        self.fresh_downloads.extend(finished.iter().map(|(_, path)| path.clone()));
+        if !finished.is_empty() && self.filters.downloaded != DownloadFilter::Any {
+            self.apply_view();
+        }
+        if was_active && !self.downloads.is_active() {
+            self.maintain_library(false);
+        }
+    }
+
+    /// Evict thumbnails and downloads past the configured size caps, then
+    /// analyze new library files and look for duplicates, in the
+    /// background. Favorites, the current wallpaper, the last download and
+    /// any others finished since the last upkeep are kept. With `rescan`, the library index is first synced with the
+    /// wallpaper directory.
+    pub fn maintain_library(&mut self, rescan: bool) {
+        let config = match self.sources.first() {
+            Some(source) => source.config().clone(),
+            None => return,
+        };
+        let sources = self.sources.clone();
+        let favorites = self.favorites.clone();
+        let wallust = self.wallust_service.clone();
+        let library = self.library.clone();
+        let mut fresh_downloads = std::mem::take(&mut self.fresh_downloads);
+        fresh_downloads.extend(self.last_downloaded.clone());
+
+        let (tx, rx) = oneshot::channel();
+        tokio::task::spawn_blocking(move || {
+            let mut protected = cache::protected_paths(&sources, &favorites, &wallust);
+            protected.extend(fresh_downloads.iter().filter_map(|p| p.canonicalize().ok()));
+            let usage = cache::scan(&config, &sources, &protected);
+            let evicted = cache::trim(&config, &usage);
+            library.remove(&evicted.removed);
+            if rescan {
+                library.rescan();
+            }
+            library.analyze();
+            let _ = tx.send(Upkeep {
+                evicted,
+                duplicates: library.duplicate_groups(),
+            });
+        });
+        self.upkeep_rx = Some(rx);
+    }
+
+    /// Pick up the result of the last library upkeep: report evictions in
+    /// the status line and flag duplicates. Only groups spanning more than
+    /// one image are flagged; renditions and crops of one image are
+    /// expected.
+    fn poll_upkeep(&mut self) {
+        let upkeep = match self.upkeep_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(upkeep)) => upkeep,
+            Some(Err(oneshot::error::TryRecvError::Empty)) | None => return,
+            Some(Err(oneshot::error::TryRecvError::Closed)) => Upkeep {
+                evicted: PurgeReport::default(),
+                duplicates: Vec::new(),
+            },
+        };
+        self.upkeep_rx = None;
+
+        if let Some((path, e)) = upkeep.evicted.failed.first() {
+            self.notice = Some(format!(
+                "Failed to evict {} cached files ({}: {})",
+                upkeep.evicted.failed.len(),
+                path.display(),
+                e
+            ));
+        } else if !upkeep.evicted.removed.is_empty() && self.notice.is_none() {
+            self.notice = Some(format!(
+                "Evicted {} cached files ({})",
+                upkeep.evicted.removed.len(),
+                human_size(upkeep.evicted.freed)
+            ));
+        }
+
+        self.duplicates.clear();
+        for group in upkeep.duplicates.iter().filter(|g| g.spans_images()) {
+            for entry in &group.entries {
+                let mut others: Vec<String> = Vec::new();
+                for other in &group.entries {
+                    let name = format!("{} {}", other.source, other.id);
+                    if (other.source != entry.source || other.id != entry.id) && !others.contains(&name) {
+                        others.push(name);
+                    }
+                }
+                self.duplicates.insert(entry.path.clone(), others);
+            }
+        }
+    }
+
+    /// Check every downloaded image in the background, quarantining corrupt
+    /// files.
+    fn verify_library(&mut self) {
+        if self.verify_rx.is_some() {
+            return;
+        }
+        let config = match self.sources.first() {
+            Some(source) => source.config().clone(),
+            None => return,
+        };
+
+        let library = self.library.clone();
+
+        let (tx, rx) = oneshot::channel();
+        tokio::task::spawn_blocking(move || {
+            let result = verify_library(&config);
+            if let Ok(report) = &result {
+                let paths: Vec<PathBuf> = report.quarantined.iter().map(|(path, _)| path.clone()).collect();
+                library.remove(&paths);
+            }
+            let _ = tx.send(result);
+        });
+        self.verify_rx = Some(rx);
+        self.notice = Some("Verifying library…".to_string());
+    }
+
+    /// Report a finished library check.
+    fn poll_verify(&mut self) {
+        let result = match self.verify_rx.as_mut().map(|rx| rx.try_recv()) {
+            Some(Ok(result)) => result,
+            Some(Err(oneshot::error::TryRecvError::Empty)) | None => return,
+            Some(Err(oneshot::error::TryRecvError::Closed)) => {
+                Err(anyhow::anyhow!("Library check stopped unexpectedly"))
+            }
+        };
+        self.verify_rx = None;
+
+        match result {
+            Ok(report) => {
+                self.notice = Some(format!(
+                    "Verified {} files, {} quarantined",
+                    report.checked,
+                    report.quarantined.len()
+                ));
+                if report.quarantined.is_empty() {
+                    self.error = None;
+                } else {
+                    let names: Vec<String> = report
+                        .quarantined
+                        .iter()
+                        .map(|(path, reason)| {
+                            let name = path.file_name().unwrap_or_default().to_string_lossy();
+                            format!("{} ({})", name, reason)
+                        })
+                        .collect();
+                    self.error = Some(format!("Quarantined corrupt files: {}", names.join("; ")));
+                }
+                // Quarantined files no longer count as downloaded
+                self.apply_view();
+            }
+            Err(e) => {
+                self.notice = None;
+                self.error = Some(format!("Failed to verify library: {}", e));
+            }
+        }
+    }
+
+    /// Mark or unmark the selected image.
+    fn toggle_mark(&mut self) {
+        if let Some(key) = self.selected_image().map(image_key) {
+            if !self.marked.remove(&key) {
+                self.marked.insert(key);
+            }
+        }
+    }
+
+    /// Positions covered by the active visual range.
+    fn visual_range(&self) -> Option<std::ops::RangeInclusive<usize>> {
+        let anchor = self.visual_anchor?;
+        let selected = self.list_state.selected().unwrap_or(0);
+        Some(anchor.min(selected)..=anchor.max(selected))
+    }
+
+    /// Mark every image in the visual range and leave visual mode.
+    fn commit_visual(&mut self) {
+        if let Some(range) = self.visual_range() {
+            for pos in range {
+                if let Some(&idx) = self.visible.get(pos) {
+                    self.marked.insert(image_key(&self.images[idx]));
+                }
+            }
+        }
+        self.visual_anchor = None;
+    }
+
+    /// Whether the image at a list position is marked or in the visual range.
+    fn is_marked(&self, pos: usize, image: &ImageRecord) -> bool {
+        self.visual_range().is_some_and(|r| r.contains(&pos)) || self.marked.contains(&image_key(image))
+    }
+
+    /// Draw the download batch (if any) at the bottom of `area`. Returns
+    /// the space left above it.
+    fn draw_downloads_below(&self, f: &mut Frame, area: Rect) -> Rect {
+        if self.downloads.items().is_empty() {
+            return area;
+        }
+        let height = (self.downloads.items().len() as u16 + 3).min(area.height / 3).max(4);
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(height)])
+            .split(area);
+        self.draw_downloads(f, parts[1]);
+        parts[0]
+    }
+
+    /// Draw the download batch: aggregate gauge plus one line per item.
+    fn draw_downloads(&self, f: &mut Frame, area: Rect) {
+        let (done, failed, total) = self.downloads.summary();
+        let title = if failed > 0 {
+            format!("Downloads {}/{} ({} failed)", done, total, failed)
+        } else {
+            format!("Downloads {}/{}", done, total)
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(0)])
+            .split(inner);
+
+        let in_flight = self.downloads.bytes_in_flight();
+        let label = if in_flight > 0 {
+            format!("{}/{} · {} in progress", done + failed, total, human_size(in_flight))
+        } else {
+            format!("{}/{}", done + failed, total)
+        };
+        let gauge = Gauge::default()
+            .gauge_style(Style::default().fg(if failed > 0 { Color::Yellow } else { Color::Green }))
+            .ratio(self.downloads.ratio())
+            .label(label);
+        f.render_widget(gauge, parts[0]);
+
+        // Running and failed items first, they're the interesting ones
+        let mut items: Vec<_> = self.downloads.items().iter().collect();
+        items.sort_by_key(|i| match i.state {
+            DownloadState::Running { .. } => 0,
+            DownloadState::Failed(_) => 1,
+            DownloadState::Queued => 2,
+            DownloadState::Done(_) => 3,
+        });
+        let lines: Vec<ListItem> = items
+            .iter()
+            .map(|item| {
+                let (symbol, detail, color) = match &item.state {
+                    DownloadState::Queued => ("…", String::new(), Color::DarkGray),
+                    DownloadState::Running { downloaded, total } => {
+                        let amount = match total {
+                            Some(total) if *total > 0 => format!(
+                                "{:>3}% {}/{} ",
+                                downloaded * 100 / total,
+                                human_size(*downloaded),
+                                human_size(*total)
+                            ),
+                            _ => format!("{} ", human_size(*downloaded)),
+                        };
+                        ("↓", amount, Color::Cyan)
+                    }
+                    DownloadState::Done(_) => ("✓", String::new(), Color::Green),
+                    DownloadState::Failed(e) => ("✗", format!(": {}", e), Color::Red),
+                };
+                // Progress goes before the title, errors after it
+                let (before, after) = match item.state {
+                    DownloadState::Failed(_) => (String::new(), detail),
+                    _ => (detail, String::new()),
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("{} ", symbol), Style::default().fg(color)),
+                    Span::styled(before, Style::default().fg(color)),
+                    Span::raw(item.title.clone()),
+                    Span::styled(after, Style::default().fg(Color::Red)),
+                ]))
+            })
+            .collect();
+        f.render_widget(List::new(lines), parts[1]);
+    }
+
+    /// Star or unstar the selected image.
+    fn toggle_favorite(&mut self) {
+        if let Some(image) = self.selected_image().cloned() {
+            if let Err(e) = self.favorites.toggle(&image) {
+                self.error = Some(format!("Failed to save favorites: {}", e));
+            }
         }
-        Ok(())
     }
 
     /// Get the best reference image path for the generator.
     /// Priority: currently selected image's download path > last_downloaded
     pub fn get_reference_image_path(&self) -> Option<PathBuf> {
         // First, check if the currently selected image is downloaded
-        if let Some(image) = self.selected_image() {
-            eprintln!("[DEBUG] Selected image ID: {}", image.id);
-            if let Some(path) = self.esa_service.get_downloaded_path(image) {
-                eprintln!("[DEBUG] Found downloaded path: {:?}", path);
-                return Some(path);
-            } else {
-                eprintln!("[DEBUG] No downloaded path found for selected image");
-            }
-        } else {
-            eprintln!("[DEBUG] No image selected");
+        if let Some(path) = self.selected_image().and_then(|image| self.downloaded_path(image)) {
+            return Some(path);
         }
 
         // Fallback to last explicitly downloaded image
-        eprintln!("[DEBUG] Falling back to last_downloaded: {:?}", self.last_downloaded);
         self.last_downloaded.clone()
     }
 
@@ -142,7 +1167,7 @@ impl BrowserScreen {
     fn apply_as_wallpaper(&mut self) {
         // First check if we have a downloaded path for the selected image
         if let Some(image) = self.selected_image() {
-            if let Some(path) = self.esa_service.get_downloaded_path(image) {
+            if let Some(path) = self.downloaded_path(image) {
                 if let Err(e) = self.wallust_service.apply_wallpaper(&path) {
                     self.error = Some(format!("Failed to apply wallpaper: {}", e));
                 } else {
@@ -164,12 +1189,153 @@ impl BrowserScreen {
         }
     }
 
+    /// Switch to the next image source, then the all-sources, favorites and
+    /// collection views.
+    async fn next_source(&mut self) {
+        let names = self.collections.names();
+        self.view = match &self.view {
+            BrowserView::Source(idx) if idx + 1 < self.sources.len() => BrowserView::Source(idx + 1),
+            BrowserView::Source(_) => BrowserView::All,
+            BrowserView::All => BrowserView::Favorites,
+            BrowserView::Favorites => match names.first() {
+                Some(name) => BrowserView::Collection(name.clone()),
+                None => BrowserView::Source(0),
+            },
+            BrowserView::Collection(current) => {
+                let next = names.iter().position(|n| n == current).and_then(|i| names.get(i + 1));
+                match next {
+                    Some(name) => BrowserView::Collection(name.clone()),
+                    None => BrowserView::Source(0),
+                }
+            }
+        };
+        self.list_state.select(Some(0));
+        let _ = self.load_images(false).await;
+    }
+
+    /// Move selection by `delta` in the grid, stopping at the ends.
+    fn move_in_grid(&mut self, delta: isize) {
+        let last = self.visible.len().saturating_sub(1) as isize;
+        let current = self.list_state.selected().unwrap_or(0) as isize;
+        self.list_state.select(Some((current + delta).clamp(0, last) as usize));
+    }
+
+    /// Title for the image list or grid.
+    fn list_title(&self) -> String {
+        let mut title = if self.more_rx.is_some() {
+            format!("Images ({}, {} loaded, loading more…)", self.source_name(), self.images.len())
+        } else if self.more_available {
+            format!("Images ({}, {} loaded, ↓ for more)", self.source_name(), self.images.len())
+        } else {
+            format!("Images ({})", self.source_name())
+        };
+        if self.sort != SortOrder::default() {
+            title.push_str(&format!(" ↕{}", self.sort.name()));
+        }
+        if self.collection_input {
+            title.push_str(&format!(" add to collection: {}▏", self.collection_buffer));
+        } else if self.filter_input {
+            title.push_str(&format!(" filter: {}▏", self.filter_buffer));
+        } else if !self.filters.is_empty() {
+            title.push_str(&format!(" [{}]", self.filters.describe()));
+        }
+        if self.search_input || !self.search_query.is_empty() {
+            title.push_str(&format!(
+                " /{}{} [{}/{}]",
+                self.search_query,
+                if self.search_input { "▏" } else { "" },
+                self.visible.len(),
+                self.images.len()
+            ));
+        }
+        if let Some(notice) = &self.notice {
+            title.push_str(&format!(" — {}", notice));
+        }
+        title
+    }
+
+    /// Draw the rendition picker (if open) over `area`.
+    fn draw_picker(&mut self, f: &mut Frame, area: Rect) {
+        if let Some(picker) = self.picker.as_mut() {
+            picker.poll();
+            picker.render(f, area);
+        }
+    }
+
+    /// Draw the thumbnail grid across the whole area.
+    fn draw_grid(&mut self, f: &mut Frame, area: Rect) {
+        let area = self.draw_downloads_below(f, area);
+
+        let mut title = self.list_title();
+        if let Some(image) = self.selected_image() {
+            title.push_str(&format!(" — {}", image.title));
+        }
+        if let Some(error) = &self.error {
+            title.push_str(&format!(" — {}", error));
+        }
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(title)
+            .title_bottom(Line::from(vec![
+                Span::styled("[←↑↓→]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" Nav "),
+                Span::styled("[Enter]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" DL "),
+                Span::styled("[w]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" Wallpaper "),
+                Span::styled("[*]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" Star "),
+                Span::styled("[v]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" List"),
+            ]));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        if self.loading {
+            let loading = Paragraph::new("Loading...").style(Style::default().fg(Color::Yellow));
+            f.render_widget(loading, inner);
+            return;
+        }
+
+        let selected = self.list_state.selected().unwrap_or(0);
+        let range = self.grid.layout(inner, selected, self.visible.len());
+
+        // Lazily fetch thumbnails for the cells on screen
+        for pos in range.clone() {
+            let image = &self.images[self.visible[pos]];
+            let key = image_key(image);
+            if !self.grid.is_requested(&key) {
+                if let Some(source) = self.source_for(image) {
+                    self.grid.request(key, source, image.clone());
+                }
+            }
+        }
+
+        let cells: Vec<GridCell> = range
+            .clone()
+            .map(|pos| {
+                let image = &self.images[self.visible[pos]];
+                GridCell {
+                    key: image_key(image),
+                    title: &image.title,
+                    downloaded: self.downloaded_path(image).is_some(),
+                    duplicate: self.duplicates_of(image).is_some(),
+                    favorite: self.favorites.is_favorite(&image.source, &image.id),
+                    marked: self.is_marked(pos, image),
+                }
+            })
+            .collect();
+        self.grid
+            .render(f, inner, &cells, selected.checked_sub(range.start));
+    }
+
     /// Move selection up.
     fn previous(&mut self) {
         let i = match self.list_state.selected() {
             Some(i) => {
                 if i == 0 {
-                    self.esa_images.len().saturating_sub(1)
+                    self.visible.len().saturating_sub(1)
                 } else {
                     i - 1
                 }
@@ -183,7 +1349,7 @@ impl BrowserScreen {
     fn next(&mut self) {
         let i = match self.list_state.selected() {
             Some(i) => {
-                if i >= self.esa_images.len().saturating_sub(1) {
+                if i >= self.visible.len().saturating_sub(1) {
                     0
                 } else {
                     i + 1
@@ -198,6 +1364,20 @@ impl BrowserScreen {
 #[async_trait]
 impl Screen for BrowserScreen {
     fn draw(&mut self, f: &mut Frame, area: Rect) {
+        self.poll_details();
+        self.poll_preview();
+        self.poll_downloads();
+        self.poll_verify();
+        self.poll_upkeep();
+        self.poll_more();
+        self.grid.poll();
+
+        if self.grid_mode {
+            self.draw_grid(f, area);
+            self.draw_picker(f, area);
+            return;
+        }
+
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([
@@ -206,47 +1386,95 @@ impl Screen for BrowserScreen {
             ])
             .split(area);
 
-        // Left side: Image list
+        // Left side: Image list, with the download batch below it
+        let list_area = self.draw_downloads_below(f, chunks[0]);
+
         let items: Vec<ListItem> = self
-            .esa_images
+            .visible
             .iter()
-            .map(|img| {
-                let downloaded = self.esa_service.is_downloaded(img);
-                let marker = if downloaded { "✓ " } else { "  " };
+            .enumerate()
+            .map(|(pos, &idx)| {
+                let img = &self.images[idx];
+                let hit = self.hits.get(&idx);
+                let downloaded = self.downloaded_path(img).is_some();
+                let marker = if self.downloads.is_pending(&image_key(img)) {
+                    "↓"
+                } else if self.duplicates_of(img).is_some() {
+                    "≈"
+                } else if downloaded {
+                    "✓"
+                } else {
+                    " "
+                };
+                let star = if self.favorites.is_favorite(&img.source, &img.id) {
+                    "★ "
+                } else {
+                    "  "
+                };
+                let mark = if self.is_marked(pos, img) { "●" } else { " " };
 
-                let date = img
-                    .pub_date
-                    .map(|d| d.format("%Y-%m-%d").to_string())
-                    .unwrap_or_else(|| "Unknown".to_string());
+                let mut spans = vec![
+                    Span::styled(mark, Style::default().fg(Color::Magenta)),
+                    Span::styled(marker, Style::default().fg(Color::Green)),
+                    Span::styled(star, Style::default().fg(Color::Yellow)),
+                ];
+                spans.extend(highlight_spans(
+                    &img.id,
+                    hit.map(|h| h.id_positions.as_slice()).unwrap_or(&[]),
+                    Style::default().fg(Color::Cyan),
+                ));
+                spans.push(Span::raw(" - "));
 
-                let content = format!("{}{} - {}", marker, img.id, date);
+                // Show the title while searching so title matches are visible
+                match hit.filter(|h| !h.title_positions.is_empty()) {
+                    Some(hit) => spans.extend(highlight_spans(
+                        &img.title,
+                        &hit.title_positions,
+                        Style::default().fg(Color::DarkGray),
+                    )),
+                    None => {
+                        let secondary = img
+                            .date
+                            .map(|d| d.format("%Y-%m-%d").to_string())
+                            .unwrap_or_else(|| img.title.clone());
+                        spans.push(Span::styled(secondary, Style::default().fg(Color::DarkGray)));
+                    }
+                }
 
-                ListItem::new(Line::from(vec![
-                    Span::styled(marker, Style::default().fg(Color::Green)),
-                    Span::styled(img.id.clone(), Style::default().fg(Color::Cyan)),
-                    Span::raw(" - "),
-                    Span::styled(date, Style::default().fg(Color::DarkGray)),
-                ]))
+                ListItem::new(Line::from(spans))
             })
             .collect();
 
-        let source_name = match self.source {
-            ImageSource::Esa => "ESA/Webb Gallery",
-            ImageSource::JwstApi => "JWST API",
-        };
+        let title = self.list_title();
 
         let list = List::new(items)
             .block(
                 Block::default()
                     .borders(Borders::ALL)
-                    .title(format!("Images ({})", source_name))
+                    .title(title)
                     .title_bottom(Line::from(vec![
                         Span::styled("[↑/↓]", Style::default().fg(Color::DarkGray)),
                         Span::raw(" Nav "),
-                        Span::styled("[Enter]", Style::default().fg(Color::DarkGray)),
+                        Span::styled("[Enter/d]", Style::default().fg(Color::DarkGray)),
                         Span::raw(" DL "),
+                        Span::styled("[Space/V]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Mark "),
                         Span::styled("[w]", Style::default().fg(Color::DarkGray)),
                         Span::raw(" Wallpaper "),
+                        Span::styled("[/]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Search "),
+                        Span::styled("[f]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Filter "),
+                        Span::styled("[o]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Sort "),
+                        Span::styled("[*]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Star "),
+                        Span::styled("[c]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Collect "),
+                        Span::styled("[s]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Source "),
+                        Span::styled("[v]", Style::default().fg(Color::DarkGray)),
+                        Span::raw(" Grid "),
                         Span::styled("[r]", Style::default().fg(Color::DarkGray)),
                         Span::raw(" Refresh"),
                     ])),
@@ -258,7 +1486,7 @@ impl Screen for BrowserScreen {
             )
             .highlight_symbol("► ");
 
-        f.render_stateful_widget(list, chunks[0], &mut self.list_state);
+        f.render_stateful_widget(list, list_area, &mut self.list_state);
 
         // Right side: Image details
         let detail_block = Block::default()
@@ -278,17 +1506,17 @@ impl Screen for BrowserScreen {
             f.render_widget(error_widget, chunks[1]);
         } else if let Some(image) = self.selected_image() {
             let date = image
-                .pub_date
+                .date
                 .map(|d| d.format("%Y-%m-%d %H:%M").to_string())
                 .unwrap_or_else(|| "Unknown".to_string());
 
-            let downloaded = if self.esa_service.is_downloaded(image) {
+            let downloaded = if self.downloaded_path(image).is_some() {
                 "Yes"
             } else {
                 "No"
             };
 
-            let details = vec![
+            let mut details = vec![
                 Line::from(vec![
                     Span::styled("ID: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(&image.id, Style::default().fg(Color::Cyan)),
@@ -306,47 +1534,169 @@ impl Screen for BrowserScreen {
                     Span::styled("Published: ", Style::default().fg(Color::DarkGray)),
                     Span::styled(date, Style::default().fg(Color::White)),
                 ]),
-                Line::from(""),
-                Line::from(vec![
-                    Span::styled("Downloaded: ", Style::default().fg(Color::DarkGray)),
-                    Span::styled(
-                        downloaded,
-                        if downloaded == "Yes" {
-                            Style::default().fg(Color::Green)
-                        } else {
-                            Style::default().fg(Color::Yellow)
-                        },
-                    ),
-                ]),
-                Line::from(""),
-                Line::from(vec![
+            ];
+
+            if self.favorites.is_favorite(&image.source, &image.id) {
+                details.push(Line::from(Span::styled(
+                    "★ Favorite",
+                    Style::default().fg(Color::Yellow),
+                )));
+            }
+
+            let in_collections = self.collections.containing(&image.source, &image.id);
+            if !in_collections.is_empty() {
+                details.push(Line::from(vec![
+                    Span::styled("Collections: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(in_collections.join(", "), Style::default().fg(Color::Magenta)),
+                ]));
+            }
+
+            let typed = [
+                ("Instruments", Some(image.instruments.join(", "))),
+                ("Categories", Some(image.categories.join(", "))),
+                ("Dimensions", image.dimensions.map(|(w, h)| format!("{}x{}", w, h))),
+            ];
+            let typed = typed
+                .into_iter()
+                .filter_map(|(label, value)| Some((label.to_string(), value.filter(|v| !v.is_empty())?)));
+            for (label, value) in image.fields.iter().cloned().chain(typed) {
+                details.push(Line::from(vec![
+                    Span::styled(format!("{}: ", label), Style::default().fg(Color::DarkGray)),
+                    Span::styled(value, Style::default().fg(Color::White)),
+                ]));
+            }
+
+            if let Some(credit) = &image.credit {
+                details.push(Line::from(vec![
+                    Span::styled("Credit: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(credit, Style::default().fg(Color::White)),
+                ]));
+            }
+
+            details.push(Line::from(""));
+            details.push(Line::from(vec![
+                Span::styled("Downloaded: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(
+                    downloaded,
+                    if downloaded == "Yes" {
+                        Style::default().fg(Color::Green)
+                    } else {
+                        Style::default().fg(Color::Yellow)
+                    },
+                ),
+            ]));
+            if let Some(others) = self.duplicates_of(image) {
+                details.push(Line::from(vec![
+                    Span::styled("Duplicates: ", Style::default().fg(Color::DarkGray)),
+                    Span::styled(others.join(", "), Style::default().fg(Color::Yellow)),
+                ]));
+            }
+
+            if let Some(url) = &image.page_url {
+                details.push(Line::from(""));
+                details.push(Line::from(vec![
                     Span::styled("Gallery URL: ", Style::default().fg(Color::DarkGray)),
-                ]),
-                Line::from(Span::styled(
-                    image.gallery_url(),
+                ]));
+                details.push(Line::from(Span::styled(
+                    url,
                     Style::default().fg(Color::Blue),
-                )),
-            ];
+                )));
+            }
 
-            let detail = Paragraph::new(details)
-                .block(detail_block)
-                .wrap(Wrap { trim: true });
-            f.render_widget(detail, chunks[1]);
+            if let Some(description) = &image.description {
+                details.push(Line::from(""));
+                details.push(Line::from(Span::styled(
+                    description,
+                    Style::default().fg(Color::Gray),
+                )));
+            }
+
+            if self.preview.has_image(&image_key(image)) {
+                // Thumbnail on top, text below
+                let inner = detail_block.inner(chunks[1]);
+                let parts = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Percentage(45), Constraint::Min(0)])
+                    .split(inner);
+                let detail = Paragraph::new(details).wrap(Wrap { trim: true });
+                f.render_widget(detail_block, chunks[1]);
+                f.render_widget(detail, parts[1]);
+                self.preview.render(f, parts[0]);
+            } else {
+                let detail = Paragraph::new(details)
+                    .block(detail_block)
+                    .wrap(Wrap { trim: true });
+                f.render_widget(detail, chunks[1]);
+            }
         } else {
             let empty = Paragraph::new("No image selected")
                 .block(detail_block)
                 .style(Style::default().fg(Color::DarkGray));
             f.render_widget(empty, chunks[1]);
         }
+
+        self.draw_picker(f, area);
     }
 
     async fn handle_key(&mut self, key: KeyEvent) {
+        if self.picker.is_some() {
+            self.handle_picker_key(key);
+            return;
+        }
+        if self.search_input {
+            self.handle_search_key(key);
+            return;
+        }
+        if self.filter_input {
+            self.handle_filter_key(key);
+            return;
+        }
+        if self.collection_input {
+            self.handle_collection_key(key);
+            return;
+        }
+
         match key.code {
-            KeyCode::Up | KeyCode::Char('k') => self.previous(),
-            KeyCode::Down | KeyCode::Char('j') => self.next(),
+            KeyCode::Up | KeyCode::Char('k') if self.grid_mode => {
+                self.move_in_grid(-(self.grid.columns() as isize));
+                self.request_details();
+            }
+            KeyCode::Down | KeyCode::Char('j') if self.grid_mode => {
+                // Moving past the last row loads older images when available
+                let columns = self.grid.columns();
+                let selected = self.list_state.selected().unwrap_or(0);
+                if selected + columns >= self.visible.len() {
+                    self.load_more_images();
+                }
+                self.move_in_grid(columns as isize);
+                self.request_details();
+            }
+            KeyCode::Left | KeyCode::Char('h') if self.grid_mode => {
+                self.move_in_grid(-1);
+                self.request_details();
+            }
+            KeyCode::Right | KeyCode::Char('l') if self.grid_mode => {
+                self.move_in_grid(1);
+                self.request_details();
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.previous();
+                self.request_details();
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                // Scrolling past the end loads older images when available
+                let at_end = self.list_state.selected() == Some(self.visible.len().saturating_sub(1));
+                if at_end {
+                    self.load_more_images();
+                }
+                self.next();
+                self.request_details();
+            }
             KeyCode::Enter => {
-                let _ = self.download_selected().await;
+                self.commit_visual();
+                self.download_selected();
             }
+            KeyCode::Char('d') => self.open_picker(),
             KeyCode::Char('w') => {
                 // Apply as wallpaper (runs in background)
                 self.apply_as_wallpaper();
@@ -355,17 +1705,99 @@ impl Screen for BrowserScreen {
                 let _ = self.load_images(true).await;
             }
             KeyCode::Char('s') => {
-                // Toggle source
-                self.source = match self.source {
-                    ImageSource::Esa => ImageSource::JwstApi,
-                    ImageSource::JwstApi => ImageSource::Esa,
-                };
-                let _ = self.load_images(false).await;
+                // Cycle source
+                self.next_source().await;
+            }
+            KeyCode::Char('/') => {
+                self.search_input = true;
+            }
+            KeyCode::Char('*') => self.toggle_favorite(),
+            KeyCode::Char('f') => {
+                self.filter_buffer = self.filters.describe();
+                self.filter_input = true;
+            }
+            KeyCode::Char('c') => {
+                self.collection_buffer = self
+                    .current_collection()
+                    .map(str::to_string)
+                    .or_else(|| self.last_collection.clone())
+                    .unwrap_or_default();
+                self.collection_input = true;
+            }
+            KeyCode::Char('x') => self.remove_from_collection().await,
+            KeyCode::Char('W') => self.rotate_collection_wallpaper().await,
+            KeyCode::Char('L') => self.verify_library(),
+            KeyCode::Char('v') => {
+                self.grid_mode = !self.grid_mode;
+            }
+            KeyCode::Char('o') => {
+                self.sort = self.sort.next();
+                self.apply_view();
+            }
+            KeyCode::Char('n') => self.jump_match(true),
+            KeyCode::Char('N') => self.jump_match(false),
+            KeyCode::Esc => {
+                // Back out one thing at a time
+                if self.visual_anchor.is_some() {
+                    self.visual_anchor = None;
+                } else if !self.search_query.is_empty() {
+                    self.search_query.clear();
+                    self.apply_view();
+                } else if !self.marked.is_empty() {
+                    self.marked.clear();
+                } else {
+                    self.downloads.clear_finished();
+                }
             }
             KeyCode::Char(' ') => {
-                self.show_detail = !self.show_detail;
+                if self.visual_anchor.is_some() {
+                    self.commit_visual();
+                } else {
+                    self.toggle_mark();
+                    self.next();
+                    self.request_details();
+                }
+            }
+            KeyCode::Char('V') => {
+                if self.visual_anchor.is_some() {
+                    self.commit_visual();
+                } else {
+                    self.visual_anchor = self.list_state.selected();
+                }
             }
             _ => {}
         }
     }
 }
+
+/// Identify an image across sources.
+fn image_key(image: &ImageRecord) -> String {
+    format!("{}:{}", image.source, image.id)
+}
+
+/// Split text into spans, highlighting the characters at `positions`.
+fn highlight_spans(text: &str, positions: &[usize], base: Style) -> Vec<Span<'static>> {
+    if positions.is_empty() {
+        return vec![Span::styled(text.to_string(), base)];
+    }
+
+    let highlight = base.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let mut spans = Vec::new();
+    let mut current = String::new();
+    let mut current_hit = false;
+
+    for (i, c) in text.chars().enumerate() {
+        let hit = positions.contains(&i);
+        if hit != current_hit && !current.is_empty() {
+            let style = if current_hit { highlight } else { base };
+            spans.push(Span::styled(std::mem::take(&mut current), style));
+        }
+        current_hit = hit;
+        current.push(c);
+    }
+    if !current.is_empty() {
+        spans.push(Span::styled(current, if current_hit { highlight } else { base }));
+    }
+
+    spans
+}
@@ -0,0 +1,235 @@
+//! Facet filters and sort orders for the browser list.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use std::cmp::Ordering;
+
+use crate::services::ImageRecord;
+
+/// Downloaded-state facet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DownloadFilter {
+    #[default]
+    Any,
+    Downloaded,
+    NotDownloaded,
+}
+
+/// Active facet filters.
+///
+/// Edited as a line of `key:value` terms, e.g.
+/// `after:2024-01-01 before:2024-06-30 dl:no src:esa cat:nebulae inst:miri`.
+#[derive(Debug, Clone, Default)]
+pub struct FilterSet {
+    pub after: Option<NaiveDate>,
+    pub before: Option<NaiveDate>,
+    pub downloaded: DownloadFilter,
+    pub source: Option<String>,
+    pub category: Option<String>,
+    pub instrument: Option<String>,
+}
+
+impl FilterSet {
+    /// Parse a filter line. Unknown or malformed terms are reported as errors.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut filters = Self::default();
+
+        for term in line.split_whitespace() {
+            let (key, value) = term
+                .split_once(':')
+                .ok_or_else(|| format!("Expected key:value, got '{}'", term))?;
+            let value = value.trim();
+            if value.is_empty() {
+                return Err(format!("Missing value for '{}'", key));
+            }
+
+            match key.to_lowercase().as_str() {
+                "after" | "from" => filters.after = Some(parse_date(value)?),
+                "before" | "to" => filters.before = Some(parse_date(value)?),
+                "dl" | "downloaded" => {
+                    filters.downloaded = match value.to_lowercase().as_str() {
+                        "yes" | "y" | "true" => DownloadFilter::Downloaded,
+                        "no" | "n" | "false" => DownloadFilter::NotDownloaded,
+                        "any" | "all" => DownloadFilter::Any,
+                        _ => return Err(format!("dl: expects yes/no, got '{}'", value)),
+                    }
+                }
+                "src" | "source" => filters.source = Some(value.to_lowercase()),
+                "cat" | "category" => filters.category = Some(value.to_lowercase()),
+                "inst" | "instrument" => filters.instrument = Some(value.to_lowercase()),
+                _ => return Err(format!("Unknown filter '{}'", key)),
+            }
+        }
+
+        Ok(filters)
+    }
+
+    /// Check whether no filter is active.
+    pub fn is_empty(&self) -> bool {
+        self.after.is_none()
+            && self.before.is_none()
+            && self.downloaded == DownloadFilter::Any
+            && self.source.is_none()
+            && self.category.is_none()
+            && self.instrument.is_none()
+    }
+
+    /// Whether a category or instrument filter needs per-image details.
+    pub fn needs_details(&self) -> bool {
+        self.category.is_some() || self.instrument.is_some()
+    }
+
+    /// Format the active filters back into filter-line syntax.
+    pub fn describe(&self) -> String {
+        let mut terms = Vec::new();
+        if let Some(after) = self.after {
+            terms.push(format!("after:{}", after));
+        }
+        if let Some(before) = self.before {
+            terms.push(format!("before:{}", before));
+        }
+        match self.downloaded {
+            DownloadFilter::Any => {}
+            DownloadFilter::Downloaded => terms.push("dl:yes".to_string()),
+            DownloadFilter::NotDownloaded => terms.push("dl:no".to_string()),
+        }
+        if let Some(source) = &self.source {
+            terms.push(format!("src:{}", source));
+        }
+        if let Some(category) = &self.category {
+            terms.push(format!("cat:{}", category));
+        }
+        if let Some(instrument) = &self.instrument {
+            terms.push(format!("inst:{}", instrument));
+        }
+        terms.join(" ")
+    }
+
+    /// Check whether an image passes the filters.
+    ///
+    /// Images without a date never pass a date filter; images whose details
+    /// haven't loaded never pass a category or instrument filter.
+    pub fn matches(&self, image: &ImageRecord, downloaded: bool) -> bool {
+        let date = image.date.map(|d| d.date_naive());
+        if let Some(after) = self.after {
+            if date.is_none_or(|d| d < after) {
+                return false;
+            }
+        }
+        if let Some(before) = self.before {
+            if date.is_none_or(|d| d > before) {
+                return false;
+            }
+        }
+
+        match self.downloaded {
+            DownloadFilter::Downloaded if !downloaded => return false,
+            DownloadFilter::NotDownloaded if downloaded => return false,
+            _ => {}
+        }
+
+        if let Some(source) = &self.source {
+            if !image.source.to_lowercase().contains(source.as_str()) {
+                return false;
+            }
+        }
+        if let Some(category) = &self.category {
+            if !contains_ci(&image.categories, category) {
+                return false;
+            }
+        }
+        if let Some(instrument) = &self.instrument {
+            if !contains_ci(&image.instruments, instrument) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+/// Check whether any value contains the (lowercase) needle.
+fn contains_ci(values: &[String], needle: &str) -> bool {
+    values.iter().any(|v| v.to_lowercase().contains(needle))
+}
+
+/// Parse `YYYY-MM-DD`, `YYYY-MM` or `YYYY` (partial dates start the period).
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d"))
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01-01", value), "%Y-%m-%d"))
+        .map_err(|_| format!("Invalid date '{}'", value))
+}
+
+/// Sort order for the browser list.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    Oldest,
+    Title,
+    Resolution,
+    AspectRatio,
+}
+
+impl SortOrder {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+            Self::Title => "title",
+            Self::Resolution => "resolution",
+            Self::AspectRatio => "aspect",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Newest => Self::Oldest,
+            Self::Oldest => Self::Title,
+            Self::Title => Self::Resolution,
+            Self::Resolution => Self::AspectRatio,
+            Self::AspectRatio => Self::Newest,
+        }
+    }
+
+    /// Compare two images. Unknown values always sort last.
+    pub fn compare(&self, a: &ImageRecord, b: &ImageRecord) -> Ordering {
+        match self {
+            Self::Newest => desc_known(a.date, b.date),
+            Self::Oldest => asc_known::<DateTime<Utc>>(a.date, b.date),
+            Self::Title => a.title.to_lowercase().cmp(&b.title.to_lowercase()),
+            Self::Resolution => desc_known(
+                a.dimensions.map(|(w, h)| w as u64 * h as u64),
+                b.dimensions.map(|(w, h)| w as u64 * h as u64),
+            ),
+            Self::AspectRatio => {
+                let ratio = |img: &ImageRecord| {
+                    img.dimensions
+                        .filter(|&(_, h)| h > 0)
+                        .map(|(w, h)| (w as u64 * 10_000) / h as u64)
+                };
+                desc_known(ratio(a), ratio(b))
+            }
+        }
+    }
+}
+
+/// Descending order with `None` last.
+fn desc_known<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+/// Ascending order with `None` last.
+fn asc_known<T: Ord>(a: Option<T>, b: Option<T>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
@@ -0,0 +1,237 @@
+//! Thumbnail grid layout for the browser.
+//!
+//! Cells are drawn with unicode half-blocks so the grid works in any
+//! terminal. Thumbnails are fetched lazily for the rows on screen.
+
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph},
+    Frame,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, Semaphore};
+
+use super::preview::half_blocks;
+use crate::services::{ImageRecord, ImageSource};
+
+/// Cell size in terminal cells, including the border.
+const CELL_WIDTH: u16 = 24;
+const CELL_HEIGHT: u16 = 12;
+
+/// Maximum concurrent thumbnail downloads for the grid.
+const THUMBNAIL_FETCH_LIMIT: usize = 6;
+
+/// How long a failed thumbnail shows as failed before it is tried again.
+const RETRY_AFTER: Duration = Duration::from_secs(30);
+
+/// Rendered thumbnail for an image key, or None if it failed to load.
+type ThumbnailResult = (String, Option<Vec<Line<'static>>>);
+
+/// One grid cell to draw.
+pub struct GridCell<'a> {
+    pub key: String,
+    pub title: &'a str,
+    pub downloaded: bool,
+    /// Downloaded copy duplicates another image
+    pub duplicate: bool,
+    pub favorite: bool,
+    /// Marked for a batch download
+    pub marked: bool,
+}
+
+/// Grid state: rendered thumbnails and scroll position.
+pub struct ThumbnailGrid {
+    /// Half-block renderings by image key
+    thumbs: HashMap<String, Vec<Line<'static>>>,
+    requested: HashSet<String>,
+    /// Thumbnails that failed to load, and when
+    failed: HashMap<String, Instant>,
+    tx: mpsc::UnboundedSender<ThumbnailResult>,
+    rx: mpsc::UnboundedReceiver<ThumbnailResult>,
+    limit: Arc<Semaphore>,
+    /// First visible row
+    offset: usize,
+    columns: usize,
+    rows: usize,
+}
+
+impl ThumbnailGrid {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            thumbs: HashMap::new(),
+            requested: HashSet::new(),
+            failed: HashMap::new(),
+            tx,
+            rx,
+            limit: Arc::new(Semaphore::new(THUMBNAIL_FETCH_LIMIT)),
+            offset: 0,
+            columns: 1,
+            rows: 1,
+        }
+    }
+
+    /// Columns in the last layout (for up/down navigation).
+    pub fn columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Lay out the grid for `area` and scroll so `selected` is visible.
+    /// Returns the range of list positions on screen.
+    pub fn layout(&mut self, area: Rect, selected: usize, len: usize) -> Range<usize> {
+        self.columns = (area.width / CELL_WIDTH).max(1) as usize;
+        self.rows = (area.height / CELL_HEIGHT).max(1) as usize;
+
+        let row = selected / self.columns;
+        if row < self.offset {
+            self.offset = row;
+        } else if row >= self.offset + self.rows {
+            self.offset = row + 1 - self.rows;
+        }
+
+        let start = (self.offset * self.columns).min(len);
+        let end = ((self.offset + self.rows) * self.columns).min(len);
+        start..end
+    }
+
+    /// Fetch and render the thumbnail for an image in the background.
+    pub fn request(&mut self, key: String, source: Arc<dyn ImageSource>, image: ImageRecord) {
+        if self.thumbs.contains_key(&key) || !self.requested.insert(key.clone()) {
+            return;
+        }
+
+        let tx = self.tx.clone();
+        let limit = self.limit.clone();
+        tokio::spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let path = match source.download_thumbnail(&image).await {
+                Ok(path) => path,
+                Err(e) => {
+                    tracing::debug!("Failed to load thumbnail for {}: {}", image.id, e);
+                    let _ = tx.send((key, None));
+                    return;
+                }
+            };
+
+            let rendered = tokio::task::spawn_blocking(move || {
+                image::open(path).map(|thumbnail| {
+                    half_blocks(&thumbnail, Rect::new(0, 0, CELL_WIDTH - 2, CELL_HEIGHT - 3))
+                })
+            })
+            .await;
+            let lines = match rendered {
+                Ok(Ok(lines)) => Some(lines),
+                Ok(Err(e)) => {
+                    tracing::debug!("Failed to decode thumbnail for {}: {}", image.id, e);
+                    None
+                }
+                Err(e) => {
+                    tracing::debug!("Thumbnail task failed: {}", e);
+                    None
+                }
+            };
+            let _ = tx.send((key, lines));
+        });
+    }
+
+    /// Check whether an image's thumbnail is loaded or on its way. A failed
+    /// one counts as requested until it is due for a retry.
+    pub fn is_requested(&self, key: &str) -> bool {
+        self.thumbs.contains_key(key)
+            || self.requested.contains(key)
+            || self.failed.get(key).is_some_and(|at| at.elapsed() < RETRY_AFTER)
+    }
+
+    /// Collect thumbnails that finished (or failed) loading.
+    pub fn poll(&mut self) {
+        while let Ok((key, lines)) = self.rx.try_recv() {
+            self.requested.remove(&key);
+            match lines {
+                Some(lines) => {
+                    self.failed.remove(&key);
+                    self.thumbs.insert(key, lines);
+                }
+                None => {
+                    self.failed.insert(key, Instant::now());
+                }
+            }
+        }
+    }
+
+    /// Draw the cells on screen; `selected` indexes into `cells`.
+    pub fn render(&self, f: &mut Frame, area: Rect, cells: &[GridCell], selected: Option<usize>) {
+        for (i, cell) in cells.iter().enumerate() {
+            let col = (i % self.columns) as u16;
+            let row = (i / self.columns) as u16;
+            let rect = Rect::new(
+                area.x + col * CELL_WIDTH,
+                area.y + row * CELL_HEIGHT,
+                CELL_WIDTH,
+                CELL_HEIGHT,
+            )
+            .intersection(area);
+            if rect.width < 3 || rect.height < 3 {
+                continue;
+            }
+
+            let border = if selected == Some(i) {
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+            } else if cell.marked {
+                Style::default().fg(Color::Magenta)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+            let block = Block::default().borders(Borders::ALL).border_style(border);
+            let inner = block.inner(rect);
+            f.render_widget(block, rect);
+
+            // Thumbnail (centered), caption on the last line
+            let image_area = Rect::new(inner.x, inner.y, inner.width, inner.height.saturating_sub(1));
+            match self.thumbs.get(&cell.key) {
+                Some(lines) => {
+                    let width = lines.first().map(|l| l.width() as u16).unwrap_or(0);
+                    let x = image_area.x + image_area.width.saturating_sub(width) / 2;
+                    let target = Rect::new(x, image_area.y, width.min(image_area.width), image_area.height);
+                    f.render_widget(Paragraph::new(lines.clone()), target);
+                }
+                None => {
+                    let glyph = if self.failed.contains_key(&cell.key) { "✗" } else { "…" };
+                    let loading = Paragraph::new(glyph).style(Style::default().fg(Color::DarkGray));
+                    f.render_widget(loading, image_area);
+                }
+            }
+
+            let mut caption = vec![
+                Span::styled(
+                    match (cell.duplicate, cell.downloaded) {
+                        (true, _) => "≈",
+                        (false, true) => "✓",
+                        (false, false) => " ",
+                    },
+                    Style::default().fg(Color::Green),
+                ),
+                Span::styled(
+                    if cell.favorite { "★" } else { " " },
+                    Style::default().fg(Color::Yellow),
+                ),
+            ];
+            let room = inner.width.saturating_sub(2) as usize;
+            let title: String = if cell.title.chars().count() > room {
+                let mut title: String = cell.title.chars().take(room.saturating_sub(1)).collect();
+                title.push('…');
+                title
+            } else {
+                cell.title.to_string()
+            };
+            caption.push(Span::styled(title, Style::default().fg(Color::White)));
+
+            let caption_area = Rect::new(inner.x, inner.y + inner.height.saturating_sub(1), inner.width, 1);
+            f.render_widget(Paragraph::new(Line::from(caption)), caption_area);
+        }
+    }
+}
@@ -1,12 +1,17 @@
 //! TUI screens.
 
 pub mod browser;
+mod filters;
 pub mod generator;
+mod grid;
 pub mod models;
+mod preview;
+mod renditions;
 
 pub use browser::BrowserScreen;
 pub use generator::GeneratorScreen;
 pub use models::ModelsScreen;
+pub use preview::GraphicsProtocol;
 
 use async_trait::async_trait;
 use crossterm::event::KeyEvent;
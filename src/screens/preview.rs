@@ -0,0 +1,365 @@
+//! Inline image preview for the browser detail pane.
+//!
+//! Uses the kitty graphics protocol or sixel when the terminal supports them,
+//! and unicode half-blocks everywhere else. Graphics protocols draw outside
+//! ratatui's buffer, so the screen reserves a blank area while drawing and the
+//! escape sequences are written afterwards by [`ImagePreview::flush`].
+
+use anyhow::Result;
+use base64::Engine;
+use image::{imageops::FilterType, DynamicImage, GenericImageView, ImageFormat, RgbImage};
+use ratatui::{
+    layout::Rect,
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Clear, Paragraph},
+    Frame,
+};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Write};
+
+/// Kitty image id used for the preview.
+const KITTY_IMAGE_ID: u32 = 7337;
+
+/// Cell size in pixels when the terminal doesn't report one.
+const DEFAULT_CELL_SIZE: (u32, u32) = (8, 16);
+
+/// How the preview is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphicsProtocol {
+    Kitty,
+    Sixel,
+    HalfBlocks,
+    Off,
+}
+
+impl GraphicsProtocol {
+    /// Resolve the `ui.preview` setting, detecting the terminal for "auto".
+    pub fn from_config(setting: &str) -> Self {
+        match setting.to_lowercase().as_str() {
+            "kitty" => Self::Kitty,
+            "sixel" => Self::Sixel,
+            "halfblocks" | "blocks" => Self::HalfBlocks,
+            "off" | "none" => Self::Off,
+            _ => Self::detect(),
+        }
+    }
+
+    /// Guess the best protocol from the environment.
+    fn detect() -> Self {
+        let env = |name: &str| std::env::var(name).unwrap_or_default().to_lowercase();
+        let term = env("TERM");
+        let program = env("TERM_PROGRAM");
+
+        // Multiplexers need passthrough wrapping we don't do
+        if std::env::var_os("TMUX").is_some() || term.starts_with("screen") {
+            return Self::HalfBlocks;
+        }
+
+        if std::env::var_os("KITTY_WINDOW_ID").is_some()
+            || term.contains("kitty")
+            || term.contains("ghostty")
+            || program == "wezterm"
+            || program == "ghostty"
+        {
+            Self::Kitty
+        } else if ["foot", "mlterm", "contour", "yaft", "sixel"]
+            .iter()
+            .any(|t| term.contains(t))
+            || program == "iterm.app"
+        {
+            Self::Sixel
+        } else {
+            Self::HalfBlocks
+        }
+    }
+}
+
+/// Preview state: the decoded thumbnail plus what is currently on screen.
+pub struct ImagePreview {
+    protocol: GraphicsProtocol,
+    /// Key of the image being previewed and its decoded thumbnail
+    image: Option<(String, DynamicImage)>,
+    /// Half-block rendering cached for a given size
+    blocks: Option<(u16, u16, Vec<Line<'static>>)>,
+    /// Where the graphics should go this frame
+    placement: Option<Rect>,
+    /// What graphics are currently on screen
+    drawn: Option<(String, Rect)>,
+}
+
+impl ImagePreview {
+    pub fn new(protocol: GraphicsProtocol) -> Self {
+        Self {
+            protocol,
+            image: None,
+            blocks: None,
+            placement: None,
+            drawn: None,
+        }
+    }
+
+    /// Whether previews are enabled.
+    pub fn enabled(&self) -> bool {
+        self.protocol != GraphicsProtocol::Off
+    }
+
+    /// Key of the image currently loaded.
+    pub fn key(&self) -> Option<&str> {
+        self.image.as_ref().map(|(key, _)| key.as_str())
+    }
+
+    /// Set the thumbnail to show.
+    pub fn set_image(&mut self, key: String, image: DynamicImage) {
+        self.image = Some((key, image));
+        self.blocks = None;
+    }
+
+    /// Check whether a preview for `key` is ready to draw.
+    pub fn has_image(&self, key: &str) -> bool {
+        self.enabled() && self.key() == Some(key)
+    }
+
+    /// Draw the preview into `area`, fitted and horizontally centered.
+    pub fn render(&mut self, f: &mut Frame, area: Rect) {
+        let image = match &self.image {
+            Some((_, image)) if self.enabled() && area.width > 0 && area.height > 0 => image,
+            _ => return,
+        };
+
+        match self.protocol {
+            GraphicsProtocol::HalfBlocks => {
+                let stale = self
+                    .blocks
+                    .as_ref()
+                    .is_none_or(|(w, h, _)| (*w, *h) != (area.width, area.height));
+                if stale {
+                    self.blocks = Some((area.width, area.height, half_blocks(image, area)));
+                }
+                if let Some((_, _, lines)) = &self.blocks {
+                    let width = lines.first().map(|l| l.width() as u16).unwrap_or(0);
+                    let x = area.x + area.width.saturating_sub(width) / 2;
+                    let target = Rect::new(x, area.y, width.min(area.width), area.height);
+                    f.render_widget(Paragraph::new(lines.clone()), target);
+                }
+            }
+            GraphicsProtocol::Kitty | GraphicsProtocol::Sixel => {
+                let (cw, ch) = cell_size();
+                let (cols, rows) = fit_cells(image.dimensions(), area, (cw, ch));
+                let x = area.x + area.width.saturating_sub(cols) / 2;
+                let placement = Rect::new(x, area.y, cols, rows);
+                // Keep the cells blank so ratatui doesn't draw over the image
+                f.render_widget(Clear, placement);
+                self.placement = Some(placement);
+            }
+            GraphicsProtocol::Off => {}
+        }
+    }
+
+    /// Write graphics escape sequences after a frame has been drawn.
+    ///
+    /// Pass `visible = false` when the preview isn't on screen (another tab,
+    /// shutting down) to remove it. Returns true when the terminal must be
+    /// cleared and redrawn to erase a stale sixel image.
+    pub fn flush(&mut self, out: &mut impl Write, visible: bool) -> Result<bool> {
+        let placement = self.placement.take();
+        let target = match (&self.image, placement) {
+            (Some((key, _)), Some(rect)) if visible => Some((key.clone(), rect)),
+            _ => None,
+        };
+        if target == self.drawn {
+            return Ok(false);
+        }
+
+        match self.protocol {
+            GraphicsProtocol::Kitty => {
+                if self.drawn.take().is_some() {
+                    write!(out, "\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)?;
+                }
+                if let (Some((key, rect)), Some((_, image))) = (target, &self.image) {
+                    write_kitty(out, image, rect)?;
+                    self.drawn = Some((key, rect));
+                }
+            }
+            GraphicsProtocol::Sixel => {
+                if self.drawn.take().is_some() {
+                    // Sixel pixels only go away when the cells are redrawn
+                    return Ok(true);
+                }
+                if let (Some((key, rect)), Some((_, image))) = (target, &self.image) {
+                    write_sixel(out, image, rect)?;
+                    self.drawn = Some((key, rect));
+                }
+            }
+            GraphicsProtocol::HalfBlocks | GraphicsProtocol::Off => {}
+        }
+
+        out.flush()?;
+        Ok(false)
+    }
+}
+
+/// Terminal cell size in pixels.
+fn cell_size() -> (u32, u32) {
+    match crossterm::terminal::window_size() {
+        Ok(size) if size.width > 0 && size.height > 0 && size.columns > 0 && size.rows > 0 => (
+            (size.width / size.columns) as u32,
+            (size.height / size.rows) as u32,
+        ),
+        _ => DEFAULT_CELL_SIZE,
+    }
+}
+
+/// Fit an image into an area, returning the size in cells.
+fn fit_cells((iw, ih): (u32, u32), area: Rect, (cw, ch): (u32, u32)) -> (u16, u16) {
+    if iw == 0 || ih == 0 {
+        return (0, 0);
+    }
+    let max_w = area.width as f64 * cw as f64;
+    let max_h = area.height as f64 * ch as f64;
+    let scale = (max_w / iw as f64).min(max_h / ih as f64);
+
+    let cols = ((iw as f64 * scale) / cw as f64).round().max(1.0) as u16;
+    let rows = ((ih as f64 * scale) / ch as f64).round().max(1.0) as u16;
+    (cols.min(area.width), rows.min(area.height))
+}
+
+/// Resize an image to exactly fill `cols` x `rows` cells.
+fn resize_for(image: &DynamicImage, rect: Rect, (cw, ch): (u32, u32)) -> RgbImage {
+    let width = (rect.width as u32 * cw).max(1);
+    let height = (rect.height as u32 * ch).max(1);
+    image
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_rgb8()
+}
+
+/// Render an image as `▀` cells: foreground is the top pixel, background the bottom.
+pub(super) fn half_blocks(image: &DynamicImage, area: Rect) -> Vec<Line<'static>> {
+    // A half-block "pixel" is roughly square
+    let (cols, rows) = fit_cells(image.dimensions(), area, (1, 2));
+    let pixels = resize_for(image, Rect::new(0, 0, cols, rows), (1, 2));
+    let (width, height) = pixels.dimensions();
+
+    (0..height)
+        .step_by(2)
+        .map(|y| {
+            let spans: Vec<Span> = (0..width)
+                .map(|x| {
+                    let top = pixels.get_pixel(x, y);
+                    let mut style = Style::default().fg(Color::Rgb(top[0], top[1], top[2]));
+                    if y + 1 < height {
+                        let bottom = pixels.get_pixel(x, y + 1);
+                        style = style.bg(Color::Rgb(bottom[0], bottom[1], bottom[2]));
+                    }
+                    Span::styled("▀", style)
+                })
+                .collect();
+            Line::from(spans)
+        })
+        .collect()
+}
+
+/// Transmit and place an image with the kitty graphics protocol.
+fn write_kitty(out: &mut impl Write, image: &DynamicImage, rect: Rect) -> Result<()> {
+    let pixels = resize_for(image, rect, cell_size());
+    let mut png = Vec::new();
+    DynamicImage::ImageRgb8(pixels).write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let data = base64::engine::general_purpose::STANDARD.encode(&png);
+
+    // Save cursor, move to the placement
+    write!(out, "\x1b7\x1b[{};{}H", rect.y + 1, rect.x + 1)?;
+
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = if i + 1 < chunks.len() { 1 } else { 0 };
+        if i == 0 {
+            write!(
+                out,
+                "\x1b_Ga=T,f=100,i={},c={},r={},C=1,q=2,m={};",
+                KITTY_IMAGE_ID, rect.width, rect.height, more
+            )?;
+        } else {
+            write!(out, "\x1b_Gm={};", more)?;
+        }
+        out.write_all(chunk)?;
+        write!(out, "\x1b\\")?;
+    }
+
+    write!(out, "\x1b8")?;
+    Ok(())
+}
+
+/// Draw an image as sixel graphics.
+fn write_sixel(out: &mut impl Write, image: &DynamicImage, rect: Rect) -> Result<()> {
+    let pixels = resize_for(image, rect, cell_size());
+    write!(out, "\x1b7\x1b[{};{}H", rect.y + 1, rect.x + 1)?;
+    out.write_all(encode_sixel(&pixels).as_bytes())?;
+    write!(out, "\x1b8")?;
+    Ok(())
+}
+
+/// Encode an image as sixel data using a 6x6x6 color cube palette.
+fn encode_sixel(pixels: &RgbImage) -> String {
+    let (width, height) = pixels.dimensions();
+    let level = |v: u8| (v as usize * 5 + 127) / 255;
+    let color = |x: u32, y: u32| {
+        let p = pixels.get_pixel(x, y);
+        level(p[0]) * 36 + level(p[1]) * 6 + level(p[2])
+    };
+
+    let mut data = format!("\x1bPq\"1;1;{};{}", width, height);
+    for i in 0..216 {
+        let percent = |c: usize| c * 100 / 5;
+        data.push_str(&format!(
+            "#{};2;{};{};{}",
+            i,
+            percent(i / 36),
+            percent((i / 6) % 6),
+            percent(i % 6)
+        ));
+    }
+
+    for band in (0..height).step_by(6) {
+        // One row of sixels per color used in this band
+        let mut rows: BTreeMap<usize, Vec<u8>> = BTreeMap::new();
+        for x in 0..width {
+            for dy in 0..6 {
+                let y = band + dy;
+                if y >= height {
+                    break;
+                }
+                let row = rows
+                    .entry(color(x, y))
+                    .or_insert_with(|| vec![0; width as usize]);
+                row[x as usize] |= 1 << dy;
+            }
+        }
+
+        for (i, (c, bits)) in rows.iter().enumerate() {
+            if i > 0 {
+                data.push('$');
+            }
+            data.push_str(&format!("#{}", c));
+            push_sixel_row(&mut data, bits);
+        }
+        data.push('-');
+    }
+
+    data.push_str("\x1b\\");
+    data
+}
+
+/// Append one run-length encoded sixel row.
+fn push_sixel_row(data: &mut String, bits: &[u8]) {
+    let mut i = 0;
+    while i < bits.len() {
+        let run = bits[i..].iter().take_while(|&&b| b == bits[i]).count();
+        let ch = (63 + bits[i]) as char;
+        if run > 3 {
+            data.push_str(&format!("!{}{}", run, ch));
+        } else {
+            (0..run).for_each(|_| data.push(ch));
+        }
+        i += run;
+    }
+}
@@ -0,0 +1,185 @@
+//! Rendition picker: choose which size of an image to download.
+//!
+//! Each rendition is probed in the background for availability, byte size
+//! and pixel dimensions while the picker is open.
+
+use ratatui::{
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph},
+    Frame,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tokio::sync::oneshot;
+
+use crate::services::image_source::RenditionProbe;
+use crate::services::{ImageRecord, ImageSource, DEFAULT_RENDITION};
+use crate::utils::human_size;
+
+/// Popup listing an image's renditions.
+pub struct RenditionPicker {
+    pub image: ImageRecord,
+    pub source: Arc<dyn ImageSource>,
+    probes: Vec<RenditionProbe>,
+    rx: Option<oneshot::Receiver<Vec<RenditionProbe>>>,
+    /// Renditions already on disk
+    downloaded: HashSet<String>,
+    selected: usize,
+}
+
+impl RenditionPicker {
+    /// Open the picker for an image and start probing its renditions.
+    pub fn open(source: Arc<dyn ImageSource>, image: ImageRecord) -> Self {
+        // Unprobed placeholders until the results come in
+        let probes: Vec<RenditionProbe> = image
+            .renditions
+            .iter()
+            .filter(|r| r.name != "thumbnail")
+            .map(|r| RenditionProbe {
+                name: r.name.clone(),
+                available: false,
+                size: None,
+                dimensions: None,
+                error: None,
+            })
+            .collect();
+        let downloaded = probes
+            .iter()
+            .filter(|p| source.download_path(&image, &p.name).exists())
+            .map(|p| p.name.clone())
+            .collect();
+        let selected = probes
+            .iter()
+            .position(|p| p.name == DEFAULT_RENDITION)
+            .unwrap_or(probes.len().saturating_sub(1));
+
+        let (tx, rx) = oneshot::channel();
+        let probe_source = source.clone();
+        let probe_image = image.clone();
+        tokio::spawn(async move {
+            let _ = tx.send(probe_source.probe_renditions(&probe_image).await);
+        });
+
+        Self {
+            image,
+            source,
+            probes,
+            rx: Some(rx),
+            downloaded,
+            selected,
+        }
+    }
+
+    /// Pick up probe results.
+    pub fn poll(&mut self) {
+        let Some(rx) = self.rx.as_mut() else {
+            return;
+        };
+        match rx.try_recv() {
+            Ok(probes) => {
+                self.probes = probes;
+                self.selected = self.selected.min(self.probes.len().saturating_sub(1));
+                self.rx = None;
+            }
+            Err(oneshot::error::TryRecvError::Empty) => {}
+            Err(oneshot::error::TryRecvError::Closed) => self.rx = None,
+        }
+    }
+
+    /// Whether probes are still running.
+    pub fn is_probing(&self) -> bool {
+        self.rx.is_some()
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    pub fn next(&mut self) {
+        if self.selected + 1 < self.probes.len() {
+            self.selected += 1;
+        }
+    }
+
+    /// Name of the selected rendition, unless probing showed it's missing.
+    pub fn selected_rendition(&self) -> Option<&str> {
+        let probe = self.probes.get(self.selected)?;
+        if self.is_probing() || probe.available {
+            Some(&probe.name)
+        } else {
+            None
+        }
+    }
+
+    /// Draw the picker centered in `area`.
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let width = area.width.min(72);
+        let height = (self.probes.len() as u16 + 4).min(area.height);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+
+        let mut lines = vec![Line::from(Span::styled(
+            format!("{:<14}{:>12}{:>11}  Status", "Rendition", "Pixels", "Size"),
+            Style::default().fg(Color::DarkGray),
+        ))];
+        for (i, probe) in self.probes.iter().enumerate() {
+            let pixels = probe
+                .dimensions
+                .map(|(w, h)| format!("{}x{}", w, h))
+                .unwrap_or_else(|| "-".to_string());
+            let size = probe.size.map(human_size).unwrap_or_else(|| "-".to_string());
+            let (status, color) = if self.is_probing() {
+                ("probing…".to_string(), Color::DarkGray)
+            } else if !probe.available {
+                (
+                    probe.error.clone().unwrap_or_else(|| "unavailable".to_string()),
+                    Color::Red,
+                )
+            } else if self.downloaded.contains(&probe.name) {
+                ("✓ downloaded".to_string(), Color::Green)
+            } else {
+                ("available".to_string(), Color::White)
+            };
+
+            let style = if i == self.selected {
+                Style::default().fg(Color::Cyan).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default()
+            };
+            lines.push(Line::from(vec![
+                Span::styled(
+                    format!(
+                        "{} {:<12}{:>12}{:>11}  ",
+                        if i == self.selected { "▶" } else { " " },
+                        probe.name,
+                        pixels,
+                        size
+                    ),
+                    style,
+                ),
+                Span::styled(status, Style::default().fg(color)),
+            ]));
+        }
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(Color::Cyan))
+            .title(format!(" Download: {} ", self.image.title))
+            .title_bottom(Line::from(vec![
+                Span::styled("[↑/↓]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" Select "),
+                Span::styled("[Enter]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" Download "),
+                Span::styled("[Esc]", Style::default().fg(Color::DarkGray)),
+                Span::raw(" Cancel"),
+            ]));
+        f.render_widget(Clear, popup);
+        f.render_widget(Paragraph::new(lines).block(block), popup);
+    }
+}
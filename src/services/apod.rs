@@ -0,0 +1,322 @@
+//! NASA Astronomy Picture of the Day service.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, Duration, FixedOffset, NaiveDate, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::http::{HttpClient, Service};
+use super::image_source::{self, ImageRecord, ImageSource, Rendition, Staleness};
+use crate::config::Config;
+
+/// Key used when no NASA API key file is available (heavily rate limited).
+const DEMO_API_KEY: &str = "DEMO_KEY";
+
+/// Represents an entry from the APOD API.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApodImage {
+    pub date: String,
+    pub title: String,
+    #[serde(default)]
+    pub explanation: Option<String>,
+    #[serde(default)]
+    pub url: Option<String>,
+    #[serde(default)]
+    pub hdurl: Option<String>,
+    #[serde(default)]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub copyright: Option<String>,
+}
+
+impl ApodImage {
+    /// Check if this entry is a still image (APOD also publishes videos).
+    pub fn is_image(&self) -> bool {
+        self.media_type.as_deref().unwrap_or("image") == "image"
+    }
+
+    /// Get the APOD page URL.
+    pub fn page_url(&self) -> Option<String> {
+        let date = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d").ok()?;
+        Some(format!(
+            "https://apod.nasa.gov/apod/ap{}.html",
+            date.format("%y%m%d")
+        ))
+    }
+
+    /// Convert into a provider-independent record.
+    pub fn to_record(&self) -> ImageRecord {
+        let mut renditions = Vec::new();
+        if let Some(url) = &self.url {
+            renditions.push(Rendition {
+                name: "screen".to_string(),
+                url: url.clone(),
+            });
+        }
+        if let Some(url) = &self.hdurl {
+            renditions.push(Rendition {
+                name: "original".to_string(),
+                url: url.clone(),
+            });
+        }
+
+        let date = NaiveDate::parse_from_str(&self.date, "%Y-%m-%d")
+            .ok()
+            .and_then(|d| d.and_hms_opt(0, 0, 0))
+            .map(|dt| Utc.from_utc_datetime(&dt));
+
+        ImageRecord {
+            source: "apod".to_string(),
+            id: self.date.clone(),
+            title: self.title.clone(),
+            date,
+            description: self.explanation.clone(),
+            credit: self.copyright.as_ref().map(|c| c.trim().replace('\n', " ")),
+            page_url: self.page_url(),
+            renditions,
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: Vec::new(),
+            fields: Vec::new(),
+        }
+    }
+}
+
+/// Service for fetching images from NASA's APOD API.
+pub struct ApodService {
+    config: Arc<Config>,
+    client: HttpClient,
+    cache_dir: PathBuf,
+    api_key: String,
+    stale: Staleness,
+}
+
+impl ApodService {
+    /// Create a new APOD service.
+    pub fn new(config: Arc<Config>) -> Result<Self> {
+        let cache_dir = config.cache_dir();
+        fs::create_dir_all(&cache_dir).ok();
+
+        let api_key = config
+            .apod_api_key()
+            .unwrap_or_else(|| DEMO_API_KEY.to_string());
+
+        Ok(Self {
+            cache_dir,
+            client: HttpClient::new(&config, Service::Images)?,
+            config,
+            api_key,
+            stale: Staleness::default(),
+        })
+    }
+
+    /// Cache file for a date range.
+    fn cache_file(&self, start: NaiveDate, end: NaiveDate) -> PathBuf {
+        self.cache_dir
+            .join(format!("apod_{}_{}.json", start.format("%Y%m%d"), end.format("%Y%m%d")))
+    }
+
+    /// Fetch entries for an inclusive date range, skipping videos.
+    pub async fn get_images_range(
+        &self,
+        start: NaiveDate,
+        end: NaiveDate,
+        force_refresh: bool,
+    ) -> Result<Vec<ApodImage>> {
+        let cache_file = self.cache_file(start, end);
+
+        // Once the day rolls over this range has no cache yet; the newest
+        // range that does is the best stale copy
+        let stale_file = if cache_file.exists() {
+            cache_file.clone()
+        } else {
+            self.latest_cache_file().unwrap_or_else(|| cache_file.clone())
+        };
+        let read_cache = |path: &Path| -> Result<Vec<ApodImage>> {
+            let content = fs::read_to_string(path)
+                .context("Failed to read APOD cache")?;
+            serde_json::from_str(&content).context("Failed to parse cached APOD entries")
+        };
+        let mut images = image_source::fetch_or_stale(
+            &self.config,
+            &cache_file,
+            &stale_file,
+            force_refresh,
+            &self.stale,
+            read_cache,
+            self.download_range(start, end, &cache_file),
+        )
+        .await?;
+
+        images.retain(ApodImage::is_image);
+
+        // Newest first
+        images.sort_by(|a, b| b.date.cmp(&a.date));
+
+        Ok(images)
+    }
+
+    /// Fetch entries for a date range from the API, updating the cache.
+    async fn download_range(&self, start: NaiveDate, end: NaiveDate, cache_file: &Path) -> Result<Vec<ApodImage>> {
+        let response = self
+            .client
+            .get(&self.config.apod.api_base)
+            .query(&[
+                ("api_key", self.api_key.as_str()),
+                ("start_date", &start.format("%Y-%m-%d").to_string()),
+                ("end_date", &end.format("%Y-%m-%d").to_string()),
+            ])
+            .send()
+            .await
+            .context("Failed to fetch from APOD API")?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("APOD request failed with status: {}", response.status());
+        }
+
+        let images: Vec<ApodImage> = response
+            .json()
+            .await
+            .context("Failed to parse APOD response")?;
+
+        // Cache the response, replacing the ranges of earlier days
+        let cache_content = serde_json::to_string_pretty(&images)?;
+        if fs::write(cache_file, &cache_content).is_ok() {
+            self.prune_cache(cache_file);
+        }
+
+        Ok(images)
+    }
+
+    /// APOD cache files, for any date range.
+    fn cache_files(&self) -> Vec<PathBuf> {
+        let Ok(entries) = fs::read_dir(&self.cache_dir) else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| {
+                let name = p.file_name().unwrap_or_default().to_string_lossy();
+                name.starts_with("apod_") && name.ends_with(".json")
+            })
+            .collect()
+    }
+
+    /// Most recently written APOD cache file, for any date range.
+    fn latest_cache_file(&self) -> Option<PathBuf> {
+        self.cache_files()
+            .into_iter()
+            .max_by_key(|p| fs::metadata(p).and_then(|m| m.modified()).ok())
+    }
+
+    /// Remove every cached range but `keep`. The window moves to a new file
+    /// each day, so old ones would otherwise pile up.
+    fn prune_cache(&self, keep: &Path) {
+        for path in self.cache_files().into_iter().filter(|p| p != keep) {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    /// Get entries for the configured number of days, ending today.
+    pub async fn get_images(&self, force_refresh: bool) -> Result<Vec<ApodImage>> {
+        // APOD publishes on US Eastern time and rejects end dates in its future
+        let now = Utc::now();
+        let end = now.with_timezone(&us_eastern_offset(now)).date_naive();
+        let start = end - Duration::days(self.config.apod.days.saturating_sub(1) as i64);
+        self.get_images_range(start, end, force_refresh).await
+    }
+}
+
+/// US Eastern offset at `now`: EDT (UTC-4) from 2am on the second Sunday
+/// of March to 2am on the first Sunday of November, EST (UTC-5) otherwise.
+fn us_eastern_offset(now: DateTime<Utc>) -> FixedOffset {
+    let sunday = |month: u32, nth: i64| {
+        let first = NaiveDate::from_ymd_opt(now.year(), month, 1).expect("valid date");
+        let days_to_sunday = (7 - first.weekday().num_days_from_sunday() as i64) % 7;
+        first + Duration::days(days_to_sunday + 7 * (nth - 1))
+    };
+    // 2am local is 07:00 UTC while on EST and 06:00 UTC while on EDT
+    let dst_start = sunday(3, 2).and_hms_opt(7, 0, 0).expect("valid time").and_utc();
+    let dst_end = sunday(11, 1).and_hms_opt(6, 0, 0).expect("valid time").and_utc();
+    let hours = if (dst_start..dst_end).contains(&now) { 4 } else { 5 };
+    FixedOffset::west_opt(hours * 3600).expect("valid offset")
+}
+
+#[async_trait]
+impl ImageSource for ApodService {
+    fn key(&self) -> &str {
+        "apod"
+    }
+
+    fn name(&self) -> &str {
+        "NASA APOD"
+    }
+
+    fn file_prefix(&self) -> &str {
+        "apod"
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    async fn list_images(&self, force_refresh: bool) -> Result<Vec<ImageRecord>> {
+        let images = self.get_images(force_refresh).await?;
+        Ok(images.iter().map(ApodImage::to_record).collect())
+    }
+
+    fn stale_since(&self) -> Option<DateTime<Utc>> {
+        self.stale.since()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eastern_date(utc: &str) -> String {
+        let now: DateTime<Utc> = utc.parse().unwrap();
+        now.with_timezone(&us_eastern_offset(now)).format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    #[test]
+    fn eastern_offset_follows_daylight_saving() {
+        // Winter and summer
+        assert_eq!(eastern_date("2025-01-15T04:30:00Z"), "2025-01-14 23:30");
+        assert_eq!(eastern_date("2025-07-15T03:30:00Z"), "2025-07-14 23:30");
+        assert_eq!(eastern_date("2025-07-15T04:30:00Z"), "2025-07-15 00:30");
+        // DST starts 2025-03-09 at 2am EST, ends 2025-11-02 at 2am EDT
+        assert_eq!(eastern_date("2025-03-09T06:59:00Z"), "2025-03-09 01:59");
+        assert_eq!(eastern_date("2025-03-09T07:00:00Z"), "2025-03-09 03:00");
+        assert_eq!(eastern_date("2025-11-02T05:59:00Z"), "2025-11-02 01:59");
+        assert_eq!(eastern_date("2025-11-02T06:00:00Z"), "2025-11-02 01:00");
+    }
+
+    #[test]
+    fn prune_keeps_only_the_current_range() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.jwst.cache_dir = dir.path().to_string_lossy().to_string();
+        let service = ApodService::new(Arc::new(config)).unwrap();
+
+        let old = dir.path().join("apod_20250101_20250130.json");
+        let current = dir.path().join("apod_20250102_20250131.json");
+        let other = dir.path().join("esa_feed.json");
+        for path in [&old, &current, &other] {
+            fs::write(path, "[]").unwrap();
+        }
+
+        service.prune_cache(&current);
+        assert!(!old.exists());
+        assert!(current.exists());
+        assert!(other.exists());
+    }
+}
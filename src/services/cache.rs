@@ -0,0 +1,575 @@
+//! Bounded on-disk caches.
+//!
+//! Thumbnails and downloaded images are evicted least recently used first
+//! once they pass the configured size caps. Favorites and the current
+//! wallpaper are never evicted, and neither are files the app didn't
+//! download (generated images, wallpapers added by hand).
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use super::library::source_prefixes;
+use super::local::is_image_file;
+use super::provenance::{read_sidecar, sidecar_path};
+use super::{FavoritesService, ImageSource, WallustService};
+use crate::config::Config;
+use crate::utils::human_size;
+
+const MB: u64 = 1024 * 1024;
+
+/// Kinds of cached files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheCategory {
+    /// Thumbnails in `<cache_dir>/thumbnails`
+    Thumbnails,
+    /// Feed, API and gallery-page caches in `<cache_dir>`
+    Feeds,
+    /// Images downloaded into the wallpaper directory
+    Downloads,
+}
+
+impl CacheCategory {
+    pub const ALL: [CacheCategory; 3] = [Self::Thumbnails, Self::Feeds, Self::Downloads];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Self::Thumbnails => "thumbnails",
+            Self::Feeds => "feeds",
+            Self::Downloads => "downloads",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|c| c.label() == s.to_lowercase())
+    }
+}
+
+/// A cached file.
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub path: PathBuf,
+    pub category: CacheCategory,
+    /// Source key, for downloads
+    pub source: Option<String>,
+    /// Size in bytes, including sidecars
+    pub size: u64,
+    /// Last access (or modification, if later)
+    pub last_used: SystemTime,
+    /// Favorite or current wallpaper; never evicted
+    pub protected: bool,
+}
+
+/// Everything in the caches.
+#[derive(Debug, Default)]
+pub struct CacheUsage {
+    pub entries: Vec<CacheEntry>,
+}
+
+impl CacheUsage {
+    /// File count and total bytes in a category.
+    pub fn total(&self, category: CacheCategory) -> (usize, u64) {
+        self.entries
+            .iter()
+            .filter(|e| e.category == category)
+            .fold((0, 0), |(count, bytes), e| (count + 1, bytes + e.size))
+    }
+
+    /// Bytes in a category that can't be evicted.
+    pub fn protected_bytes(&self, category: CacheCategory) -> u64 {
+        self.entries
+            .iter()
+            .filter(|e| e.category == category && e.protected)
+            .map(|e| e.size)
+            .sum()
+    }
+
+    /// File count and bytes of downloads per source, largest first.
+    pub fn by_source(&self) -> Vec<(String, usize, u64)> {
+        let mut sources: Vec<(String, usize, u64)> = Vec::new();
+        for entry in &self.entries {
+            let Some(source) = &entry.source else {
+                continue;
+            };
+            match sources.iter_mut().find(|(key, _, _)| key == source) {
+                Some((_, count, bytes)) => {
+                    *count += 1;
+                    *bytes += entry.size;
+                }
+                None => sources.push((source.clone(), 1, entry.size)),
+            }
+        }
+        sources.sort_by_key(|(_, _, bytes)| std::cmp::Reverse(*bytes));
+        sources
+    }
+}
+
+/// Which files a purge removes. Entries must match every filter given.
+#[derive(Debug, Clone, Default)]
+pub struct PurgeFilter {
+    pub categories: Vec<CacheCategory>,
+    /// Only files unused for at least this long
+    pub older_than: Option<Duration>,
+    /// Only downloads from this source
+    pub source: Option<String>,
+    /// Evict least recently used files until each category (as narrowed by
+    /// `source`) fits in this many bytes; without it every match goes
+    pub max_bytes: Option<u64>,
+}
+
+/// Result of a purge.
+#[derive(Debug, Default)]
+pub struct PurgeReport {
+    pub removed: Vec<PathBuf>,
+    /// Bytes freed
+    pub freed: u64,
+    /// Files that matched but couldn't be removed
+    pub failed: Vec<(PathBuf, std::io::Error)>,
+}
+
+/// Files that must survive eviction: every downloaded file of a favorite,
+/// and the current wallpaper.
+pub fn protected_paths(
+    sources: &[Arc<dyn ImageSource>],
+    favorites: &FavoritesService,
+    wallust: &WallustService,
+) -> HashSet<PathBuf> {
+    let mut protected = HashSet::new();
+    for favorite in favorites.list() {
+        if let Some(source) = sources.iter().find(|s| s.key() == favorite.record.source) {
+            protected.extend(source.downloaded_files(&favorite.record));
+        }
+    }
+    protected.extend(wallust.current_wallpaper().map(PathBuf::from));
+
+    // Compare real paths, in case the wallpaper directory is behind a symlink
+    protected.iter().map(|p| canonical(p)).collect()
+}
+
+/// Scan the thumbnail, feed and download caches.
+pub fn scan(config: &Config, sources: &[Arc<dyn ImageSource>], protected: &HashSet<PathBuf>) -> CacheUsage {
+    let mut usage = CacheUsage::default();
+
+    let thumbnail_dir = config.thumbnail_dir();
+    for path in list_files(&thumbnail_dir) {
+        usage.entries.extend(entry(path, CacheCategory::Thumbnails, None, protected));
+    }
+
+    let mut feeds = Vec::new();
+    walk(&config.cache_dir(), &[thumbnail_dir, config.data_dir()], &mut feeds);
+    for path in feeds {
+        // Validators are counted with the file they belong to
+        if !path.to_string_lossy().ends_with(".http.json") {
+            usage.entries.extend(entry(path, CacheCategory::Feeds, None, protected));
+        }
+    }
+
+    let prefixes = source_prefixes(sources);
+    for path in list_files(&config.wallpaper_dir()) {
+        if !is_image_file(&path) {
+            continue;
+        }
+        if let Some(source) = download_source(&path, &prefixes) {
+            usage.entries.extend(entry(path, CacheCategory::Downloads, Some(source), protected));
+        }
+    }
+
+    usage
+}
+
+/// Remove the cached files matching `filter`, least recently used first.
+/// Protected files are always kept. Files that can't be removed are
+/// reported in `failed` and the next one is tried. With `dry_run`, only
+/// reports what would go.
+pub fn purge(usage: &CacheUsage, filter: &PurgeFilter, dry_run: bool) -> PurgeReport {
+    let now = SystemTime::now();
+    let mut report = PurgeReport::default();
+
+    for category in &filter.categories {
+        let matching: Vec<&CacheEntry> = usage
+            .entries
+            .iter()
+            .filter(|e| e.category == *category)
+            .filter(|e| filter.source.is_none() || e.source == filter.source)
+            .collect();
+        let mut total: u64 = matching.iter().map(|e| e.size).sum();
+
+        let mut candidates: Vec<&CacheEntry> = matching
+            .into_iter()
+            .filter(|e| !e.protected)
+            .filter(|e| match filter.older_than {
+                Some(age) => now.duration_since(e.last_used).is_ok_and(|d| d >= age),
+                None => true,
+            })
+            .collect();
+        candidates.sort_by_key(|e| e.last_used);
+
+        for entry in candidates {
+            if filter.max_bytes.is_some_and(|max| total <= max) {
+                break;
+            }
+            if !dry_run {
+                if let Err(e) = remove_entry(&entry.path) {
+                    report.failed.push((entry.path.clone(), e));
+                    continue;
+                }
+            }
+            total -= entry.size;
+            report.freed += entry.size;
+            report.removed.push(entry.path.clone());
+        }
+    }
+
+    report
+}
+
+/// Evict thumbnails and downloads past their configured size caps.
+pub fn trim(config: &Config, usage: &CacheUsage) -> PurgeReport {
+    let mut report = PurgeReport::default();
+    let caps = [
+        (CacheCategory::Thumbnails, config.jwst.thumbnail_cache_mb),
+        (CacheCategory::Downloads, config.jwst.download_cache_mb),
+    ];
+
+    for (category, cap_mb) in caps {
+        if cap_mb == 0 {
+            continue;
+        }
+        let filter = PurgeFilter {
+            categories: vec![category],
+            max_bytes: Some(cap_mb * MB),
+            ..Default::default()
+        };
+        let trimmed = purge(usage, &filter, false);
+        report.freed += trimmed.freed;
+        report.removed.extend(trimmed.removed);
+        report.failed.extend(trimmed.failed);
+    }
+
+    if !report.removed.is_empty() {
+        tracing::debug!("Evicted {} cached files ({})", report.removed.len(), human_size(report.freed));
+    }
+    report
+}
+
+/// Which source downloaded a file: from its provenance sidecar, or else its
+/// file name prefix. Generated images and unknown files have none.
+fn download_source(path: &Path, prefixes: &[(String, String)]) -> Option<String> {
+    if let Some(provenance) = read_sidecar(path) {
+        return provenance.generated_from.is_none().then_some(provenance.source);
+    }
+    let name = path.file_name()?.to_string_lossy();
+    prefixes
+        .iter()
+        .find(|(prefix, _)| name.starts_with(prefix.as_str()))
+        .map(|(_, key)| key.clone())
+}
+
+/// Build the entry for a cached file, counting its sidecars.
+fn entry(
+    path: PathBuf,
+    category: CacheCategory,
+    source: Option<String>,
+    protected: &HashSet<PathBuf>,
+) -> Option<CacheEntry> {
+    let metadata = fs::metadata(&path).ok()?;
+    let modified = metadata.modified().ok()?;
+    // atime may lag (relatime) or be off (noatime); never trust it below mtime
+    let last_used = metadata.accessed().map_or(modified, |a| a.max(modified));
+    let size = metadata.len()
+        + companions(&path)
+            .iter()
+            .filter_map(|p| fs::metadata(p).ok())
+            .map(|m| m.len())
+            .sum::<u64>();
+
+    Some(CacheEntry {
+        protected: protected.contains(&canonical(&path)),
+        path,
+        category,
+        source,
+        size,
+        last_used,
+    })
+}
+
+/// Files that belong to a cached file: its provenance sidecar and HTTP
+/// validators.
+fn companions(path: &Path) -> [PathBuf; 2] {
+    let mut validators = path.as_os_str().to_os_string();
+    validators.push(".http.json");
+    [sidecar_path(path), PathBuf::from(validators)]
+}
+
+/// Remove a cached file and its companions.
+pub fn remove_entry(path: &Path) -> std::io::Result<()> {
+    fs::remove_file(path)?;
+    for companion in companions(path) {
+        if companion.exists() {
+            let _ = fs::remove_file(companion);
+        }
+    }
+    Ok(())
+}
+
+/// Regular files directly inside `dir`.
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+    match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file())
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Collect regular files under `dir`, skipping the `skip` directories.
+fn walk(dir: &Path, skip: &[PathBuf], out: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+        if path.is_dir() {
+            if !skip.contains(&path) {
+                walk(&path, skip, out);
+            }
+        } else if path.is_file() {
+            out.push(path);
+        }
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JwstConfig;
+    use crate::services::image_source::Rendition;
+    use crate::services::{EsaService, ImageRecord};
+    use std::fs::FileTimes;
+    use std::time::UNIX_EPOCH;
+
+    /// A point in time `secs` seconds after an arbitrary fixed start.
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(1_700_000_000 + secs)
+    }
+
+    /// Write a file of `size` bytes with the given access and modification times.
+    fn file(path: &Path, size: usize, accessed: SystemTime, modified: SystemTime) -> PathBuf {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, vec![0u8; size]).unwrap();
+        let times = FileTimes::new().set_accessed(accessed).set_modified(modified);
+        fs::File::options().write(true).open(path).unwrap().set_times(times).unwrap();
+        path.to_path_buf()
+    }
+
+    fn usage(paths: &[(PathBuf, CacheCategory)], protected: &HashSet<PathBuf>) -> CacheUsage {
+        CacheUsage {
+            entries: paths
+                .iter()
+                .filter_map(|(path, category)| entry(path.clone(), *category, None, protected))
+                .collect(),
+        }
+    }
+
+    fn temp_config(dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.jwst.wallpaper_dir = dir.join("wallpapers").display().to_string();
+        config.jwst.cache_dir = dir.join("cache").display().to_string();
+        config.jwst.data_dir = dir.join("data").display().to_string();
+        config
+    }
+
+    #[test]
+    fn purge_removes_least_recently_used_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let newest = file(&dir.path().join("c.jpg"), 100, at(300), at(300));
+        let oldest = file(&dir.path().join("a.jpg"), 100, at(100), at(100));
+        let middle = file(&dir.path().join("b.jpg"), 100, at(200), at(200));
+        let usage = usage(
+            &[
+                (newest.clone(), CacheCategory::Thumbnails),
+                (oldest.clone(), CacheCategory::Thumbnails),
+                (middle.clone(), CacheCategory::Thumbnails),
+            ],
+            &HashSet::new(),
+        );
+
+        let filter = PurgeFilter {
+            categories: vec![CacheCategory::Thumbnails],
+            max_bytes: Some(150),
+            ..Default::default()
+        };
+        let report = purge(&usage, &filter, false);
+        assert_eq!(report.removed, [oldest.clone(), middle.clone()]);
+        assert_eq!(report.freed, 200);
+        assert!(report.failed.is_empty());
+        assert!(!oldest.exists() && !middle.exists() && newest.exists());
+    }
+
+    #[test]
+    fn dry_run_removes_nothing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = file(&dir.path().join("a.jpg"), 100, at(100), at(100));
+        let usage = usage(&[(path.clone(), CacheCategory::Thumbnails)], &HashSet::new());
+
+        let filter = PurgeFilter {
+            categories: vec![CacheCategory::Thumbnails],
+            ..Default::default()
+        };
+        assert_eq!(purge(&usage, &filter, true).removed, std::slice::from_ref(&path));
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn last_used_is_the_later_of_access_and_modification() {
+        let dir = tempfile::tempdir().unwrap();
+        // noatime/relatime leave atime behind mtime
+        let stale_atime = file(&dir.path().join("a.jpg"), 1, at(100), at(500));
+        // A thumbnail read since it was written
+        let read_since = file(&dir.path().join("b.jpg"), 1, at(900), at(200));
+        let usage = usage(
+            &[
+                (stale_atime, CacheCategory::Thumbnails),
+                (read_since, CacheCategory::Thumbnails),
+            ],
+            &HashSet::new(),
+        );
+
+        assert_eq!(usage.entries[0].last_used, at(500));
+        assert_eq!(usage.entries[1].last_used, at(900));
+    }
+
+    #[test]
+    fn trim_keeps_capped_categories_under_their_caps() {
+        let dir = tempfile::tempdir().unwrap();
+        let half = (MB / 2) as usize;
+        let mut paths = Vec::new();
+        for (n, age) in [300, 100, 200].into_iter().enumerate() {
+            let thumbnail = dir.path().join(format!("thumb{}.jpg", n));
+            let download = dir.path().join(format!("download{}.jpg", n));
+            paths.push((file(&thumbnail, half, at(age), at(age)), CacheCategory::Thumbnails));
+            paths.push((file(&download, half, at(age), at(age)), CacheCategory::Downloads));
+        }
+        let mut config = Config::default();
+        config.jwst.thumbnail_cache_mb = 1;
+        // No cap on downloads
+        config.jwst.download_cache_mb = 0;
+        let report = trim(&config, &usage(&paths, &HashSet::new()));
+        assert_eq!(report.removed, [dir.path().join("thumb1.jpg")]);
+        assert_eq!(report.freed, MB / 2);
+
+        config.jwst.thumbnail_cache_mb = 0;
+        config.jwst.download_cache_mb = 1;
+        let report = trim(&config, &usage(&paths, &HashSet::new()));
+        assert_eq!(report.removed, [dir.path().join("download1.jpg")]);
+    }
+
+    #[test]
+    fn favorites_and_current_wallpaper_are_never_evicted() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = temp_config(dir.path());
+        let feed = JwstConfig::default().esa_feeds().remove(0);
+        let sources: Vec<Arc<dyn ImageSource>> =
+            vec![Arc::new(EsaService::new(Arc::new(config.clone()), feed).unwrap())];
+
+        let wallpapers = config.wallpaper_dir();
+        let favorite = file(&wallpapers.join("webb-fav1-screen.jpg"), 100, at(100), at(100));
+        let wallpaper = file(&wallpapers.join("webb-wall1-screen.jpg"), 100, at(200), at(200));
+        let other = file(&wallpapers.join("webb-other1-screen.jpg"), 100, at(300), at(300));
+
+        let favorites = FavoritesService::new(&config);
+        favorites
+            .toggle(&ImageRecord {
+                source: "esa".to_string(),
+                id: "fav1".to_string(),
+                title: "Favorite".to_string(),
+                date: None,
+                description: None,
+                credit: None,
+                page_url: None,
+                renditions: vec![Rendition {
+                    name: "screen".to_string(),
+                    url: "https://cdn.esawebb.org/archives/images/screen/fav1.jpg".to_string(),
+                }],
+                dimensions: None,
+                categories: Vec::new(),
+                instruments: Vec::new(),
+                fields: Vec::new(),
+            })
+            .unwrap();
+
+        // wallust records the current wallpaper under the user cache directory
+        let xdg_cache = dir.path().join("xdg-cache");
+        fs::create_dir_all(xdg_cache.join("wallust")).unwrap();
+        fs::write(xdg_cache.join("wallust/current-wallpaper"), wallpaper.display().to_string()).unwrap();
+        std::env::set_var("XDG_CACHE_HOME", &xdg_cache);
+        let wallust = WallustService::new(config.clone());
+
+        let protected = protected_paths(&sources, &favorites, &wallust);
+        let usage = scan(&config, &sources, &protected);
+        assert_eq!(usage.total(CacheCategory::Downloads), (3, 300));
+        assert_eq!(usage.protected_bytes(CacheCategory::Downloads), 200);
+
+        let filter = PurgeFilter {
+            categories: vec![CacheCategory::Downloads],
+            ..Default::default()
+        };
+        let report = purge(&usage, &filter, false);
+        assert_eq!(report.removed, [other]);
+        assert!(favorite.exists() && wallpaper.exists());
+    }
+
+    #[test]
+    fn remove_entry_takes_sidecars_along() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = file(&dir.path().join("webb-a-screen.jpg"), 10, at(0), at(0));
+        let sidecar = file(&sidecar_path(&image), 10, at(0), at(0));
+        let validators = file(&dir.path().join("webb-a-screen.jpg.http.json"), 10, at(0), at(0));
+        let neighbour = file(&dir.path().join("webb-a-screen.jpg.1920x1080.jpg"), 10, at(0), at(0));
+
+        let usage = usage(&[(image.clone(), CacheCategory::Downloads)], &HashSet::new());
+        assert_eq!(usage.entries[0].size, 30);
+
+        remove_entry(&image).unwrap();
+        assert!(!image.exists() && !sidecar.exists() && !validators.exists());
+        assert!(neighbour.exists());
+    }
+
+    #[test]
+    fn purge_reports_files_it_could_not_remove() {
+        let dir = tempfile::tempdir().unwrap();
+        let removable = file(&dir.path().join("a.jpg"), 100, at(200), at(200));
+        // A directory can't be removed as a file
+        let stuck = dir.path().join("b.jpg");
+        fs::create_dir(&stuck).unwrap();
+        let usage = CacheUsage {
+            entries: vec![
+                CacheEntry {
+                    path: stuck.clone(),
+                    category: CacheCategory::Thumbnails,
+                    source: None,
+                    size: 100,
+                    last_used: at(100),
+                    protected: false,
+                },
+                entry(removable.clone(), CacheCategory::Thumbnails, None, &HashSet::new()).unwrap(),
+            ],
+        };
+
+        let filter = PurgeFilter {
+            categories: vec![CacheCategory::Thumbnails],
+            ..Default::default()
+        };
+        let report = purge(&usage, &filter, false);
+        assert_eq!(report.removed, [removable]);
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, stuck);
+    }
+}
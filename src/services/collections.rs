@@ -0,0 +1,340 @@
+//! Named, user-curated image collections (e.g. "nebulae for the ultrawide").
+//!
+//! Each collection is stored as a TOML file under `<data_dir>/collections/`,
+//! in the same format used for import and export.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use super::image_source::{ImageRecord, ImageSource, DEFAULT_RENDITION};
+use super::wallust::WallustService;
+use crate::config::Config;
+
+/// A named set of images from any source, including local files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Collection {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Index of the next item for wallpaper rotation
+    #[serde(default)]
+    pub position: usize,
+    #[serde(default)]
+    pub items: Vec<ImageRecord>,
+}
+
+impl Collection {
+    /// Create an empty collection.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            description: None,
+            position: 0,
+            items: Vec::new(),
+        }
+    }
+
+    /// Check if the collection contains an image.
+    pub fn contains(&self, source: &str, id: &str) -> bool {
+        self.items.iter().any(|i| i.source == source && i.id == id)
+    }
+}
+
+/// Turn a collection name into a safe file name.
+fn slug(name: &str) -> String {
+    let slug: String = name
+        .trim()
+        .to_lowercase()
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    slug.split('-')
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Service for managing collections.
+pub struct CollectionsService {
+    dir: PathBuf,
+    collections: Mutex<Vec<Collection>>,
+}
+
+impl CollectionsService {
+    /// Create a new collections service, loading any saved collections.
+    pub fn new(config: &Config) -> Self {
+        let dir = config.data_dir().join("collections");
+
+        let mut collections: Vec<Collection> = fs::read_dir(&dir)
+            .map(|entries| {
+                entries
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.extension().is_some_and(|e| e == "toml"))
+                    .filter_map(|p| Self::read_file(&p).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+        collections.sort_by_key(|c| c.name.to_lowercase());
+
+        Self {
+            dir,
+            collections: Mutex::new(collections),
+        }
+    }
+
+    /// Read a collection from a TOML file.
+    fn read_file(path: &Path) -> Result<Collection> {
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read collection: {}", path.display()))?;
+        toml::from_str(&content)
+            .with_context(|| format!("Failed to parse collection: {}", path.display()))
+    }
+
+    /// Write a collection to a TOML file.
+    fn write_file(collection: &Collection, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let content = toml::to_string_pretty(collection)?;
+        let tmp = path.with_extension("toml.tmp");
+        fs::write(&tmp, content)
+            .with_context(|| format!("Failed to write collection: {}", path.display()))?;
+        fs::rename(&tmp, path)
+            .with_context(|| format!("Failed to save collection: {}", path.display()))?;
+        Ok(())
+    }
+
+    /// Path of a collection's state file.
+    fn path_for(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.toml", slug(name)))
+    }
+
+    /// Apply a change to a collection (creating it if needed) and save it.
+    fn modify<T>(&self, name: &str, f: impl FnOnce(&mut Collection) -> T) -> Result<T> {
+        let name = name.trim();
+        if slug(name).is_empty() {
+            anyhow::bail!("Invalid collection name: '{}'", name);
+        }
+
+        let mut collections = self.collections.lock().unwrap();
+        let idx = match collections.iter().position(|c| c.name == name) {
+            Some(idx) => idx,
+            None => {
+                collections.push(Collection::new(name));
+                collections.sort_by_key(|c| c.name.to_lowercase());
+                collections.iter().position(|c| c.name == name).unwrap()
+            }
+        };
+
+        let result = f(&mut collections[idx]);
+        Self::write_file(&collections[idx], &self.path_for(name))?;
+        Ok(result)
+    }
+
+    /// Get the names of all collections.
+    pub fn names(&self) -> Vec<String> {
+        self.collections
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Get a collection by name.
+    pub fn get(&self, name: &str) -> Option<Collection> {
+        self.collections
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|c| c.name == name)
+            .cloned()
+    }
+
+    /// Get all collections.
+    pub fn list(&self) -> Vec<Collection> {
+        self.collections.lock().unwrap().clone()
+    }
+
+    /// Add an image to a collection, creating the collection if needed.
+    /// Returns false if it was already there.
+    pub fn add(&self, name: &str, record: &ImageRecord) -> Result<bool> {
+        self.modify(name, |collection| {
+            if collection.contains(&record.source, &record.id) {
+                false
+            } else {
+                collection.items.push(record.clone());
+                true
+            }
+        })
+    }
+
+    /// Get the names of the collections containing an image.
+    pub fn containing(&self, source: &str, id: &str) -> Vec<String> {
+        self.collections
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|c| c.contains(source, id))
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Refresh the stored record of an image in every collection holding it.
+    pub fn update(&self, record: &ImageRecord) -> Result<()> {
+        for name in self.containing(&record.source, &record.id) {
+            self.modify(&name, |collection| {
+                for item in collection.items.iter_mut() {
+                    if item.source == record.source && item.id == record.id {
+                        *item = record.clone();
+                    }
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Point every collection item of an image at another record (e.g. once
+    /// its files were removed as duplicates), keeping its place in the
+    /// collection. A collection that already holds that record just drops
+    /// the old item.
+    pub fn relink(&self, source: &str, id: &str, record: &ImageRecord) -> Result<()> {
+        for name in self.containing(source, id) {
+            self.modify(&name, |collection| {
+                if collection.contains(&record.source, &record.id) {
+                    collection.items.retain(|i| !(i.source == source && i.id == id));
+                } else {
+                    for item in collection.items.iter_mut().filter(|i| i.source == source && i.id == id) {
+                        *item = record.clone();
+                    }
+                }
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Remove an image from a collection.
+    pub fn remove(&self, name: &str, source: &str, id: &str) -> Result<()> {
+        self.modify(name, |collection| {
+            collection.items.retain(|i| !(i.source == source && i.id == id));
+        })
+    }
+
+    /// Delete a collection.
+    pub fn delete(&self, name: &str) -> Result<()> {
+        let mut collections = self.collections.lock().unwrap();
+        let before = collections.len();
+        collections.retain(|c| c.name != name);
+        if collections.len() == before {
+            anyhow::bail!("No such collection: {}", name);
+        }
+
+        let path = self.path_for(name);
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to delete collection: {}", path.display()))?;
+        }
+        Ok(())
+    }
+
+    /// Export a collection to a TOML file.
+    pub fn export(&self, name: &str, path: &Path) -> Result<()> {
+        let collection = self
+            .get(name)
+            .with_context(|| format!("No such collection: {}", name))?;
+        Self::write_file(&collection, path)
+    }
+
+    /// Import a collection from a TOML file, merging into any existing
+    /// collection of the same name. Returns the collection name.
+    pub fn import(&self, path: &Path) -> Result<String> {
+        let imported = Self::read_file(path)?;
+        let name = imported.name.clone();
+
+        self.modify(&name, |collection| {
+            if collection.description.is_none() {
+                collection.description = imported.description.clone();
+            }
+            for item in imported.items {
+                if !collection.contains(&item.source, &item.id) {
+                    collection.items.push(item);
+                }
+            }
+        })?;
+
+        Ok(name)
+    }
+
+    /// Get the next item in a collection's wallpaper rotation and advance it.
+    pub fn next_in_rotation(&self, name: &str) -> Result<Option<ImageRecord>> {
+        if self.get(name).is_none() {
+            anyhow::bail!("No such collection: {}", name);
+        }
+
+        self.modify(name, |collection| {
+            if collection.items.is_empty() {
+                return None;
+            }
+            let idx = collection.position % collection.items.len();
+            collection.position = (idx + 1) % collection.items.len();
+            Some(collection.items[idx].clone())
+        })
+    }
+}
+
+/// Apply the next image in a collection's rotation as the wallpaper,
+/// downloading it first if needed.
+///
+/// Items that can't be fetched (unknown source, failed download, deleted
+/// local file) are skipped. Returns the applied image.
+pub async fn rotate_wallpaper(
+    collections: &CollectionsService,
+    name: &str,
+    sources: &[Arc<dyn ImageSource>],
+    wallust: &WallustService,
+) -> Result<ImageRecord> {
+    let count = collections
+        .get(name)
+        .with_context(|| format!("No such collection: {}", name))?
+        .items
+        .len();
+
+    let mut failures = Vec::new();
+    for _ in 0..count {
+        let image = match collections.next_in_rotation(name)? {
+            Some(image) => image,
+            None => break,
+        };
+        let source = match sources.iter().find(|s| s.key() == image.source) {
+            Some(source) => source,
+            None => {
+                failures.push(format!("{}: unknown source {}", image.id, image.source));
+                continue;
+            }
+        };
+
+        let path = match source.get_downloaded_path(&image) {
+            Some(path) => path,
+            None => match source.download_image(&image, DEFAULT_RENDITION).await {
+                Ok(path) => path,
+                Err(e) => {
+                    failures.push(format!("{}: {}", image.id, e));
+                    continue;
+                }
+            },
+        };
+
+        wallust.apply_wallpaper(&path)?;
+        return Ok(image);
+    }
+
+    if failures.is_empty() {
+        anyhow::bail!("Collection '{}' is empty", name)
+    } else {
+        anyhow::bail!("No usable image in '{}': {}", name, failures.join("; "))
+    }
+}
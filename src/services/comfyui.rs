@@ -2,7 +2,6 @@
 
 use anyhow::{Context, Result};
 use futures_util::{SinkExt, StreamExt};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
@@ -12,6 +11,9 @@ use tokio::sync::RwLock;
 use tokio_tungstenite::tungstenite::Message;
 use uuid::Uuid;
 
+use super::http::{HttpClient, Service};
+use crate::config::Config;
+
 /// Generation progress information.
 #[derive(Debug, Clone)]
 pub struct GenerationProgress {
@@ -73,22 +75,19 @@ struct ImageOutput {
 
 /// Service for interacting with ComfyUI.
 pub struct ComfyUiService {
-    client: Client,
+    client: HttpClient,
     base_url: Arc<RwLock<Option<String>>>,
     client_id: String,
 }
 
 impl ComfyUiService {
     /// Create a new ComfyUI service.
-    pub fn new() -> Self {
-        Self {
-            client: Client::builder()
-                .timeout(std::time::Duration::from_secs(600))
-                .build()
-                .expect("Failed to create HTTP client"),
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: HttpClient::new(config, Service::ComfyUi)?,
             base_url: Arc::new(RwLock::new(None)),
             client_id: Uuid::new_v4().to_string(),
-        }
+        })
     }
 
     /// Set the base URL (from SSH tunnel).
@@ -119,52 +118,6 @@ impl ComfyUiService {
         }
     }
 
-    /// Upload an image to ComfyUI.
-    pub async fn upload_image(&self, image_path: &Path) -> Result<String> {
-        let base_url = self.get_base_url().await?;
-
-        let file_name = image_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .context("Invalid image filename")?;
-
-        let file_bytes = std::fs::read(image_path)
-            .context(format!("Failed to read image: {:?}", image_path))?;
-
-        let part = reqwest::multipart::Part::bytes(file_bytes)
-            .file_name(file_name.to_string())
-            .mime_str("image/jpeg")?;
-
-        let form = reqwest::multipart::Form::new()
-            .part("image", part)
-            .text("overwrite", "true");
-
-        let response = self
-            .client
-            .post(format!("{}/upload/image", base_url))
-            .multipart(form)
-            .send()
-            .await
-            .context("Failed to upload image")?;
-
-        if !response.status().is_success() {
-            let error_text = response.text().await.unwrap_or_default();
-            anyhow::bail!("Upload failed: {}", error_text);
-        }
-
-        #[derive(Deserialize)]
-        struct UploadResponse {
-            name: String,
-        }
-
-        let upload_resp: UploadResponse = response
-            .json()
-            .await
-            .context("Failed to parse upload response")?;
-
-        Ok(upload_resp.name)
-    }
-
     /// Load a workflow template and substitute parameters.
     fn prepare_workflow(
         &self,
@@ -437,9 +390,3 @@ impl ComfyUiService {
         Ok(())
     }
 }
-
-impl Default for ComfyUiService {
-    fn default() -> Self {
-        Self::new()
-    }
-}
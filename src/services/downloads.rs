@@ -0,0 +1,376 @@
+//! Background image downloads with a bounded worker pool.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::{mpsc, Semaphore};
+
+use super::image_source::{ImageRecord, ImageSource, DEFAULT_RENDITION};
+use super::library::LibraryIndex;
+
+/// State of one queued download.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DownloadState {
+    Queued,
+    Running { downloaded: u64, total: Option<u64> },
+    Done(PathBuf),
+    Failed(String),
+}
+
+/// A download in the current batch.
+#[derive(Debug, Clone)]
+pub struct DownloadItem {
+    /// `source:id` of the image
+    pub key: String,
+    pub title: String,
+    pub state: DownloadState,
+}
+
+/// Progress message from a worker.
+enum DownloadEvent {
+    Progress(String, u64, Option<u64>),
+    Finished(String, PathBuf),
+    Failed(String, String),
+}
+
+/// Runs downloads in the background, at most `limit` at a time.
+///
+/// A failed item is recorded and the rest of the batch keeps going. Each
+/// finished download is added to the library index.
+pub struct DownloadManager {
+    limit: Arc<Semaphore>,
+    library: Arc<LibraryIndex>,
+    tx: mpsc::UnboundedSender<DownloadEvent>,
+    rx: mpsc::UnboundedReceiver<DownloadEvent>,
+    items: Vec<DownloadItem>,
+}
+
+impl DownloadManager {
+    /// Create a manager running up to `limit` downloads in parallel.
+    pub fn new(limit: usize, library: Arc<LibraryIndex>) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        Self {
+            limit: Arc::new(Semaphore::new(limit.max(1))),
+            library,
+            tx,
+            rx,
+            items: Vec::new(),
+        }
+    }
+
+    /// Queue an image for download. Returns false if it's already queued
+    /// or running.
+    pub fn enqueue(&mut self, source: Arc<dyn ImageSource>, image: ImageRecord, rendition: &str) -> bool {
+        let key = format!("{}:{}", image.source, image.id);
+        let pending = |i: &DownloadItem| {
+            i.key == key && matches!(i.state, DownloadState::Queued | DownloadState::Running { .. })
+        };
+        if self.items.iter().any(pending) {
+            return false;
+        }
+
+        // A new batch starts once the previous one has finished
+        if !self.is_active() {
+            self.items.clear();
+        }
+        self.items.retain(|i| i.key != key);
+        let title = if rendition == DEFAULT_RENDITION {
+            image.title.clone()
+        } else {
+            format!("{} ({})", image.title, rendition)
+        };
+        self.items.push(DownloadItem {
+            key: key.clone(),
+            title,
+            state: DownloadState::Queued,
+        });
+
+        let tx = self.tx.clone();
+        let limit = self.limit.clone();
+        let library = self.library.clone();
+        let rendition = rendition.to_string();
+        tokio::spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let progress_tx = tx.clone();
+            let progress_key = key.clone();
+            let progress = move |downloaded, total| {
+                let _ = progress_tx.send(DownloadEvent::Progress(progress_key.clone(), downloaded, total));
+            };
+            let result = source.download_image_with_progress(&image, &rendition, &progress).await;
+            let event = match result {
+                Ok(path) => {
+                    // Indexed before reporting, so it shows as downloaded
+                    // straight away
+                    let _ = tokio::task::spawn_blocking(move || {
+                        library.add(&source.downloaded_files(&image), Some(&image))
+                    })
+                    .await;
+                    DownloadEvent::Finished(key, path)
+                }
+                Err(e) => DownloadEvent::Failed(key, e.to_string()),
+            };
+            let _ = tx.send(event);
+        });
+
+        true
+    }
+
+    /// Apply progress from the workers. Returns the downloads that finished
+    /// since the last poll.
+    pub fn poll(&mut self) -> Vec<(String, PathBuf)> {
+        let mut finished = Vec::new();
+        while let Ok(event) = self.rx.try_recv() {
+            let (key, state) = match event {
+                DownloadEvent::Progress(key, downloaded, total) => {
+                    (key, DownloadState::Running { downloaded, total })
+                }
+                DownloadEvent::Finished(key, path) => {
+                    finished.push((key.clone(), path.clone()));
+                    (key, DownloadState::Done(path))
+                }
+                DownloadEvent::Failed(key, error) => (key, DownloadState::Failed(error)),
+            };
+            if let Some(item) = self.items.iter_mut().find(|i| i.key == key) {
+                item.state = state;
+            }
+        }
+        finished
+    }
+
+    /// Items in the current (or last) batch, in queue order.
+    pub fn items(&self) -> &[DownloadItem] {
+        &self.items
+    }
+
+    /// Whether anything is still queued or running.
+    pub fn is_active(&self) -> bool {
+        self.items
+            .iter()
+            .any(|i| matches!(i.state, DownloadState::Queued | DownloadState::Running { .. }))
+    }
+
+    /// Check whether an image is queued or downloading.
+    pub fn is_pending(&self, key: &str) -> bool {
+        self.items.iter().any(|i| {
+            i.key == key && matches!(i.state, DownloadState::Queued | DownloadState::Running { .. })
+        })
+    }
+
+    /// Overall completion of the batch from 0.0 to 1.0, counting the bytes
+    /// of running downloads.
+    pub fn ratio(&self) -> f64 {
+        if self.items.is_empty() {
+            return 0.0;
+        }
+        let completed: f64 = self
+            .items
+            .iter()
+            .map(|i| match i.state {
+                DownloadState::Queued => 0.0,
+                DownloadState::Running { downloaded, total: Some(total) } if total > 0 => {
+                    (downloaded as f64 / total as f64).min(1.0)
+                }
+                DownloadState::Running { .. } => 0.0,
+                DownloadState::Done(_) | DownloadState::Failed(_) => 1.0,
+            })
+            .sum();
+        completed / self.items.len() as f64
+    }
+
+    /// Bytes received so far by running downloads.
+    pub fn bytes_in_flight(&self) -> u64 {
+        self.items
+            .iter()
+            .map(|i| match i.state {
+                DownloadState::Running { downloaded, .. } => downloaded,
+                _ => 0,
+            })
+            .sum()
+    }
+
+    /// Counts for the batch: (done, failed, total).
+    pub fn summary(&self) -> (usize, usize, usize) {
+        let done = self
+            .items
+            .iter()
+            .filter(|i| matches!(i.state, DownloadState::Done(_)))
+            .count();
+        let failed = self
+            .items
+            .iter()
+            .filter(|i| matches!(i.state, DownloadState::Failed(_)))
+            .count();
+        (done, failed, self.items.len())
+    }
+
+    /// Forget a finished batch.
+    pub fn clear_finished(&mut self) {
+        if !self.is_active() {
+            self.items.clear();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{Config, JwstConfig};
+    use crate::services::image_source::Rendition;
+    use crate::services::test_server::{Reply, TestServer};
+    use crate::services::EsaService;
+    use std::time::Duration;
+
+    fn png() -> Vec<u8> {
+        let image = image::RgbImage::from_fn(16, 16, |x, y| image::Rgb([(x * 16) as u8, (y * 16) as u8, 0]));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    struct Fixture {
+        _dir: tempfile::TempDir,
+        source: Arc<dyn ImageSource>,
+        library: Arc<LibraryIndex>,
+    }
+
+    fn fixture() -> Fixture {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.jwst.wallpaper_dir = dir.path().join("wallpapers").display().to_string();
+        config.jwst.cache_dir = dir.path().join("cache").display().to_string();
+        config.jwst.data_dir = dir.path().join("data").display().to_string();
+        config.network.retries = 0;
+        config.network.requests_per_second = 0.0;
+
+        let feed = JwstConfig::default().esa_feeds().remove(0);
+        let source: Arc<dyn ImageSource> = Arc::new(EsaService::new(Arc::new(config.clone()), feed).unwrap());
+        let library = Arc::new(LibraryIndex::new(&config, std::slice::from_ref(&source)));
+        Fixture {
+            _dir: dir,
+            source,
+            library,
+        }
+    }
+
+    fn image(server: &TestServer, id: &str) -> ImageRecord {
+        ImageRecord {
+            source: "esa".to_string(),
+            id: id.to_string(),
+            title: format!("Image {}", id),
+            date: None,
+            description: None,
+            credit: None,
+            page_url: None,
+            renditions: vec![Rendition {
+                name: "screen".to_string(),
+                url: format!("{}/{}.png", server.url, id),
+            }],
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: Vec::new(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// Poll until the batch is done, collecting what finished.
+    async fn settle(manager: &mut DownloadManager) -> Vec<(String, PathBuf)> {
+        let mut finished = Vec::new();
+        for _ in 0..500 {
+            finished.extend(manager.poll());
+            if !manager.is_active() {
+                return finished;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("downloads didn't finish");
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn runs_at_most_limit_downloads_at_once() {
+        let body = png();
+        let server = TestServer::start(move |_, _| {
+            Reply::new(200)
+                .header("Content-Type", "image/png")
+                .body(&body)
+                .delay(Duration::from_millis(100))
+        })
+        .await;
+        let fixture = fixture();
+        let mut manager = DownloadManager::new(2, fixture.library.clone());
+
+        for id in ["a", "b", "c", "d", "e"] {
+            assert!(manager.enqueue(fixture.source.clone(), image(&server, id), "screen"));
+        }
+        let finished = settle(&mut manager).await;
+
+        assert_eq!(finished.len(), 5);
+        assert_eq!(server.requests().len(), 5);
+        assert_eq!(server.max_in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn every_outcome_is_delivered() {
+        let body = png();
+        let server = TestServer::start(move |_, request| {
+            if request.path == "/broken.png" {
+                Reply::new(500)
+            } else {
+                Reply::new(200).header("Content-Type", "image/png").body(&body)
+            }
+        })
+        .await;
+        let fixture = fixture();
+        let mut manager = DownloadManager::new(3, fixture.library.clone());
+
+        for id in ["a", "broken", "b"] {
+            manager.enqueue(fixture.source.clone(), image(&server, id), "screen");
+        }
+        // Already pending
+        assert!(!manager.enqueue(fixture.source.clone(), image(&server, "a"), "screen"));
+
+        let mut finished = settle(&mut manager).await;
+        finished.sort();
+        let keys: Vec<&str> = finished.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(keys, ["esa:a", "esa:b"]);
+        assert_eq!(manager.summary(), (2, 1, 3));
+        assert_eq!(manager.ratio(), 1.0);
+        assert!(matches!(manager.items()[1].state, DownloadState::Failed(_)));
+
+        // Indexed before being reported
+        for (_, path) in &finished {
+            assert!(path.is_file());
+            assert_eq!(fixture.library.first_indexed(std::slice::from_ref(path)).as_ref(), Some(path));
+        }
+    }
+
+    #[tokio::test]
+    async fn progress_is_reported_while_running() {
+        let body = png();
+        let server = TestServer::start(move |_, _| {
+            Reply::new(200)
+                .header("Content-Type", "image/png")
+                .body(&body)
+                .delay(Duration::from_millis(50))
+        })
+        .await;
+        let fixture = fixture();
+        let mut manager = DownloadManager::new(1, fixture.library.clone());
+        manager.enqueue(fixture.source.clone(), image(&server, "a"), "screen");
+        manager.enqueue(fixture.source.clone(), image(&server, "b"), "screen");
+
+        let mut seen_running = false;
+        for _ in 0..500 {
+            manager.poll();
+            seen_running |= manager.items().iter().any(|i| matches!(i.state, DownloadState::Running { .. }));
+            if !manager.is_active() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        assert!(seen_running);
+        assert_eq!(manager.summary(), (2, 0, 2));
+
+        // The next enqueue starts a new batch
+        manager.enqueue(fixture.source.clone(), image(&server, "c"), "screen");
+        assert_eq!(manager.items().len(), 1);
+    }
+}
@@ -0,0 +1,286 @@
+//! Rich per-image metadata scraped from ESA-style gallery detail pages.
+
+use serde::{Deserialize, Serialize};
+
+use super::image_source::ImageRecord;
+use crate::utils::html;
+
+/// Metadata from an image's gallery page.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EsaMetadata {
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub credit: Option<String>,
+    #[serde(default)]
+    pub release_date: Option<String>,
+    #[serde(default)]
+    pub object_name: Option<String>,
+    #[serde(default)]
+    pub object_type: Option<String>,
+    #[serde(default)]
+    pub constellation: Option<String>,
+    #[serde(default)]
+    pub distance: Option<String>,
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    #[serde(default)]
+    pub filters: Vec<String>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    /// Original pixel dimensions
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+impl EsaMetadata {
+    /// Whether the page yielded nothing, e.g. a placeholder or error page.
+    pub fn is_empty(&self) -> bool {
+        self.description.is_none()
+            && self.credit.is_none()
+            && self.release_date.is_none()
+            && self.object_name.is_none()
+            && self.object_type.is_none()
+            && self.constellation.is_none()
+            && self.distance.is_none()
+            && self.instruments.is_empty()
+            && self.filters.is_empty()
+            && self.categories.is_empty()
+            && self.width.is_none()
+            && self.height.is_none()
+    }
+
+    /// Merge this metadata into an image record.
+    pub fn apply_to(&self, record: &mut ImageRecord) {
+        if self.description.is_some() {
+            record.description = self.description.clone();
+        }
+        if self.credit.is_some() {
+            record.credit = self.credit.clone();
+        }
+
+        let mut set = |label: &str, value: Option<String>| {
+            if let Some(value) = value.filter(|v| !v.is_empty()) {
+                record.fields.retain(|(l, _)| l != label);
+                record.fields.push((label.to_string(), value));
+            }
+        };
+
+        set("Object", self.object_name.clone());
+        set("Object type", self.object_type.clone());
+        set("Constellation", self.constellation.clone());
+        set("Distance", self.distance.clone());
+        set("Filters", Some(self.filters.join(", ")));
+
+        if !self.instruments.is_empty() {
+            record.instruments = self.instruments.clone();
+        }
+        if !self.categories.is_empty() {
+            record.categories = self.categories.clone();
+        }
+        if let Some(dimensions) = self.width.zip(self.height) {
+            record.dimensions = Some(dimensions);
+        }
+    }
+}
+
+/// Parse an image detail page.
+///
+/// Gallery pages list their facts as `<td class="title">Label:</td><td>Value</td>`
+/// rows in tables under "About the Image" / "About the Object" headings, and
+/// the filters in a "Colours & filters" table of band / wavelength / telescope
+/// cells.
+pub fn parse_image_page(page: &str) -> EsaMetadata {
+    let mut meta = EsaMetadata::default();
+
+    // Description: paragraphs between the title and the credit block
+    if let Some(title_end) = page.find("</h1>") {
+        let body = &page[title_end..];
+        let body_end = body
+            .find("class=\"credit\"")
+            .or_else(|| body.find("<h3"))
+            .unwrap_or(body.len());
+        let paragraphs: Vec<String> = html::elements(&body[..body_end], "p")
+            .into_iter()
+            .map(html::to_text)
+            .filter(|p| !p.is_empty())
+            .collect();
+        if !paragraphs.is_empty() {
+            meta.description = Some(paragraphs.join("\n\n"));
+        }
+    }
+
+    // Credit block
+    if let Some(pos) = page.find("class=\"credit\"") {
+        let block = &page[pos..];
+        let block = block.find('>').map(|p| &block[p + 1..]).unwrap_or(block);
+        let block = &block[..block.find("</div>").unwrap_or(block.len())];
+        let credit = html::to_text(block)
+            .lines()
+            .filter(|line| !line.eq_ignore_ascii_case("credit:"))
+            .collect::<Vec<_>>()
+            .join(" ");
+        if !credit.is_empty() {
+            meta.credit = Some(credit);
+        }
+    }
+
+    // Fact tables, one section per <h3> heading
+    let headings: Vec<usize> = page.match_indices("<h3").map(|(i, _)| i).collect();
+    for (n, &start) in headings.iter().enumerate() {
+        let end = headings.get(n + 1).copied().unwrap_or(page.len());
+        let section = &page[start..end];
+        let heading = html::elements(section, "h3")
+            .first()
+            .map(|h| html::to_text(h).to_lowercase())
+            .unwrap_or_default();
+
+        for row in html::elements(section, "tr") {
+            let cells: Vec<String> = html::elements(row, "td")
+                .into_iter()
+                .map(html::to_text)
+                .collect();
+
+            if heading.starts_with("colours") {
+                // Band + filter / wavelength / telescope + instrument
+                if cells.len() >= 3 {
+                    if let Some(filter) = cells[0].lines().last() {
+                        push_unique(&mut meta.filters, filter);
+                    }
+                    if let Some(instrument) = cells[2].lines().last() {
+                        push_unique(&mut meta.instruments, instrument);
+                    }
+                }
+                continue;
+            }
+
+            if cells.len() < 2 {
+                continue;
+            }
+            let label = cells[0].trim_end_matches(':').to_lowercase();
+            let value = cells[1].clone();
+            let first_line = value.lines().next().unwrap_or("").to_string();
+
+            match (heading.as_str(), label.as_str()) {
+                (_, "size") => {
+                    // e.g. "4500 x 4500 px"
+                    let dims: Vec<u32> = value
+                        .split(|c: char| !c.is_ascii_digit())
+                        .filter_map(|n| n.parse().ok())
+                        .collect();
+                    if dims.len() >= 2 {
+                        meta.width = Some(dims[0]);
+                        meta.height = Some(dims[1]);
+                    }
+                }
+                (_, "release date") => meta.release_date = Some(first_line),
+                (h, "name") if h.contains("object") => meta.object_name = Some(value.replace('\n', ", ")),
+                (h, "type") if h.contains("object") => meta.object_type = Some(first_line),
+                (_, "distance") => meta.distance = Some(first_line),
+                (_, "constellation") => meta.constellation = Some(first_line),
+                (_, "category") | (_, "categories") => {
+                    for category in value.lines() {
+                        push_unique(&mut meta.categories, category);
+                    }
+                }
+                (_, "instrument") | (_, "instruments") => {
+                    for instrument in value.lines() {
+                        push_unique(&mut meta.instruments, instrument);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    meta
+}
+
+/// Push a trimmed value unless it is empty or already present.
+fn push_unique(list: &mut Vec<String>, value: &str) {
+    let value = value.trim();
+    if !value.is_empty() && !list.iter().any(|v| v == value) {
+        list.push(value.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const IMAGE_PAGE: &str = include_str!("../../tests/fixtures/esa_image_page.html");
+
+    #[test]
+    fn parses_description_and_credit() {
+        let meta = parse_image_page(IMAGE_PAGE);
+        assert_eq!(
+            meta.description.as_deref(),
+            Some(
+                "What looks much like craggy mountains on a moonlit evening is actually the \
+                 edge of a nearby, young, star-forming region called NGC 3324 in the Carina Nebula.\n\n\
+                 Captured in infrared light by Webb\u{2019}s Near-Infrared Camera (NIRCam), this image \
+                 reveals previously obscured areas of star birth.\n\
+                 Called the \u{201C}Cosmic Cliffs\u{201D}, the region is roughly 7600 light-years away."
+            )
+        );
+        assert_eq!(meta.credit.as_deref(), Some("NASA, ESA, CSA, and STScI"));
+    }
+
+    #[test]
+    fn parses_fact_tables() {
+        let meta = parse_image_page(IMAGE_PAGE);
+        assert_eq!((meta.width, meta.height), (Some(14575), Some(8441)));
+        assert_eq!(meta.release_date.as_deref(), Some("12 July 2022, 16:30"));
+        assert_eq!(meta.object_name.as_deref(), Some("Carina Nebula, NGC 3324"));
+        assert_eq!(meta.object_type.as_deref(), Some("Milky Way : Nebula : Type : Star Formation"));
+        assert_eq!(meta.distance.as_deref(), Some("7600 light years"));
+        assert_eq!(meta.constellation.as_deref(), Some("Carina"));
+        assert_eq!(meta.categories, ["Nebulae", "Stars"]);
+    }
+
+    #[test]
+    fn parses_filters_and_instruments_without_duplicates() {
+        let meta = parse_image_page(IMAGE_PAGE);
+        assert_eq!(meta.filters, ["F090W", "F187N", "F770W"]);
+        assert_eq!(meta.instruments, ["NIRCam", "MIRI"]);
+    }
+
+    #[test]
+    fn applies_lists_and_dimensions_as_typed_fields() {
+        let mut record = ImageRecord {
+            source: "esa".to_string(),
+            id: "weic2425a".to_string(),
+            title: "Cosmic Cliffs".to_string(),
+            date: None,
+            description: None,
+            credit: None,
+            page_url: None,
+            renditions: Vec::new(),
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: Vec::new(),
+            fields: Vec::new(),
+        };
+        parse_image_page(IMAGE_PAGE).apply_to(&mut record);
+
+        assert_eq!(record.dimensions, Some((14575, 8441)));
+        assert_eq!(record.categories, ["Nebulae", "Stars"]);
+        assert_eq!(record.instruments, ["NIRCam", "MIRI"]);
+        assert_eq!(record.field("Constellation"), Some("Carina"));
+        assert_eq!(record.field("Filters"), Some("F090W, F187N, F770W"));
+        assert_eq!(record.field("Dimensions"), None);
+
+        // Metadata without the lists keeps what the listing already had
+        EsaMetadata::default().apply_to(&mut record);
+        assert_eq!(record.dimensions, Some((14575, 8441)));
+        assert_eq!(record.instruments, ["NIRCam", "MIRI"]);
+    }
+
+    #[test]
+    fn unrelated_page_is_empty() {
+        assert!(parse_image_page("<html><body><p>Page not found</p></body></html>").is_empty());
+        assert!(!parse_image_page(IMAGE_PAGE).is_empty());
+    }
+}
@@ -0,0 +1,142 @@
+//! Starred images, persisted in the data directory.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::image_source::ImageRecord;
+use crate::config::Config;
+
+/// A starred image.
+///
+/// The full record is stored so favorites can be shown and re-downloaded
+/// after they drop off the feed or the metadata cache expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Favorite {
+    pub record: ImageRecord,
+    pub added: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FavoritesFile {
+    #[serde(default)]
+    favorites: Vec<Favorite>,
+}
+
+/// Service for managing favorites.
+pub struct FavoritesService {
+    path: PathBuf,
+    favorites: Mutex<Vec<Favorite>>,
+}
+
+impl FavoritesService {
+    /// Create a new favorites service, loading any saved favorites.
+    pub fn new(config: &Config) -> Self {
+        let path = config.data_dir().join("favorites.json");
+
+        let favorites = fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str::<FavoritesFile>(&content).ok())
+            .map(|file| file.favorites)
+            .unwrap_or_default();
+
+        Self {
+            path,
+            favorites: Mutex::new(favorites),
+        }
+    }
+
+    /// Write favorites to disk.
+    fn save(&self, favorites: &[Favorite]) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = FavoritesFile {
+            favorites: favorites.to_vec(),
+        };
+        let content = serde_json::to_string_pretty(&file)?;
+
+        // Write to a temp file first so a crash can't truncate the list
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, content).context("Failed to write favorites")?;
+        fs::rename(&tmp, &self.path).context("Failed to save favorites")?;
+        Ok(())
+    }
+
+    /// Check if an image is starred.
+    pub fn is_favorite(&self, source: &str, id: &str) -> bool {
+        self.favorites
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|f| f.record.source == source && f.record.id == id)
+    }
+
+    /// Star or unstar an image. Returns whether it is now a favorite.
+    pub fn toggle(&self, record: &ImageRecord) -> Result<bool> {
+        let mut favorites = self.favorites.lock().unwrap();
+        let before = favorites.len();
+        favorites.retain(|f| !(f.record.source == record.source && f.record.id == record.id));
+
+        let starred = favorites.len() == before;
+        if starred {
+            favorites.push(Favorite {
+                record: record.clone(),
+                added: Utc::now(),
+            });
+        }
+
+        self.save(&favorites)?;
+        Ok(starred)
+    }
+
+    /// Refresh the stored record of a favorite (e.g. after details load).
+    pub fn update(&self, record: &ImageRecord) -> Result<()> {
+        let mut favorites = self.favorites.lock().unwrap();
+        let existing = favorites
+            .iter_mut()
+            .find(|f| f.record.source == record.source && f.record.id == record.id);
+
+        match existing {
+            Some(favorite) => {
+                favorite.record = record.clone();
+                self.save(&favorites)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Point a favorite at another record (e.g. once its files were removed
+    /// as duplicates), keeping when it was starred. If that record is
+    /// already a favorite, the old one is dropped.
+    pub fn relink(&self, source: &str, id: &str, record: &ImageRecord) -> Result<()> {
+        let mut favorites = self.favorites.lock().unwrap();
+        let Some(pos) = favorites
+            .iter()
+            .position(|f| f.record.source == source && f.record.id == id)
+        else {
+            return Ok(());
+        };
+
+        if favorites
+            .iter()
+            .any(|f| f.record.source == record.source && f.record.id == record.id)
+        {
+            favorites.remove(pos);
+        } else {
+            favorites[pos].record = record.clone();
+        }
+        self.save(&favorites)
+    }
+
+    /// Get all favorites, most recently starred first.
+    pub fn list(&self) -> Vec<Favorite> {
+        let mut favorites = self.favorites.lock().unwrap().clone();
+        favorites.sort_by_key(|f| std::cmp::Reverse(f.added));
+        favorites
+    }
+}
@@ -0,0 +1,332 @@
+//! Shared HTTP client for every service.
+//!
+//! Applies the user agent, proxy, extra CA roots and per-service timeouts,
+//! retries transient failures (timeouts, dropped connections, 429 and 5xx)
+//! with exponential backoff, and spaces out requests to the same image host.
+
+use anyhow::{Context, Result};
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Certificate, ClientBuilder, IntoUrl, Method, NoProxy, Proxy, Response, StatusCode};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::config::Config;
+
+/// User agent sent with every request.
+pub const USER_AGENT: &str = "JWST-Cosmos/0.1.0 (Rust; Ratatui TUI)";
+
+/// Delay before the first retry; doubles each time.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Longest wait between retries, including server-sent Retry-After.
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Next free request slot per host, shared by every client.
+static NEXT_SLOT: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+
+/// Which service a client is for; each has its own timeouts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Service {
+    /// Feeds, APIs, thumbnails and image downloads
+    Images,
+    /// ComfyUI on the remote GPU host (generations can take minutes)
+    ComfyUi,
+    /// Ollama on the remote GPU host
+    Ollama,
+}
+
+/// HTTP client with retries and per-host rate limiting.
+#[derive(Debug, Clone)]
+pub struct HttpClient {
+    inner: reqwest::Client,
+    retries: u32,
+    /// Minimum gap between requests to one host
+    interval: Option<Duration>,
+}
+
+impl HttpClient {
+    /// Build the client for a service. Fails on a bad `[network]` proxy or
+    /// CA setting rather than silently going without it.
+    pub fn new(config: &Config, service: Service) -> Result<Self> {
+        let network = &config.network;
+        let builder = configure(reqwest::Client::builder().user_agent(USER_AGENT), config)?;
+        let (builder, retries, interval) = match service {
+            // No overall timeout: a full-size TIFF can take a long time,
+            // but a stalled transfer still fails
+            Service::Images => (
+                builder
+                    .connect_timeout(Duration::from_secs(15))
+                    .read_timeout(Duration::from_secs(60)),
+                network.retries,
+                (network.requests_per_second > 0.0)
+                    .then(|| Duration::from_secs_f64(1.0 / network.requests_per_second)),
+            ),
+            // The tunnel is local; a failure there is rarely worth more
+            // than one retry
+            Service::ComfyUi => (builder.timeout(Duration::from_secs(600)), network.retries.min(1), None),
+            Service::Ollama => (builder.timeout(Duration::from_secs(300)), network.retries.min(1), None),
+        };
+
+        Ok(Self {
+            inner: builder.build().context("Failed to create HTTP client")?,
+            retries,
+            interval,
+        })
+    }
+
+    pub fn get(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::GET, url)
+    }
+
+    pub fn head(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::HEAD, url)
+    }
+
+    pub fn post(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::POST, url)
+    }
+
+    pub fn delete(&self, url: impl IntoUrl) -> HttpRequest<'_> {
+        self.request(Method::DELETE, url)
+    }
+
+    pub fn request(&self, method: Method, url: impl IntoUrl) -> HttpRequest<'_> {
+        HttpRequest {
+            client: self,
+            inner: self.inner.request(method, url),
+        }
+    }
+
+    /// Send a request, retrying transient failures.
+    ///
+    /// POSTs are only retried when the connection failed, since the server
+    /// may have acted on them. Bodies that can't be replayed (streams) get a
+    /// single attempt. After the last retry, the final response is returned
+    /// whatever its status.
+    async fn execute(&self, request: reqwest::Request) -> reqwest::Result<Response> {
+        let idempotent = !matches!(*request.method(), Method::POST | Method::PATCH);
+        let host = request.url().host_str().unwrap_or_default().to_string();
+
+        let mut attempt = 0;
+        loop {
+            let copy = if attempt < self.retries { request.try_clone() } else { None };
+            let Some(copy) = copy else {
+                self.throttle(&host).await;
+                return self.inner.execute(request).await;
+            };
+
+            self.throttle(&host).await;
+            let delay = match self.inner.execute(copy).await {
+                Ok(response) if idempotent && is_transient(response.status()) => {
+                    tracing::debug!("{} returned {}, retrying", response.url(), response.status());
+                    retry_after(&response).unwrap_or_else(|| backoff(attempt))
+                }
+                Err(e) if e.is_connect() || (idempotent && e.is_timeout()) => {
+                    tracing::debug!("Request failed, retrying: {}", e);
+                    backoff(attempt)
+                }
+                result => return result,
+            };
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Wait for this client's turn at `host`.
+    async fn throttle(&self, host: &str) {
+        let Some(interval) = self.interval else {
+            return;
+        };
+        let slot = {
+            let mut slots = NEXT_SLOT.get_or_init(Default::default).lock().unwrap();
+            let now = Instant::now();
+            let slot = slots.get(host).map_or(now, |next| (*next).max(now));
+            slots.insert(host.to_string(), slot + interval);
+            slot
+        };
+        tokio::time::sleep_until(slot.into()).await;
+    }
+}
+
+/// Check that the `[network]` proxy and CA settings are usable.
+pub fn check_config(config: &Config) -> Result<()> {
+    configure(reqwest::Client::builder(), config)?
+        .build()
+        .context("Failed to create HTTP client")?;
+    Ok(())
+}
+
+/// Apply the proxy and extra CA roots from `[network]`.
+fn configure(mut builder: ClientBuilder, config: &Config) -> Result<ClientBuilder> {
+    let network = &config.network;
+
+    if let Some(url) = network.proxy.as_deref().filter(|url| !url.is_empty()) {
+        let proxy = Proxy::all(url).with_context(|| format!("Invalid proxy URL: {}", url))?;
+        builder = builder.proxy(proxy.no_proxy(NoProxy::from_string(&network.no_proxy.join(","))));
+    }
+
+    for file in &network.ca_certificates {
+        let path = config.expand_path(file);
+        let pem = fs::read(&path).with_context(|| format!("Failed to read CA file {}", path.display()))?;
+        let certificates = Certificate::from_pem_bundle(&pem)
+            .with_context(|| format!("Invalid PEM in {}", path.display()))?;
+        if certificates.is_empty() {
+            anyhow::bail!("No certificates in {}", path.display());
+        }
+        for certificate in certificates {
+            builder = builder.add_root_certificate(certificate);
+        }
+    }
+
+    Ok(builder)
+}
+
+/// A request being built; sent through its client's retry policy.
+pub struct HttpRequest<'a> {
+    client: &'a HttpClient,
+    inner: reqwest::RequestBuilder,
+}
+
+impl HttpRequest<'_> {
+    pub fn header(self, key: impl AsRef<str>, value: impl AsRef<str>) -> Self {
+        Self {
+            inner: self.inner.header(key.as_ref(), value.as_ref()),
+            ..self
+        }
+    }
+
+    pub fn query<T: Serialize + ?Sized>(self, query: &T) -> Self {
+        Self {
+            inner: self.inner.query(query),
+            ..self
+        }
+    }
+
+    pub fn json<T: Serialize + ?Sized>(self, json: &T) -> Self {
+        Self {
+            inner: self.inner.json(json),
+            ..self
+        }
+    }
+
+    pub async fn send(self) -> reqwest::Result<Response> {
+        let request = self.inner.build()?;
+        self.client.execute(request).await
+    }
+}
+
+/// Statuses worth retrying.
+fn is_transient(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Delay before retry `attempt` (counting from 0).
+fn backoff(attempt: u32) -> Duration {
+    BASE_BACKOFF.saturating_mul(2u32.saturating_pow(attempt)).min(MAX_BACKOFF)
+}
+
+/// Server-requested delay, when given in seconds.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds: u64 = response.headers().get(RETRY_AFTER)?.to_str().ok()?.trim().parse().ok()?;
+    Some(Duration::from_secs(seconds).min(MAX_BACKOFF))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::test_server::{Reply, TestServer};
+
+    fn client(retries: u32, requests_per_second: f64) -> HttpClient {
+        let mut config = Config::default();
+        config.network.retries = retries;
+        config.network.requests_per_second = requests_per_second;
+        HttpClient::new(&config, Service::Images).unwrap()
+    }
+
+    /// Gaps between consecutive requests the server saw.
+    fn gaps(server: &TestServer) -> Vec<Duration> {
+        let requests = server.requests();
+        requests.windows(2).map(|w| w[1].at - w[0].at).collect()
+    }
+
+    #[tokio::test]
+    async fn retries_transient_statuses_with_growing_backoff() {
+        let server = TestServer::start(|index, _| match index {
+            0 => Reply::new(503),
+            1 => Reply::new(429),
+            _ => Reply::new(200).body(b"ok"),
+        })
+        .await;
+
+        let response = client(3, 0.0).get(&server.url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(server.requests().len(), 3);
+
+        let gaps = gaps(&server);
+        assert!(gaps[0] >= backoff(0), "first retry after {:?}", gaps[0]);
+        assert!(gaps[1] >= backoff(1), "second retry after {:?}", gaps[1]);
+        assert!(gaps[1] > gaps[0]);
+    }
+
+    #[tokio::test]
+    async fn returns_last_response_after_final_retry() {
+        let server = TestServer::start(|_, _| Reply::new(503)).await;
+
+        let response = client(1, 0.0).get(&server.url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let server = TestServer::start(|_, _| Reply::new(404)).await;
+
+        let response = client(3, 0.0).get(&server.url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn posts_are_not_retried_on_error_status() {
+        let server = TestServer::start(|_, _| Reply::new(503)).await;
+
+        let response = client(3, 0.0).post(&server.url).send().await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].method, "POST");
+    }
+
+    #[tokio::test]
+    async fn spaces_requests_to_one_host() {
+        let server = TestServer::start(|_, _| Reply::new(200)).await;
+        let client = client(0, 10.0);
+
+        for _ in 0..3 {
+            client.get(&server.url).send().await.unwrap();
+        }
+        // 10 per second: 100ms apart, give or take scheduling jitter
+        for gap in gaps(&server) {
+            assert!(gap >= Duration::from_millis(90), "requests {:?} apart", gap);
+        }
+    }
+
+    #[test]
+    fn bad_network_settings_fail_client_creation() {
+        let mut config = Config::default();
+        config.network.ca_certificates = vec!["/nonexistent/ca.pem".to_string()];
+        let error = HttpClient::new(&config, Service::Images).unwrap_err();
+        assert!(format!("{:#}", error).contains("/nonexistent/ca.pem"));
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_cap() {
+        assert_eq!(backoff(0), BASE_BACKOFF);
+        assert_eq!(backoff(1), BASE_BACKOFF * 2);
+        assert_eq!(backoff(2), BASE_BACKOFF * 4);
+        assert_eq!(backoff(20), MAX_BACKOFF);
+    }
+}
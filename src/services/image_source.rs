@@ -0,0 +1,1123 @@
+//! Common interface for image providers (ESA/Webb, JWST API, ...).
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::SystemTime;
+use tokio::io::AsyncWriteExt;
+
+use super::http::{HttpClient, HttpRequest};
+use super::library::quarantine;
+use super::provenance::{self, Provenance};
+use super::variants::{self, monitor_profiles, variant_mode};
+use crate::config::Config;
+use crate::utils::{validate_image_file, SizePreset};
+
+/// Rendition requested when the user downloads an image from the browser.
+pub const DEFAULT_RENDITION: &str = "wallpaper_uhd";
+
+/// Extensions a rendition can be saved with.
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "tif", "tiff"];
+
+/// Bytes fetched from the start of a file to read its dimensions. Large
+/// enough to get past the embedded XMP/ICC blocks in gallery JPEGs.
+const PROBE_PREFIX_BYTES: u64 = 256 * 1024;
+
+/// Download progress callback: (bytes downloaded, total bytes if known).
+pub type ProgressFn<'a> = dyn Fn(u64, Option<u64>) + Send + Sync + 'a;
+
+/// A downloadable size of an image.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rendition {
+    /// Rendition name (e.g. "thumbnail", "screen", "large", "wallpaper_uhd", "original", "tiff")
+    pub name: String,
+    pub url: String,
+}
+
+impl Rendition {
+    /// File extension for this rendition, from its URL (`jpg` if unknown).
+    pub fn extension(&self) -> String {
+        let path = self.url.split(['?', '#']).next().unwrap_or_default();
+        path.rsplit('/')
+            .next()
+            .and_then(|name| name.rsplit_once('.'))
+            .map(|(_, ext)| ext.to_lowercase())
+            .filter(|ext| IMAGE_EXTENSIONS.contains(&ext.as_str()))
+            .unwrap_or_else(|| "jpg".to_string())
+    }
+}
+
+/// What probing a rendition's URL found out.
+#[derive(Debug, Clone)]
+pub struct RenditionProbe {
+    pub name: String,
+    /// Whether the server has the file
+    pub available: bool,
+    /// Size in bytes, if the server reported it
+    pub size: Option<u64>,
+    /// Pixel dimensions, read from the start of the file
+    pub dimensions: Option<(u32, u32)>,
+    /// Why the probe failed
+    pub error: Option<String>,
+}
+
+/// Provider-independent image record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRecord {
+    /// Key of the provider this image came from
+    pub source: String,
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub date: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub credit: Option<String>,
+    /// Web page for the image, if the provider has one
+    #[serde(default)]
+    pub page_url: Option<String>,
+    /// Available renditions, smallest first
+    #[serde(default)]
+    pub renditions: Vec<Rendition>,
+    /// Original pixel dimensions, if known
+    #[serde(default)]
+    pub dimensions: Option<(u32, u32)>,
+    #[serde(default)]
+    pub categories: Vec<String>,
+    #[serde(default)]
+    pub instruments: Vec<String>,
+    /// Provider-specific text shown in the detail pane
+    #[serde(default)]
+    pub fields: Vec<(String, String)>,
+}
+
+impl ImageRecord {
+    /// Get a rendition by name.
+    pub fn rendition(&self, name: &str) -> Option<&Rendition> {
+        self.renditions.iter().find(|r| r.name == name)
+    }
+
+    /// Get a provider-specific field by label.
+    pub fn field(&self, label: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(l, _)| l == label)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+/// An image provider the browser can list and download from.
+///
+/// Providers only need to list their images; thumbnail caching, downloads
+/// and the downloaded-file lookup are shared.
+#[async_trait]
+pub trait ImageSource: Send + Sync {
+    /// Stable key for this provider (e.g. "esa").
+    fn key(&self) -> &str;
+
+    /// Human-readable provider name.
+    fn name(&self) -> &str;
+
+    /// Prefix used for downloaded filenames (e.g. "webb" -> webb-<id>.jpg).
+    fn file_prefix(&self) -> &str;
+
+    /// Application configuration.
+    fn config(&self) -> &Config;
+
+    /// HTTP client used for downloads.
+    fn client(&self) -> &HttpClient;
+
+    /// Get list of available images.
+    async fn list_images(&self, force_refresh: bool) -> Result<Vec<ImageRecord>>;
+
+    /// Load an additional page of older images (page numbers start at 1).
+    ///
+    /// Returns an empty list once the provider has nothing more to offer.
+    async fn list_more(&self, _page: u32) -> Result<Vec<ImageRecord>> {
+        Ok(Vec::new())
+    }
+
+    /// When the listing last served was fetched, if it came from an expired
+    /// cache because the network was unavailable (or offline mode is on).
+    fn stale_since(&self) -> Option<DateTime<Utc>> {
+        None
+    }
+
+    /// Whether this provider supports `list_more`.
+    fn has_more(&self) -> bool {
+        false
+    }
+
+    /// Fetch extra metadata (description, credit, object info, ...) for an image.
+    ///
+    /// Returns the enriched record, or `None` if the provider has nothing more
+    /// than what `list_images` returned.
+    async fn load_details(&self, _image: &ImageRecord) -> Result<Option<ImageRecord>> {
+        Ok(None)
+    }
+
+    /// Path a rendition of an image is downloaded to. The file name records
+    /// the rendition, e.g. `webb-weic2216b-wallpaper_uhd.jpg`.
+    fn download_path(&self, image: &ImageRecord, rendition: &str) -> PathBuf {
+        let extension = image
+            .rendition(rendition)
+            .map(Rendition::extension)
+            .unwrap_or_else(|| "jpg".to_string());
+        self.config()
+            .wallpaper_dir()
+            .join(format!("{}-{}-{}.{}", self.file_prefix(), image.id, rendition, extension))
+    }
+
+    /// Path of an image's variant for a monitor profile, e.g.
+    /// `webb-<id>-ultrawide.jpg`.
+    fn variant_path(&self, image: &ImageRecord, profile: &str) -> PathBuf {
+        self.config()
+            .wallpaper_dir()
+            .join(format!("{}-{}-{}.jpg", self.file_prefix(), image.id, profile))
+    }
+
+    /// Paths a downloaded copy of an image may have, most preferred first.
+    ///
+    /// Prefers a variant for the first configured monitor profile, then the
+    /// default rendition, then the largest other one. Downloads from before
+    /// file names recorded the rendition are included too.
+    fn download_candidates(&self, image: &ImageRecord) -> Vec<PathBuf> {
+        let prefix = self.file_prefix();
+        let mut paths: Vec<PathBuf> = monitor_profiles(self.config())
+            .iter()
+            .map(|profile| self.variant_path(image, &profile.suffix()))
+            .collect();
+        paths.push(self.variant_path(image, "ultrawide"));
+        paths.push(self.download_path(image, DEFAULT_RENDITION));
+        paths.push(self.config().wallpaper_dir().join(format!("{}-{}.jpg", prefix, image.id)));
+        paths.extend(
+            image
+                .renditions
+                .iter()
+                .rev()
+                .filter(|r| r.name != DEFAULT_RENDITION)
+                .map(|r| self.download_path(image, &r.name)),
+        );
+        paths.push(self.variant_path(image, "laptop"));
+        paths
+    }
+
+    /// Get path to downloaded image if it exists.
+    fn get_downloaded_path(&self, image: &ImageRecord) -> Option<PathBuf> {
+        self.download_candidates(image).into_iter().find(|p| p.exists())
+    }
+
+    /// Every file of an image on disk: all downloaded renditions plus its
+    /// monitor variants.
+    fn downloaded_files(&self, image: &ImageRecord) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = image
+            .renditions
+            .iter()
+            .map(|r| self.download_path(image, &r.name))
+            .collect();
+        paths.push(
+            self.config()
+                .wallpaper_dir()
+                .join(format!("{}-{}.jpg", self.file_prefix(), image.id)),
+        );
+        paths.extend(
+            monitor_profiles(self.config())
+                .iter()
+                .map(|profile| self.variant_path(image, &profile.suffix())),
+        );
+        paths.push(self.variant_path(image, "ultrawide"));
+        paths.push(self.variant_path(image, "laptop"));
+
+        paths.sort();
+        paths.dedup();
+        paths.retain(|p| p.exists());
+        paths
+    }
+
+    /// Write the monitor-profile variants of a downloaded image. Returns the
+    /// paths written.
+    ///
+    /// A failure here doesn't fail the download; the original is still there.
+    async fn write_variants(&self, image: &ImageRecord, path: &Path) -> Vec<PathBuf> {
+        let targets: Vec<(SizePreset, PathBuf)> = monitor_profiles(self.config())
+            .into_iter()
+            .map(|profile| (profile, self.variant_path(image, &profile.suffix())))
+            .collect();
+        let mode = variant_mode(self.config());
+        let source = path.to_path_buf();
+
+        match tokio::task::spawn_blocking(move || variants::write_variants(&source, &targets, mode)).await {
+            Ok(Ok(written)) => {
+                tracing::debug!("Wrote {} variants of {}", written.len(), image.id);
+                written
+            }
+            // The download itself succeeded, and the full image is used
+            // wherever a variant is missing
+            Ok(Err(e)) => {
+                tracing::debug!("Failed to write variants of {}: {:#}", image.id, e);
+                Vec::new()
+            }
+            Err(e) => {
+                tracing::debug!("Variant task failed: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    /// Record credit and provenance in downloaded files (embedded and as
+    /// JSON sidecars).
+    async fn save_provenance(&self, image: &ImageRecord, rendition: &str, paths: Vec<PathBuf>) {
+        let provenance = Provenance::for_download(image, rendition);
+        let saved = tokio::task::spawn_blocking(move || {
+            for path in paths {
+                if let Err(e) = provenance::save(&path, &provenance) {
+                    tracing::warn!("Failed to save provenance for {}: {:#}", path.display(), e);
+                }
+            }
+        })
+        .await;
+        if let Err(e) = saved {
+            tracing::warn!("Provenance task failed: {}", e);
+        }
+    }
+
+    /// Download a thumbnail for an image.
+    async fn download_thumbnail(&self, image: &ImageRecord) -> Result<PathBuf> {
+        let thumbnail_dir = self.config().thumbnail_dir();
+        fs::create_dir_all(&thumbnail_dir)?;
+
+        // Keyed by source too: IDs are only unique within one source
+        let thumbnail_path = thumbnail_dir.join(format!("{}-{}.thumb.jpg", self.key(), image.id));
+
+        // Return cached if exists
+        if thumbnail_path.exists() {
+            return Ok(thumbnail_path);
+        }
+        ensure_online(self.config())?;
+
+        // Try the thumbnail first, then fall back to screen size
+        let mut last_error = None;
+        for name in ["thumbnail", "screen"] {
+            if let Some(rendition) = image.rendition(name) {
+                match fetch_bytes(self.client(), &rendition.url).await {
+                    Ok(bytes) => {
+                        fs::write(&thumbnail_path, &bytes)?;
+                        return Ok(thumbnail_path);
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No thumbnail URL available")))
+    }
+
+    /// Download an image at the specified rendition.
+    async fn download_image(&self, image: &ImageRecord, rendition: &str) -> Result<PathBuf> {
+        self.download_image_with_progress(image, rendition, &|_, _| {})
+            .await
+    }
+
+    /// Download an image at the specified rendition, reporting progress.
+    ///
+    /// Falls back to the next smaller rendition only if the requested one
+    /// isn't available (no URL, or the server answers 404/410), and to the
+    /// largest one if the image doesn't have the requested rendition. Any
+    /// other failure is returned, keeping the `.part` file so the next try
+    /// resumes from it. An already downloaded rendition is returned as is.
+    async fn download_image_with_progress(
+        &self,
+        image: &ImageRecord,
+        rendition: &str,
+        progress: &ProgressFn<'_>,
+    ) -> Result<PathBuf> {
+        // Return cached if exists (including downloads from before file
+        // names recorded the rendition)
+        let mut existing = vec![self.download_path(image, rendition)];
+        if rendition == DEFAULT_RENDITION {
+            existing.push(self.config().wallpaper_dir().join(format!("{}-{}.jpg", self.file_prefix(), image.id)));
+        }
+        if let Some(path) = existing.into_iter().find(|p| p.is_file()) {
+            return Ok(path);
+        }
+
+        ensure_online(self.config())?;
+        fs::create_dir_all(self.config().wallpaper_dir())?;
+
+        // Requested rendition first, then smaller ones largest first; never
+        // fall back to something bigger than what was asked for
+        let requested = image.renditions.iter().position(|r| r.name == rendition);
+        let fallbacks = &image.renditions[..requested.unwrap_or(image.renditions.len())];
+        let mut candidates: Vec<&Rendition> = requested.map(|i| &image.renditions[i]).into_iter().collect();
+        candidates.extend(fallbacks.iter().rev().filter(|r| r.name != "thumbnail"));
+
+        let mut last_error = None;
+        for candidate in candidates {
+            if candidate.url.is_empty() {
+                tracing::debug!("Rendition {} of {} has no URL", candidate.name, image.id);
+                continue;
+            }
+            let output_path = self.download_path(image, &candidate.name);
+            let part = part_path(&output_path);
+            match download_to_file(self.client(), &candidate.url, &output_path, &part, progress).await {
+                Ok(()) => {
+                    let mut saved = self.write_variants(image, &output_path).await;
+                    saved.insert(0, output_path.clone());
+                    self.save_provenance(image, &candidate.name, saved).await;
+                    return Ok(output_path);
+                }
+                Err(e) if e.is::<Unavailable>() => {
+                    tracing::debug!("Rendition {} of {} unavailable: {:#}", candidate.name, image.id, e);
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e.context(format!("Failed to download {} of {}", candidate.name, image.id))),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow::anyhow!("No download URL available for image")))
+    }
+
+    /// Probe every downloadable rendition of an image, smallest first.
+    async fn probe_renditions(&self, image: &ImageRecord) -> Vec<RenditionProbe> {
+        if let Err(e) = ensure_online(self.config()) {
+            return image
+                .renditions
+                .iter()
+                .filter(|r| r.name != "thumbnail")
+                .map(|r| RenditionProbe {
+                    name: r.name.clone(),
+                    available: false,
+                    size: None,
+                    dimensions: None,
+                    error: Some(e.to_string()),
+                })
+                .collect();
+        }
+        let probes = image
+            .renditions
+            .iter()
+            .filter(|r| r.name != "thumbnail")
+            .map(|r| probe_rendition(self.client(), r));
+        futures_util::future::join_all(probes).await
+    }
+}
+
+/// Partial download file for `output`.
+fn part_path(output: &Path) -> PathBuf {
+    let mut name = output.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    output.with_file_name(name)
+}
+
+/// Check whether a rendition exists and how big it is.
+///
+/// A HEAD request gives availability and byte size; the pixel dimensions come
+/// from decoding the header of the first few hundred KB. Files that keep
+/// their dimensions further in (some TIFFs) report none.
+pub async fn probe_rendition(client: &HttpClient, rendition: &Rendition) -> RenditionProbe {
+    let mut probe = RenditionProbe {
+        name: rendition.name.clone(),
+        available: false,
+        size: None,
+        dimensions: None,
+        error: None,
+    };
+
+    // Some servers refuse HEAD; the ranged GET below still tells us enough
+    if let Ok(response) = client.head(&rendition.url).send().await {
+        let status = response.status();
+        if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::GONE {
+            probe.error = Some(format!("HTTP {}", status.as_u16()));
+            return probe;
+        }
+        if status.is_success() {
+            probe.available = true;
+            probe.size = response.content_length().filter(|&len| len > 0);
+        }
+    }
+
+    let range = format!("bytes=0-{}", PROBE_PREFIX_BYTES - 1);
+    let mut response = match client
+        .get(&rendition.url)
+        .header(reqwest::header::RANGE, range)
+        .send()
+        .await
+    {
+        Ok(response) => response,
+        Err(e) => {
+            probe.error = Some(e.to_string());
+            return probe;
+        }
+    };
+    let status = response.status();
+    if !status.is_success() {
+        probe.available = false;
+        probe.error = Some(format!("HTTP {}", status.as_u16()));
+        return probe;
+    }
+    probe.available = true;
+    if probe.size.is_none() {
+        probe.size = if status == reqwest::StatusCode::PARTIAL_CONTENT {
+            content_range_total(&response)
+        } else {
+            response.content_length()
+        };
+    }
+
+    // Servers that ignore the range send the whole file; stop reading early
+    let mut prefix = Vec::new();
+    while let Ok(Some(chunk)) = response.chunk().await {
+        prefix.extend_from_slice(&chunk);
+        if prefix.len() as u64 >= PROBE_PREFIX_BYTES {
+            break;
+        }
+    }
+    probe.dimensions = image::ImageReader::new(std::io::Cursor::new(prefix))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok());
+
+    probe
+}
+
+/// The server doesn't have a file (404 or 410), as opposed to a transfer
+/// that failed.
+#[derive(Debug)]
+struct Unavailable(reqwest::StatusCode);
+
+impl std::fmt::Display for Unavailable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Not available on the server ({})", self.0)
+    }
+}
+
+impl std::error::Error for Unavailable {}
+
+/// Stream a URL into `part`, then rename it to `dest`.
+///
+/// An existing `part` file is resumed with an HTTP Range request; servers
+/// that ignore the range get a fresh download. The partial file is kept on
+/// network failure so a later attempt can pick up where this one stopped,
+/// but a complete file that doesn't decode as an image is quarantined.
+pub async fn download_to_file(
+    client: &HttpClient,
+    url: &str,
+    dest: &Path,
+    part: &Path,
+    progress: &ProgressFn<'_>,
+) -> Result<()> {
+    let existing = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+
+    let mut request = client.get(url);
+    if existing > 0 {
+        request = request.header(reqwest::header::RANGE, format!("bytes={}-", existing));
+    }
+    let mut response = request
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?;
+
+    let status = response.status();
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE && existing > 0 {
+        // The partial file is already complete
+        progress(existing, Some(existing));
+        return commit_download(part, dest);
+    }
+    if status == reqwest::StatusCode::NOT_FOUND || status == reqwest::StatusCode::GONE {
+        return Err(Unavailable(status).into());
+    }
+    if !status.is_success() {
+        anyhow::bail!("Download failed with status: {}", status);
+    }
+    check_content_type(&response)?;
+
+    // Only append when the server honoured the range from where we left off
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT
+        && content_range_start(&response) == Some(existing);
+    let mut downloaded = if resumed { existing } else { 0 };
+    let total = if resumed {
+        content_range_total(&response).or_else(|| response.content_length().map(|len| len + existing))
+    } else {
+        response.content_length()
+    };
+
+    let mut file = tokio::fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part)
+        .await
+        .with_context(|| format!("Failed to open {}", part.display()))?;
+
+    progress(downloaded, total);
+    loop {
+        let chunk = match response.chunk().await {
+            Ok(Some(chunk)) => chunk,
+            Ok(None) => break,
+            Err(e) => {
+                // Make sure what did arrive is on disk before the next
+                // attempt resumes from the file's length
+                file.flush().await.ok();
+                return Err(anyhow::Error::new(e).context(format!("Download of {} interrupted", url)));
+            }
+        };
+        file.write_all(&chunk).await?;
+        downloaded += chunk.len() as u64;
+        progress(downloaded, total);
+    }
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    if let Some(total) = total {
+        if downloaded != total {
+            anyhow::bail!("Download incomplete: got {} of {} bytes", downloaded, total);
+        }
+    }
+
+    commit_download(part, dest)
+}
+
+/// Reject responses that can't be an image, such as an HTML error page
+/// served with a 200. Generic binary types are let through to the image
+/// check.
+fn check_content_type(response: &reqwest::Response) -> Result<()> {
+    let content_type = match response.headers().get(reqwest::header::CONTENT_TYPE) {
+        Some(value) => value.to_str().unwrap_or_default().to_lowercase(),
+        None => return Ok(()),
+    };
+    let mime = content_type.split(';').next().unwrap_or_default().trim();
+    if mime.starts_with("image/") || mime == "application/octet-stream" || mime == "binary/octet-stream" {
+        Ok(())
+    } else {
+        anyhow::bail!("Server returned {} instead of an image", mime)
+    }
+}
+
+/// Validate a finished partial download and move it to `dest`. A file that
+/// isn't a complete image is quarantined instead, so it isn't resumed; the
+/// error says where it went.
+fn commit_download(part: &Path, dest: &Path) -> Result<()> {
+    if let Err(e) = validate_image_file(part) {
+        let target = quarantine(part)?;
+        return Err(e.context(format!(
+            "Downloaded file is not a valid image (quarantined as {})",
+            target.display()
+        )));
+    }
+    fs::rename(part, dest).context("Failed to move download into place")?;
+    Ok(())
+}
+
+/// Parse `Content-Range: bytes <start>-<end>/<total>`.
+fn parse_content_range(response: &reqwest::Response) -> Option<(u64, Option<u64>)> {
+    let value = response
+        .headers()
+        .get(reqwest::header::CONTENT_RANGE)?
+        .to_str()
+        .ok()?;
+    let (range, total) = value.strip_prefix("bytes ")?.split_once('/')?;
+    let start = range.split_once('-')?.0.trim().parse().ok()?;
+    Some((start, total.trim().parse().ok()))
+}
+
+fn content_range_start(response: &reqwest::Response) -> Option<u64> {
+    parse_content_range(response).map(|(start, _)| start)
+}
+
+fn content_range_total(response: &reqwest::Response) -> Option<u64> {
+    parse_content_range(response).and_then(|(_, total)| total)
+}
+
+/// Fetch a URL, failing on non-success status codes.
+pub async fn fetch_bytes(client: &HttpClient, url: &str) -> Result<Vec<u8>> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?;
+
+    if !response.status().is_success() {
+        anyhow::bail!("Download failed with status: {}", response.status());
+    }
+
+    Ok(response.bytes().await?.to_vec())
+}
+
+/// HTTP validators of a cached response, stored next to the cache file so
+/// the next fetch can be conditional.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheValidators {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etag: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_modified: Option<String>,
+}
+
+impl CacheValidators {
+    /// Read the validators from a response.
+    pub fn from_response(response: &reqwest::Response) -> Self {
+        let header = |name| {
+            response
+                .headers()
+                .get(name)
+                .and_then(|v: &reqwest::header::HeaderValue| v.to_str().ok())
+                .map(str::to_string)
+        };
+        Self {
+            etag: header(reqwest::header::ETAG),
+            last_modified: header(reqwest::header::LAST_MODIFIED),
+        }
+    }
+
+    /// Path of the validators file for a cache file.
+    fn path(cache_file: &Path) -> PathBuf {
+        let mut name = cache_file.file_name().unwrap_or_default().to_os_string();
+        name.push(".http.json");
+        cache_file.with_file_name(name)
+    }
+
+    /// Load the validators stored for a cache file.
+    pub fn load(cache_file: &Path) -> Self {
+        fs::read_to_string(Self::path(cache_file))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    /// Store the validators for a cache file (after the cache file itself has
+    /// been written). Responses without validators remove stale ones.
+    pub fn save(&self, cache_file: &Path) {
+        let path = Self::path(cache_file);
+        if self.etag.is_none() && self.last_modified.is_none() {
+            let _ = fs::remove_file(path);
+        } else if let Ok(content) = serde_json::to_string_pretty(self) {
+            fs::write(path, content).ok();
+        }
+    }
+}
+
+/// Outcome of a conditional fetch.
+pub enum Conditional {
+    /// The cached copy is still current; its timestamp has been refreshed
+    NotModified,
+    /// Any other response, with the validators to store once its content
+    /// is cached
+    Modified(reqwest::Response, CacheValidators),
+}
+
+/// Send a request that revalidates `cache_file`, using If-None-Match and
+/// If-Modified-Since from its stored validators.
+///
+/// A 304 touches the cache file so it counts as fresh for another TTL.
+/// Other statuses are left for the caller to check.
+pub async fn fetch_conditional(request: HttpRequest<'_>, cache_file: &Path) -> Result<Conditional> {
+    let mut request = request;
+    if cache_file.exists() {
+        let validators = CacheValidators::load(cache_file);
+        if let Some(etag) = &validators.etag {
+            request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+        }
+        if let Some(last_modified) = &validators.last_modified {
+            request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+        }
+    }
+
+    let response = request.send().await?;
+    let status = response.status();
+    if status == reqwest::StatusCode::NOT_MODIFIED && cache_file.exists() {
+        touch(cache_file)?;
+        return Ok(Conditional::NotModified);
+    }
+
+    let validators = CacheValidators::from_response(&response);
+    Ok(Conditional::Modified(response, validators))
+}
+
+/// Set a file's modification time to now.
+fn touch(path: &Path) -> Result<()> {
+    fs::File::options()
+        .append(true)
+        .open(path)
+        .and_then(|file| file.set_modified(SystemTime::now()))
+        .with_context(|| format!("Failed to refresh {}", path.display()))
+}
+
+/// Check if a cache file exists and is younger than the TTL.
+pub fn is_cache_valid(path: &Path, ttl_secs: u64) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .map(|age| age.as_secs() < ttl_secs)
+        .unwrap_or(false)
+}
+
+/// Fail if offline mode is on.
+pub fn ensure_online(config: &Config) -> Result<()> {
+    if config.jwst.offline {
+        anyhow::bail!("Offline mode: network access is disabled");
+    }
+    Ok(())
+}
+
+/// Remembers when a source last served an expired cache, for the "stale
+/// since" banner.
+#[derive(Debug, Default)]
+pub struct Staleness(Mutex<Option<DateTime<Utc>>>);
+
+impl Staleness {
+    /// Record that `cache_file` was served stale. Its modification time is
+    /// when it was last fetched (or revalidated).
+    pub fn mark(&self, cache_file: &Path) {
+        let fetched = fs::metadata(cache_file)
+            .and_then(|m| m.modified())
+            .map(DateTime::<Utc>::from)
+            .unwrap_or_else(|_| Utc::now());
+        let mut since = self.0.lock().unwrap();
+        *since = Some(since.map_or(fetched, |s| s.min(fetched)));
+    }
+
+    /// Record that fresh data was fetched.
+    pub fn clear(&self) {
+        *self.0.lock().unwrap() = None;
+    }
+
+    pub fn since(&self) -> Option<DateTime<Utc>> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// Read through a cache file, falling back to a stale copy when the network
+/// fails.
+///
+/// A `cache_file` younger than the TTL is read as is. Otherwise `fetch` runs
+/// (and is expected to rewrite the cache); if it fails, the last good copy
+/// in `stale_file` (usually the same file) is read instead and `stale`
+/// records how old it is. In offline mode `fetch` never runs.
+pub async fn fetch_or_stale<T>(
+    config: &Config,
+    cache_file: &Path,
+    stale_file: &Path,
+    force_refresh: bool,
+    stale: &Staleness,
+    read_cache: impl Fn(&Path) -> Result<T>,
+    fetch: impl Future<Output = Result<T>>,
+) -> Result<T> {
+    if config.jwst.offline {
+        if !stale_file.exists() {
+            anyhow::bail!("Offline mode, and nothing cached yet");
+        }
+        stale.mark(stale_file);
+        return read_cache(stale_file);
+    }
+
+    if !force_refresh && is_cache_valid(cache_file, config.jwst.cache_ttl) {
+        stale.clear();
+        return read_cache(cache_file);
+    }
+
+    match fetch.await {
+        Ok(value) => {
+            stale.clear();
+            Ok(value)
+        }
+        Err(e) if stale_file.exists() => {
+            // Shown in the browser as "stale since"; a log line would
+            // only print over the TUI
+            tracing::debug!("{:#}; using cached {}", e, stale_file.display());
+            stale.mark(stale_file);
+            read_cache(stale_file)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::http::Service;
+    use crate::services::test_server::{Reply, TestServer};
+
+    /// A small PNG, noisy enough not to compress to nothing.
+    fn png() -> Vec<u8> {
+        let image = image::RgbImage::from_fn(64, 64, |x, y| image::Rgb([(x * 7) as u8, (y * 13) as u8, (x ^ y) as u8]));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    fn client() -> HttpClient {
+        let mut config = Config::default();
+        config.network.retries = 0;
+        config.network.requests_per_second = 0.0;
+        HttpClient::new(&config, Service::Images).unwrap()
+    }
+
+    struct Target {
+        _dir: tempfile::TempDir,
+        dest: PathBuf,
+        part: PathBuf,
+    }
+
+    fn target() -> Target {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("webb-test.jpg");
+        let part = part_path(&dest);
+        Target { _dir: dir, dest, part }
+    }
+
+    async fn download(server: &TestServer, target: &Target) -> Result<()> {
+        let url = format!("{}/image.png", server.url);
+        download_to_file(&client(), &url, &target.dest, &target.part, &|_, _| {}).await
+    }
+
+    /// Serves `body`, honouring `Range: bytes=<start>-`.
+    fn ranged(body: Vec<u8>) -> impl Fn(usize, &crate::services::test_server::Request) -> Reply {
+        move |_, request| match request.header("Range").and_then(|r| r.strip_prefix("bytes=")) {
+            Some(range) => {
+                let start: usize = range.trim_end_matches('-').parse().unwrap();
+                Reply::new(206)
+                    .header("Content-Type", "image/png")
+                    .header(
+                        "Content-Range",
+                        &format!("bytes {}-{}/{}", start, body.len() - 1, body.len()),
+                    )
+                    .body(&body[start..])
+            }
+            None => Reply::new(200).header("Content-Type", "image/png").body(&body),
+        }
+    }
+
+    #[tokio::test]
+    async fn interrupted_download_resumes_with_range() {
+        let body = png();
+        let cut = body.len() / 2;
+        let full = body.clone();
+        let server = TestServer::start(move |index, request| {
+            if index == 0 {
+                Reply::new(200).header("Content-Type", "image/png").body(&full).cut_at(cut)
+            } else {
+                ranged(full.clone())(index, request)
+            }
+        })
+        .await;
+        let target = target();
+
+        assert!(download(&server, &target).await.is_err());
+        assert_eq!(fs::metadata(&target.part).unwrap().len(), cut as u64);
+        assert!(!target.dest.exists());
+
+        download(&server, &target).await.unwrap();
+        let requests = server.requests();
+        assert_eq!(requests[1].header("Range"), Some(format!("bytes={}-", cut).as_str()));
+        assert_eq!(fs::read(&target.dest).unwrap(), body);
+        assert!(!target.part.exists());
+    }
+
+    #[tokio::test]
+    async fn ignored_range_restarts_download() {
+        let body = png();
+        let full = body.clone();
+        let server = TestServer::start(move |_, _| Reply::new(200).header("Content-Type", "image/png").body(&full)).await;
+        let target = target();
+        fs::write(&target.part, b"stale partial bytes").unwrap();
+
+        download(&server, &target).await.unwrap();
+        assert_eq!(server.requests()[0].header("Range"), Some("bytes=19-"));
+        assert_eq!(fs::read(&target.dest).unwrap(), body);
+        assert!(!target.part.exists());
+    }
+
+    #[tokio::test]
+    async fn range_not_satisfiable_commits_complete_part() {
+        let body = png();
+        let server = TestServer::start(|_, _| Reply::new(416)).await;
+        let target = target();
+        fs::write(&target.part, &body).unwrap();
+
+        download(&server, &target).await.unwrap();
+        assert_eq!(fs::read(&target.dest).unwrap(), body);
+        assert!(!target.part.exists());
+    }
+
+    #[tokio::test]
+    async fn invalid_image_is_quarantined_not_committed() {
+        let server = TestServer::start(|_, _| {
+            Reply::new(200)
+                .header("Content-Type", "application/octet-stream")
+                .body(b"<html>not an image</html>")
+        })
+        .await;
+        let target = target();
+
+        let error = download(&server, &target).await.unwrap_err();
+        assert!(!target.dest.exists());
+        assert!(!target.part.exists());
+        let quarantined: Vec<PathBuf> = fs::read_dir(target.dest.with_file_name(".quarantine"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(quarantined.len(), 1);
+        assert!(error.to_string().contains(&quarantined[0].display().to_string()));
+    }
+
+    #[tokio::test]
+    async fn html_error_page_writes_nothing() {
+        let server = TestServer::start(|_, _| {
+            Reply::new(200)
+                .header("Content-Type", "text/html; charset=utf-8")
+                .body(b"<html>rate limited</html>")
+        })
+        .await;
+        let target = target();
+
+        assert!(download(&server, &target).await.is_err());
+        assert!(!target.dest.exists());
+        assert!(!target.part.exists());
+    }
+
+    #[tokio::test]
+    async fn failed_status_keeps_partial_file() {
+        let server = TestServer::start(|_, _| Reply::new(404)).await;
+        let target = target();
+        fs::write(&target.part, b"partial").unwrap();
+
+        assert!(download(&server, &target).await.is_err());
+        assert!(!target.dest.exists());
+        assert_eq!(fs::read(&target.part).unwrap(), b"partial");
+    }
+
+    /// A source whose images download into a temporary directory.
+    struct TestSource {
+        _dir: tempfile::TempDir,
+        config: Config,
+        client: HttpClient,
+    }
+
+    impl TestSource {
+        fn new() -> Self {
+            let dir = tempfile::tempdir().unwrap();
+            let mut config = Config::default();
+            config.jwst.wallpaper_dir = dir.path().display().to_string();
+            Self { _dir: dir, config, client: client() }
+        }
+
+        /// An image with `screen` and `large` renditions on `server`.
+        fn image(&self, server: &TestServer, large_url: Option<&str>) -> ImageRecord {
+            let rendition = |name: &str, url: String| Rendition { name: name.to_string(), url };
+            ImageRecord {
+                source: "test".to_string(),
+                id: "img1".to_string(),
+                title: "Test image".to_string(),
+                date: None,
+                description: None,
+                credit: None,
+                page_url: None,
+                renditions: vec![
+                    rendition("screen", format!("{}/screen.png", server.url)),
+                    rendition("large", large_url.map_or_else(|| format!("{}/large.png", server.url), str::to_string)),
+                ],
+                dimensions: None,
+                categories: Vec::new(),
+                instruments: Vec::new(),
+                fields: Vec::new(),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ImageSource for TestSource {
+        fn key(&self) -> &str {
+            "test"
+        }
+
+        fn name(&self) -> &str {
+            "Test"
+        }
+
+        fn file_prefix(&self) -> &str {
+            "test"
+        }
+
+        fn config(&self) -> &Config {
+            &self.config
+        }
+
+        fn client(&self) -> &HttpClient {
+            &self.client
+        }
+
+        async fn list_images(&self, _force_refresh: bool) -> Result<Vec<ImageRecord>> {
+            Ok(Vec::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn missing_rendition_falls_back_to_smaller_one() {
+        let body = png();
+        let server = TestServer::start(move |index, _| {
+            if index == 0 {
+                Reply::new(404)
+            } else {
+                Reply::new(200).header("Content-Type", "image/png").body(&body)
+            }
+        })
+        .await;
+        let source = TestSource::new();
+        let image = source.image(&server, None);
+
+        let path = source.download_image(&image, "large").await.unwrap();
+        assert_eq!(path, source.download_path(&image, "screen"));
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn rendition_without_url_falls_back_to_smaller_one() {
+        let body = png();
+        let server = TestServer::start(move |_, _| Reply::new(200).header("Content-Type", "image/png").body(&body)).await;
+        let source = TestSource::new();
+        let image = source.image(&server, Some(""));
+
+        let path = source.download_image(&image, "large").await.unwrap();
+        assert_eq!(path, source.download_path(&image, "screen"));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn interrupted_download_fails_and_keeps_partial_file() {
+        let body = png();
+        let cut = body.len() / 2;
+        let server = TestServer::start(move |_, _| {
+            Reply::new(200).header("Content-Type", "image/png").body(&body).cut_at(cut)
+        })
+        .await;
+        let source = TestSource::new();
+        let image = source.image(&server, None);
+
+        assert!(source.download_image(&image, "large").await.is_err());
+        // No fallback to the smaller rendition
+        assert_eq!(server.requests().len(), 1);
+        let large = source.download_path(&image, "large");
+        assert_eq!(fs::metadata(part_path(&large)).unwrap().len(), cut as u64);
+        assert!(!source.download_path(&image, "screen").exists());
+    }
+
+    #[tokio::test]
+    async fn server_error_is_not_treated_as_missing() {
+        let server = TestServer::start(|_, _| Reply::new(503)).await;
+        let source = TestSource::new();
+        let image = source.image(&server, None);
+
+        let error = source.download_image(&image, "large").await.unwrap_err();
+        assert_eq!(server.requests().len(), 1);
+        assert!(format!("{:#}", error).contains("503"));
+    }
+}
@@ -1,14 +1,15 @@
 //! JWST API service - fetches raw scientific images from jwstapi.com.
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
 
+use super::http::{HttpClient, Service};
+use super::image_source::{self, Conditional, ImageRecord, ImageSource, Rendition, Staleness};
 use crate::config::Config;
 
 /// Represents an image from the JWST API.
@@ -56,14 +57,52 @@ impl JwstImage {
             .unwrap_or_else(|| self.id.clone())
     }
 
-    /// Get instruments as a comma-separated string.
-    pub fn instruments_str(&self) -> String {
-        if let Some(details) = &self.details {
-            if let Some(instruments) = &details.instruments {
-                return instruments.join(", ");
-            }
+    /// Convert into a provider-independent record.
+    pub fn to_record(&self) -> ImageRecord {
+        let mut renditions = Vec::new();
+        if let Some(url) = &self.thumbnail {
+            renditions.push(Rendition {
+                name: "thumbnail".to_string(),
+                url: url.clone(),
+            });
+        }
+        if let Some(url) = &self.location {
+            renditions.push(Rendition {
+                name: "original".to_string(),
+                url: url.clone(),
+            });
+        }
+
+        let mut fields = Vec::new();
+        if let Some(observation_id) = &self.observation_id {
+            fields.push(("Observation".to_string(), observation_id.clone()));
+        }
+        if let Some(program) = self.program {
+            fields.push(("Program".to_string(), program.to_string()));
+        }
+
+        ImageRecord {
+            source: "jwst_api".to_string(),
+            id: self.id.clone(),
+            title: self.title(),
+            date: None,
+            description: self
+                .details
+                .as_ref()
+                .and_then(|d| d.description.clone())
+                .filter(|d| !d.is_empty()),
+            credit: None,
+            page_url: None,
+            renditions,
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: self
+                .details
+                .as_ref()
+                .and_then(|d| d.instruments.clone())
+                .unwrap_or_default(),
+            fields,
         }
-        String::new()
     }
 }
 
@@ -77,78 +116,71 @@ struct ApiResponse {
 /// Service for fetching images from JWST API.
 pub struct JwstApiService {
     config: Arc<Config>,
-    client: Client,
+    client: HttpClient,
     cache_file: PathBuf,
     api_key: Option<String>,
+    stale: Staleness,
 }
 
 impl JwstApiService {
     /// Create a new JWST API service.
-    pub fn new(config: Arc<Config>) -> Self {
+    pub fn new(config: Arc<Config>) -> Result<Self> {
         let cache_dir = config.cache_dir();
         fs::create_dir_all(&cache_dir).ok();
 
         let api_key = config.jwst_api_key();
 
-        Self {
+        Ok(Self {
             cache_file: cache_dir.join("jwst_api_metadata.json"),
+            client: HttpClient::new(&config, Service::Images)?,
             config,
-            client: Client::builder()
-                .user_agent("JWST-Cosmos/0.1.0 (Rust; Ratatui TUI)")
-                .build()
-                .expect("Failed to create HTTP client"),
             api_key,
-        }
+            stale: Staleness::default(),
+        })
     }
 
-    /// Check if we have a valid API key.
-    pub fn has_api_key(&self) -> bool {
-        self.api_key.is_some()
-    }
-
-    /// Check if the cache is still valid.
-    fn is_cache_valid(&self) -> bool {
-        if !self.cache_file.exists() {
-            return false;
-        }
-
-        if let Ok(metadata) = fs::metadata(&self.cache_file) {
-            if let Ok(modified) = metadata.modified() {
-                if let Ok(duration) = SystemTime::now().duration_since(modified) {
-                    return duration.as_secs() < self.config.jwst.cache_ttl;
-                }
-            }
-        }
-
-        false
-    }
-
-    /// Fetch images from the API.
+    /// Fetch images from the API, serving the expired cache if the API
+    /// can't be reached.
     async fn fetch_images(&self, force_refresh: bool) -> Result<Vec<JwstImage>> {
-        // Check cache first
-        if !force_refresh && self.is_cache_valid() {
-            let content = fs::read_to_string(&self.cache_file)
+        let read_cache = |path: &Path| -> Result<Vec<JwstImage>> {
+            let content = fs::read_to_string(path)
                 .context("Failed to read API cache")?;
-            let images: Vec<JwstImage> = serde_json::from_str(&content)
-                .context("Failed to parse cached images")?;
-            return Ok(images);
-        }
+            serde_json::from_str(&content).context("Failed to parse cached images")
+        };
+        image_source::fetch_or_stale(
+            &self.config,
+            &self.cache_file,
+            &self.cache_file,
+            force_refresh,
+            &self.stale,
+            read_cache,
+            self.download_images(),
+        )
+        .await
+    }
 
+    /// Fetch images from the API, updating the cache.
+    async fn download_images(&self) -> Result<Vec<JwstImage>> {
         // Need API key for fresh fetch
         let api_key = self
             .api_key
             .as_ref()
             .context("No JWST API key available")?;
 
-        // Fetch from API
+        // Fetch from API, revalidating the cached copy
         let url = format!("{}/all/type/jpg", self.config.jwst.api_base);
-        let response = self
-            .client
-            .get(&url)
-            .header("X-API-KEY", api_key)
-            .send()
+        let request = self.client.get(&url).header("X-API-KEY", api_key);
+        let (response, validators) = match image_source::fetch_conditional(request, &self.cache_file)
             .await
-            .context("Failed to fetch from JWST API")?;
+            .context("Failed to fetch from JWST API")?
+        {
+            Conditional::NotModified => {
+                let content = fs::read_to_string(&self.cache_file)
+                    .context("Failed to read API cache")?;
+                return serde_json::from_str(&content).context("Failed to parse cached images");
+            }
+            Conditional::Modified(response, validators) => (response, validators),
+        };
 
         if !response.status().is_success() {
             anyhow::bail!("API request failed with status: {}", response.status());
@@ -161,7 +193,9 @@ impl JwstApiService {
 
         // Cache the response
         let cache_content = serde_json::to_string_pretty(&api_response.body)?;
-        fs::write(&self.cache_file, &cache_content).ok();
+        if fs::write(&self.cache_file, &cache_content).is_ok() {
+            validators.save(&self.cache_file);
+        }
 
         Ok(api_response.body)
     }
@@ -170,75 +204,36 @@ impl JwstApiService {
     pub async fn get_images(&self, force_refresh: bool) -> Result<Vec<JwstImage>> {
         self.fetch_images(force_refresh).await
     }
+}
 
-    /// Download an image.
-    pub async fn download_image(&self, image: &JwstImage) -> Result<PathBuf> {
-        let wallpaper_dir = self.config.wallpaper_dir();
-        fs::create_dir_all(&wallpaper_dir)?;
-
-        let output_path = wallpaper_dir.join(format!("jwst-{}.jpg", image.id));
-
-        // Return cached if exists
-        if output_path.exists() {
-            return Ok(output_path);
-        }
-
-        // Get download URL
-        let url = image
-            .location
-            .as_ref()
-            .context("No download URL available for image")?;
-
-        // Download
-        let response = self
-            .client
-            .get(url)
-            .send()
-            .await
-            .context("Failed to download image")?;
-
-        if !response.status().is_success() {
-            anyhow::bail!("Download failed with status: {}", response.status());
-        }
-
-        let bytes = response.bytes().await?;
-        fs::write(&output_path, &bytes)?;
-
-        Ok(output_path)
+#[async_trait]
+impl ImageSource for JwstApiService {
+    fn key(&self) -> &str {
+        "jwst_api"
     }
 
-    /// Download a thumbnail for an image.
-    pub async fn download_thumbnail(&self, image: &JwstImage) -> Result<PathBuf> {
-        let thumbnail_dir = self.config.thumbnail_dir();
-        fs::create_dir_all(&thumbnail_dir)?;
-
-        let thumbnail_path = thumbnail_dir.join(format!("{}.thumb.jpg", image.id));
-
-        // Return cached if exists
-        if thumbnail_path.exists() {
-            return Ok(thumbnail_path);
-        }
+    fn name(&self) -> &str {
+        "JWST API"
+    }
 
-        // Get thumbnail URL
-        let url = image
-            .thumbnail
-            .as_ref()
-            .context("No thumbnail URL available")?;
+    fn file_prefix(&self) -> &str {
+        "jwst"
+    }
 
-        // Download
-        let response = self.client.get(url).send().await?;
+    fn config(&self) -> &Config {
+        &self.config
+    }
 
-        if response.status().is_success() {
-            let bytes = response.bytes().await?;
-            fs::write(&thumbnail_path, &bytes)?;
-        }
+    fn client(&self) -> &HttpClient {
+        &self.client
+    }
 
-        Ok(thumbnail_path)
+    async fn list_images(&self, force_refresh: bool) -> Result<Vec<ImageRecord>> {
+        let images = self.get_images(force_refresh).await?;
+        Ok(images.iter().map(JwstImage::to_record).collect())
     }
 
-    /// Check if an image is already downloaded.
-    pub fn is_downloaded(&self, image: &JwstImage) -> bool {
-        let wallpaper_dir = self.config.wallpaper_dir();
-        wallpaper_dir.join(format!("jwst-{}.jpg", image.id)).exists()
+    fn stale_since(&self) -> Option<DateTime<Utc>> {
+        self.stale.since()
     }
 }
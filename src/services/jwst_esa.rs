@@ -1,16 +1,26 @@
 //! ESA/Webb image service - fetches colorized JWST images from ESA's RSS feed.
+//!
+//! Also serves other ESA-style galleries (ESA/Hubble, ESO) that share the same
+//! RSS layout and CDN renditions.
 
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use quick_xml::de::from_str;
-use reqwest::Client;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::SystemTime;
 
-use crate::config::Config;
+use super::esa_metadata::{self, EsaMetadata};
+use super::http::{HttpClient, Service};
+use super::image_source::{self, Conditional, ImageRecord, ImageSource, Rendition, Staleness};
+use crate::config::{Config, EsaFeedConfig};
+use crate::utils::html;
+
+/// How long scraped gallery page metadata is trusted. Pages rarely change
+/// after release, but do get corrections.
+const METADATA_TTL_SECS: u64 = 30 * 24 * 60 * 60;
 
 /// Represents an image from ESA/Webb gallery.
 #[derive(Debug, Clone)]
@@ -23,31 +33,174 @@ pub struct EsaImage {
 
 impl EsaImage {
     /// Get the thumbnail URL.
-    pub fn thumbnail_url(&self, config: &Config) -> String {
-        format!("{}/news/{}.jpg", config.jwst.esa_cdn_base, self.id)
+    pub fn thumbnail_url(&self, feed: &EsaFeedConfig) -> String {
+        format!("{}/news/{}.jpg", feed.cdn_base, self.id)
     }
 
     /// Get the screen-size URL.
-    pub fn screen_url(&self, config: &Config) -> String {
-        format!("{}/screen/{}.jpg", config.jwst.esa_cdn_base, self.id)
+    pub fn screen_url(&self, feed: &EsaFeedConfig) -> String {
+        format!("{}/screen/{}.jpg", feed.cdn_base, self.id)
     }
 
     /// Get the UHD wallpaper URL.
-    pub fn wallpaper_uhd_url(&self, config: &Config) -> String {
-        format!("{}/wallpaper_uhd/{}.jpg", config.jwst.esa_cdn_base, self.id)
+    pub fn wallpaper_uhd_url(&self, feed: &EsaFeedConfig) -> String {
+        format!("{}/wallpaper_uhd/{}.jpg", feed.cdn_base, self.id)
     }
 
     /// Get the large version URL.
-    pub fn large_url(&self, config: &Config) -> String {
-        format!("{}/large/{}.jpg", config.jwst.esa_cdn_base, self.id)
+    pub fn large_url(&self, feed: &EsaFeedConfig) -> String {
+        format!("{}/large/{}.jpg", feed.cdn_base, self.id)
+    }
+
+    /// Get the full-resolution JPEG URL.
+    pub fn original_url(&self, feed: &EsaFeedConfig) -> String {
+        format!("{}/publicationjpg/{}.jpg", feed.cdn_base, self.id)
+    }
+
+    /// Get the full-resolution TIFF URL (not published for every image).
+    pub fn tiff_url(&self, feed: &EsaFeedConfig) -> String {
+        format!("{}/original/{}.tif", feed.cdn_base, self.id)
+    }
+
+    /// Get the gallery page URL.
+    pub fn gallery_url(&self, feed: &EsaFeedConfig) -> String {
+        feed.gallery_url.replace("{id}", &self.id)
     }
 
-    /// Get the ESA gallery page URL.
-    pub fn gallery_url(&self) -> String {
-        format!("https://esawebb.org/images/{}/", self.id)
+    /// Convert into a provider-independent record.
+    pub fn to_record(&self, feed: &EsaFeedConfig) -> ImageRecord {
+        let rendition = |name: &str, url: String| Rendition {
+            name: name.to_string(),
+            url,
+        };
+
+        ImageRecord {
+            source: feed.key.clone(),
+            id: self.id.clone(),
+            title: self.title.clone(),
+            date: self.pub_date,
+            description: None,
+            credit: None,
+            page_url: Some(self.gallery_url(feed)),
+            renditions: vec![
+                rendition("thumbnail", self.thumbnail_url(feed)),
+                rendition("screen", self.screen_url(feed)),
+                rendition("large", self.large_url(feed)),
+                rendition("wallpaper_uhd", self.wallpaper_uhd_url(feed)),
+                rendition("original", self.original_url(feed)),
+                rendition("tiff", self.tiff_url(feed)),
+            ],
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: Vec::new(),
+            fields: vec![("License".to_string(), feed.license.clone())],
+        }
     }
 }
 
+/// An entry from a gallery archive listing page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveEntry {
+    pub id: String,
+    pub title: String,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+impl ArchiveEntry {
+    /// Convert into a provider-independent record.
+    pub fn to_record(&self, feed: &EsaFeedConfig) -> ImageRecord {
+        let image = EsaImage {
+            id: self.id.clone(),
+            title: self.title.clone(),
+            pub_date: None,
+            enclosure_url: None,
+        };
+        let mut record = image.to_record(feed);
+        record.dimensions = self.width.zip(self.height);
+        record
+    }
+}
+
+/// Parse the `var images = [...]` listing embedded in archive pages.
+///
+/// Archive pages render their thumbnails from a JavaScript array of object
+/// literals with single-quoted strings, e.g.
+/// `{ id: 'potm2511a', title: '...', width: 4000, height: 2250, ... }`.
+pub fn parse_archive_page(html: &str) -> Vec<ArchiveEntry> {
+    let start = match html.find("var images = [") {
+        Some(pos) => pos + "var images = [".len(),
+        None => return Vec::new(),
+    };
+
+    let mut entries = Vec::new();
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut key = String::new();
+    let mut chars = html[start..].chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            ']' => break,
+            '{' => fields.clear(),
+            '}' => {
+                let get = |name: &str| {
+                    fields
+                        .iter()
+                        .find(|(k, _)| k == name)
+                        .map(|(_, v)| v.clone())
+                };
+                if let Some(id) = get("id").filter(|id| !id.is_empty()) {
+                    entries.push(ArchiveEntry {
+                        title: html::unescape(&get("title").unwrap_or_else(|| id.clone())),
+                        width: get("width").and_then(|w| w.parse().ok()),
+                        height: get("height").and_then(|h| h.parse().ok()),
+                        id,
+                    });
+                }
+            }
+            '\'' | '"' => {
+                // Quoted value with backslash escapes
+                let quote = c;
+                let mut value = String::new();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some(escaped) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        _ if c == quote => break,
+                        _ => value.push(c),
+                    }
+                }
+                fields.push((std::mem::take(&mut key), value));
+            }
+            ':' | ',' | ' ' | '\n' | '\r' | '\t' => {}
+            _ => {
+                // Bare identifier (key) or number (value)
+                let mut word = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_alphanumeric() || next == '_' || next == '.' {
+                        word.push(next);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if key.is_empty() && chars.peek() == Some(&':') {
+                    key = word;
+                } else {
+                    fields.push((std::mem::take(&mut key), word));
+                }
+            }
+        }
+    }
+
+    entries
+}
+
 /// RSS feed structures for deserialization.
 #[derive(Debug, Deserialize)]
 struct Rss {
@@ -74,61 +227,71 @@ struct Enclosure {
     url: Option<String>,
 }
 
-/// Service for fetching images from ESA/Webb RSS feed.
+/// Service for fetching images from an ESA-style RSS feed.
 pub struct EsaService {
     config: Arc<Config>,
-    client: Client,
+    feed: EsaFeedConfig,
+    client: HttpClient,
     cache_file: PathBuf,
+    stale: Staleness,
 }
 
 impl EsaService {
-    /// Create a new ESA service.
-    pub fn new(config: Arc<Config>) -> Self {
+    /// Create a new ESA service for the given feed.
+    pub fn new(config: Arc<Config>, feed: EsaFeedConfig) -> Result<Self> {
         let cache_dir = config.cache_dir();
         fs::create_dir_all(&cache_dir).ok();
 
-        Self {
-            cache_file: cache_dir.join("esa_metadata.xml"),
-            config,
-            client: Client::builder()
-                .user_agent("JWST-Cosmos/0.1.0 (Rust; Ratatui TUI)")
-                .build()
-                .expect("Failed to create HTTP client"),
-        }
-    }
-
-    /// Check if the cache is still valid.
-    fn is_cache_valid(&self) -> bool {
-        if !self.cache_file.exists() {
-            return false;
-        }
-
-        if let Ok(metadata) = fs::metadata(&self.cache_file) {
-            if let Ok(modified) = metadata.modified() {
-                if let Ok(duration) = SystemTime::now().duration_since(modified) {
-                    return duration.as_secs() < self.config.jwst.cache_ttl;
-                }
-            }
-        }
+        // Keep the original cache name for ESA/Webb
+        let cache_file = if feed.key == "esa" {
+            cache_dir.join("esa_metadata.xml")
+        } else {
+            cache_dir.join(format!("esa_{}_metadata.xml", feed.key))
+        };
 
-        false
+        Ok(Self {
+            cache_file,
+            stale: Staleness::default(),
+            feed,
+            client: HttpClient::new(&config, Service::Images)?,
+            config,
+        })
     }
 
     /// Fetch the RSS feed.
+    ///
+    /// Once the cache expires (or on a forced refresh) the feed is
+    /// revalidated with its ETag / Last-Modified, so an unchanged feed costs
+    /// a 304. If the feed can't be reached, the expired cache is served.
     async fn fetch_rss(&self, force_refresh: bool) -> Result<String> {
-        // Check cache first
-        if !force_refresh && self.is_cache_valid() {
-            return fs::read_to_string(&self.cache_file)
-                .context("Failed to read RSS cache");
-        }
+        let read_cache = |path: &Path| fs::read_to_string(path).context("Failed to read RSS cache");
+        image_source::fetch_or_stale(
+            &self.config,
+            &self.cache_file,
+            &self.cache_file,
+            force_refresh,
+            &self.stale,
+            read_cache,
+            self.download_rss(),
+        )
+        .await
+    }
 
-        // Fetch from RSS
-        let response = self
-            .client
-            .get(&self.config.jwst.esa_rss_url)
-            .send()
+    /// Fetch the RSS feed from the network, updating the cache.
+    async fn download_rss(&self) -> Result<String> {
+        let request = self.client.get(&self.feed.rss_url);
+        let (response, validators) = match image_source::fetch_conditional(request, &self.cache_file)
             .await
-            .context("Failed to fetch ESA RSS feed")?;
+            .with_context(|| format!("Failed to fetch {} RSS feed", self.feed.name))?
+        {
+            Conditional::NotModified => {
+                return fs::read_to_string(&self.cache_file).context("Failed to read RSS cache");
+            }
+            Conditional::Modified(response, validators) => (response, validators),
+        };
+        if !response.status().is_success() {
+            anyhow::bail!("RSS request failed with status: {}", response.status());
+        }
 
         let content = response
             .text()
@@ -136,7 +299,9 @@ impl EsaService {
             .context("Failed to read RSS response")?;
 
         // Cache the response
-        fs::write(&self.cache_file, &content).ok();
+        if fs::write(&self.cache_file, &content).is_ok() {
+            validators.save(&self.cache_file);
+        }
 
         Ok(content)
     }
@@ -163,13 +328,8 @@ impl EsaService {
                 continue;
             }
 
-            let title = item
-                .title
-                .unwrap_or_else(|| id.clone())
-                .replace("&amp;", "&")
-                .replace("&lt;", "<")
-                .replace("&gt;", ">")
-                .replace("&quot;", "\"");
+            // quick_xml has already decoded entities in the title
+            let title = item.title.unwrap_or_else(|| id.clone());
 
             let pub_date = item.pub_date.and_then(|d| {
                 // Parse RSS date format: "Mon, 01 Jan 2024 00:00:00 +0000"
@@ -200,97 +360,326 @@ impl EsaService {
         self.parse_rss(&content)
     }
 
-    /// Download a thumbnail for an image.
-    pub async fn download_thumbnail(&self, image: &EsaImage) -> Result<PathBuf> {
-        let thumbnail_dir = self.config.thumbnail_dir();
-        fs::create_dir_all(&thumbnail_dir)?;
+    /// Directory holding cached per-image metadata for this feed.
+    fn metadata_dir(&self) -> PathBuf {
+        self.config.cache_dir().join("metadata").join(&self.feed.key)
+    }
 
-        let thumbnail_path = thumbnail_dir.join(format!("{}.thumb.jpg", image.id));
+    /// Get previously scraped metadata without touching the network.
+    pub fn cached_metadata(&self, id: &str) -> Option<EsaMetadata> {
+        let content = fs::read_to_string(self.metadata_dir().join(format!("{}.json", id))).ok()?;
+        serde_json::from_str(&content).ok()
+    }
 
-        // Return cached if exists
-        if thumbnail_path.exists() {
-            return Ok(thumbnail_path);
+    /// Convert to a record, merging any cached gallery page metadata.
+    fn record_with_cached_metadata(&self, image: &EsaImage) -> ImageRecord {
+        let mut record = image.to_record(&self.feed);
+        if let Some(metadata) = self.cached_metadata(&image.id) {
+            metadata.apply_to(&mut record);
         }
+        record
+    }
 
-        // Try news-sized thumbnail first
-        let url = image.thumbnail_url(&self.config);
-        let response = self.client.get(&url).send().await;
+    /// Get rich metadata for an image from its gallery page.
+    ///
+    /// The result is cached per image ID for `METADATA_TTL_SECS`, unless the
+    /// page yielded nothing (a placeholder or error page is tried again next
+    /// time). If the page can't be fetched, an expired cache is served.
+    pub async fn get_metadata(&self, id: &str) -> Result<EsaMetadata> {
+        let metadata_dir = self.metadata_dir();
+        let cache_file = metadata_dir.join(format!("{}.json", id));
+
+        let cached = self.cached_metadata(id).filter(|metadata| !metadata.is_empty());
+        if let Some(metadata) = &cached {
+            if image_source::is_cache_valid(&cache_file, METADATA_TTL_SECS) {
+                return Ok(metadata.clone());
+            }
+        }
 
-        if let Ok(resp) = response {
-            if resp.status().is_success() {
-                let bytes = resp.bytes().await?;
-                fs::write(&thumbnail_path, &bytes)?;
-                return Ok(thumbnail_path);
+        match self.fetch_metadata(id).await {
+            Ok(metadata) => {
+                if !metadata.is_empty() {
+                    fs::create_dir_all(&metadata_dir).ok();
+                    fs::write(&cache_file, serde_json::to_string_pretty(&metadata)?).ok();
+                }
+                Ok(metadata)
             }
+            Err(e) => match cached {
+                Some(metadata) => {
+                    tracing::debug!("{:#}; using cached metadata for {}", e, id);
+                    Ok(metadata)
+                }
+                None => Err(e),
+            },
         }
+    }
 
-        // Fallback to screen size
-        let url = image.screen_url(&self.config);
-        let response = self.client.get(&url).send().await?;
-        let bytes = response.bytes().await?;
-        fs::write(&thumbnail_path, &bytes)?;
+    /// Fetch and parse an image's gallery page.
+    async fn fetch_metadata(&self, id: &str) -> Result<EsaMetadata> {
+        image_source::ensure_online(&self.config)?;
 
-        Ok(thumbnail_path)
+        let url = self.feed.gallery_url.replace("{id}", id);
+        let response = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .with_context(|| format!("Failed to fetch gallery page for {}", id))?;
+
+        if !response.status().is_success() {
+            anyhow::bail!("Gallery page request failed with status: {}", response.status());
+        }
+
+        let page = response
+            .text()
+            .await
+            .context("Failed to read gallery page")?;
+        Ok(esa_metadata::parse_image_page(&page))
     }
 
-    /// Download an image at the specified resolution.
-    pub async fn download_image(
-        &self,
-        image: &EsaImage,
-        resolution: &str,
-    ) -> Result<PathBuf> {
-        let wallpaper_dir = self.config.wallpaper_dir();
-        fs::create_dir_all(&wallpaper_dir)?;
+    /// Get one page of the gallery archive listing.
+    ///
+    /// Pages are cached individually so crawling further back only fetches
+    /// pages that haven't been seen (or have expired). Returns an empty list
+    /// past the last page.
+    pub async fn get_archive_page(&self, page: u32) -> Result<Vec<ArchiveEntry>> {
+        let pattern = match &self.feed.archive_url {
+            Some(pattern) => pattern,
+            None => return Ok(Vec::new()),
+        };
 
-        let output_path = wallpaper_dir.join(format!("webb-{}.jpg", image.id));
+        let archive_dir = self.config.cache_dir().join("archive").join(&self.feed.key);
+        let cache_file = archive_dir.join(format!("page-{}.json", page));
 
-        // Select URL based on resolution
-        let url = match resolution {
-            "thumbnail" => image.thumbnail_url(&self.config),
-            "screen" => image.screen_url(&self.config),
-            "large" => image.large_url(&self.config),
-            _ => image.wallpaper_uhd_url(&self.config),
+        let read_cache = |path: &Path| -> Result<Vec<ArchiveEntry>> {
+            let content = fs::read_to_string(path)
+                .context("Failed to read archive cache")?;
+            serde_json::from_str(&content).context("Failed to parse cached archive page")
         };
+        image_source::fetch_or_stale(
+            &self.config,
+            &cache_file,
+            &cache_file,
+            false,
+            &self.stale,
+            read_cache,
+            self.download_archive_page(pattern, page, &cache_file),
+        )
+        .await
+    }
 
-        // Try downloading
-        let try_download = async |url: &str| -> Result<Vec<u8>> {
-            let response = self.client.get(url).send().await?;
-            if response.status().is_success() {
-                Ok(response.bytes().await?.to_vec())
-            } else {
-                anyhow::bail!("Download failed with status: {}", response.status())
-            }
+    /// Fetch one archive page from the network, updating its cache file.
+    async fn download_archive_page(&self, pattern: &str, page: u32, cache_file: &Path) -> Result<Vec<ArchiveEntry>> {
+        let read_cache = || -> Result<Vec<ArchiveEntry>> {
+            let content = fs::read_to_string(cache_file)
+                .context("Failed to read archive cache")?;
+            serde_json::from_str(&content).context("Failed to parse cached archive page")
         };
 
-        // Try requested resolution, then fallbacks
-        let bytes = match try_download(&url).await {
-            Ok(b) => b,
-            Err(_) => match try_download(&image.large_url(&self.config)).await {
-                Ok(b) => b,
-                Err(_) => try_download(&image.screen_url(&self.config)).await?,
-            },
+        let url = pattern.replace("{page}", &page.to_string());
+        let request = self.client.get(&url);
+        let (response, validators) = match image_source::fetch_conditional(request, cache_file)
+            .await
+            .with_context(|| format!("Failed to fetch {} archive page {}", self.feed.name, page))?
+        {
+            Conditional::NotModified => return read_cache(),
+            Conditional::Modified(response, validators) => (response, validators),
         };
 
-        fs::write(&output_path, &bytes)?;
-        Ok(output_path)
+        // Past the last page
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !response.status().is_success() {
+            anyhow::bail!("Archive request failed with status: {}", response.status());
+        }
+
+        let html = response
+            .text()
+            .await
+            .context("Failed to read archive page")?;
+        let entries = parse_archive_page(&html);
+
+        // Cache the parsed page
+        if let Some(archive_dir) = cache_file.parent() {
+            fs::create_dir_all(archive_dir).ok();
+        }
+        if fs::write(cache_file, serde_json::to_string_pretty(&entries)?).is_ok() {
+            validators.save(cache_file);
+        }
+
+        Ok(entries)
+    }
+}
+
+#[async_trait]
+impl ImageSource for EsaService {
+    fn key(&self) -> &str {
+        &self.feed.key
+    }
+
+    fn name(&self) -> &str {
+        &self.feed.name
+    }
+
+    fn file_prefix(&self) -> &str {
+        &self.feed.file_prefix
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    async fn list_images(&self, force_refresh: bool) -> Result<Vec<ImageRecord>> {
+        let images = self.get_images(force_refresh).await?;
+        Ok(images.iter().map(|img| self.record_with_cached_metadata(img)).collect())
+    }
+
+    async fn list_more(&self, page: u32) -> Result<Vec<ImageRecord>> {
+        let entries = self.get_archive_page(page).await?;
+        Ok(entries
+            .iter()
+            .map(|e| {
+                let mut record = e.to_record(&self.feed);
+                if let Some(metadata) = self.cached_metadata(&e.id) {
+                    metadata.apply_to(&mut record);
+                }
+                record
+            })
+            .collect())
+    }
+
+    fn has_more(&self) -> bool {
+        self.feed.archive_url.is_some()
+    }
+
+    fn stale_since(&self) -> Option<DateTime<Utc>> {
+        self.stale.since()
+    }
+
+    async fn load_details(&self, image: &ImageRecord) -> Result<Option<ImageRecord>> {
+        let metadata = self.get_metadata(&image.id).await?;
+        let mut record = image.clone();
+        metadata.apply_to(&mut record);
+        Ok(Some(record))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JwstConfig;
+    use crate::services::test_server::{Reply, TestServer};
+    use std::time::{Duration, SystemTime};
+
+    const ARCHIVE_PAGE: &str = include_str!("../../tests/fixtures/esa_archive_page.html");
+    const LAST_ARCHIVE_PAGE: &str = include_str!("../../tests/fixtures/esa_archive_last_page.html");
+    const IMAGE_PAGE: &str = include_str!("../../tests/fixtures/esa_image_page.html");
+
+    fn esa_feed() -> EsaFeedConfig {
+        JwstConfig::default().esa_feeds().remove(0)
     }
 
-    /// Check if an image is already downloaded.
-    pub fn is_downloaded(&self, image: &EsaImage) -> bool {
-        let wallpaper_dir = self.config.wallpaper_dir();
-        wallpaper_dir.join(format!("webb-{}.jpg", image.id)).exists()
-            || wallpaper_dir.join(format!("webb-{}-ultrawide.jpg", image.id)).exists()
+    /// A service caching under `dir` and scraping gallery pages from `server`.
+    fn gallery_service(dir: &Path, server: &TestServer) -> EsaService {
+        let mut config = Config::default();
+        config.jwst.cache_dir = dir.display().to_string();
+        config.network.retries = 0;
+        config.network.requests_per_second = 0.0;
+        let mut feed = esa_feed();
+        feed.gallery_url = format!("{}/images/{{id}}/", server.url);
+        EsaService::new(Arc::new(config), feed).unwrap()
     }
 
-    /// Get path to downloaded image if it exists.
-    pub fn get_downloaded_path(&self, image: &EsaImage) -> Option<PathBuf> {
-        let wallpaper_dir = self.config.wallpaper_dir();
-        let paths = [
-            wallpaper_dir.join(format!("webb-{}-ultrawide.jpg", image.id)),
-            wallpaper_dir.join(format!("webb-{}.jpg", image.id)),
-            wallpaper_dir.join(format!("webb-{}-laptop.jpg", image.id)),
-        ];
+    #[test]
+    fn parses_archive_entries() {
+        let entries = parse_archive_page(ARCHIVE_PAGE);
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        assert_eq!(ids, ["weic2425a", "potm2511a", "weic2424b"]);
+
+        assert_eq!(entries[0].title, "Cosmic Cliffs in the Carina Nebula (NIRCam & MIRI)");
+        assert_eq!((entries[0].width, entries[0].height), (Some(14575), Some(8441)));
+        assert_eq!(entries[1].title, "A galaxy's \"spiral\" arms");
+        assert_eq!(entries[2].title, "Double-quoted title");
+        assert_eq!((entries[2].width, entries[2].height), (None, None));
+    }
+
+    #[test]
+    fn archive_entries_map_to_cdn_renditions() {
+        let feed = esa_feed();
+        let record = parse_archive_page(ARCHIVE_PAGE)[1].to_record(&feed);
+
+        assert_eq!(record.source, "esa");
+        assert_eq!(
+            record.rendition("thumbnail").map(|r| r.url.as_str()),
+            Some("https://cdn.esawebb.org/archives/images/news/potm2511a.jpg")
+        );
+        assert_eq!(record.page_url.as_deref(), Some("https://esawebb.org/images/potm2511a/"));
+        assert_eq!(record.dimensions, Some((4000, 2250)));
+    }
 
-        paths.into_iter().find(|p| p.exists())
+    #[test]
+    fn last_archive_page_is_empty() {
+        assert!(parse_archive_page(LAST_ARCHIVE_PAGE).is_empty());
+        assert!(parse_archive_page("<html><body>Not found</body></html>").is_empty());
+    }
+
+    #[tokio::test]
+    async fn metadata_is_cached_until_it_expires() {
+        let server = TestServer::start(|_, _| Reply::new(200).header("Content-Type", "text/html").body(IMAGE_PAGE.as_bytes())).await;
+        let dir = tempfile::tempdir().unwrap();
+        let service = gallery_service(dir.path(), &server);
+
+        let metadata = service.get_metadata("weic2425a").await.unwrap();
+        assert_eq!(metadata.constellation.as_deref(), Some("Carina"));
+        service.get_metadata("weic2425a").await.unwrap();
+        assert_eq!(server.requests().len(), 1);
+
+        let cache_file = service.metadata_dir().join("weic2425a.json");
+        let expired = SystemTime::now() - Duration::from_secs(METADATA_TTL_SECS + 60);
+        fs::File::options().write(true).open(&cache_file).unwrap().set_modified(expired).unwrap();
+        service.get_metadata("weic2425a").await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn empty_metadata_is_not_cached() {
+        let server = TestServer::start(|_, _| {
+            Reply::new(200).header("Content-Type", "text/html").body(b"<html><body>Coming soon</body></html>")
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let service = gallery_service(dir.path(), &server);
+
+        assert!(service.get_metadata("weic2425a").await.unwrap().is_empty());
+        assert!(service.cached_metadata("weic2425a").is_none());
+        service.get_metadata("weic2425a").await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn expired_metadata_is_served_when_the_page_fails() {
+        let server = TestServer::start(|index, _| {
+            if index == 0 {
+                Reply::new(200).header("Content-Type", "text/html").body(IMAGE_PAGE.as_bytes())
+            } else {
+                Reply::new(503)
+            }
+        })
+        .await;
+        let dir = tempfile::tempdir().unwrap();
+        let service = gallery_service(dir.path(), &server);
+        service.get_metadata("weic2425a").await.unwrap();
+
+        let cache_file = service.metadata_dir().join("weic2425a.json");
+        let expired = SystemTime::now() - Duration::from_secs(METADATA_TTL_SECS + 60);
+        fs::File::options().write(true).open(&cache_file).unwrap().set_modified(expired).unwrap();
+        let metadata = service.get_metadata("weic2425a").await.unwrap();
+        assert_eq!(metadata.credit.as_deref(), Some("NASA, ESA, CSA, and STScI"));
+        assert_eq!(server.requests().len(), 2);
     }
 }
@@ -0,0 +1,1155 @@
+//! The downloaded image library.
+//!
+//! Every image in the wallpaper directory (downloads, their monitor variants,
+//! generated images and files added by hand) is recorded in an index at
+//! `<data_dir>/library.json` with its source, dimensions, palette, tags,
+//! perceptual hash and generation parameters. The browser asks the index
+//! whether an image is downloaded instead of checking the disk, and
+//! `library search` queries it.
+//!
+//! The same picture often ends up on disk more than once: as several
+//! renditions and monitor crops, or from more than one source (a Webb
+//! release is in the ESA feed, the JWST API and sometimes APOD).
+//! `duplicate_groups` finds these by image ID and perceptual hash, and
+//! `dedupe` keeps the best copy of each.
+//!
+//! Files that fail validation are moved to `<wallpaper_dir>/.quarantine/`
+//! rather than deleted, so they can be inspected.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use super::cache::remove_entry;
+use super::collections::CollectionsService;
+use super::favorites::FavoritesService;
+use super::image_source::{ImageRecord, ImageSource};
+use super::local::{is_image_file, local_record};
+use super::provenance::read_sidecar;
+use super::variants::MAX_SOURCE_PIXELS;
+use crate::config::Config;
+use crate::utils::{dominant_colors, hash_distance, perceptual_hash, validate_image_file, SizePreset};
+
+/// Rendition names used in download file names.
+const RENDITIONS: &[&str] = &["thumbnail", "screen", "large", "wallpaper_uhd", "original", "tiff"];
+
+/// Record fields kept as tags, besides its categories and instruments.
+const TAG_FIELDS: &[&str] = &["Object", "Object type", "Constellation"];
+
+/// Colors kept per image.
+const PALETTE_SIZE: usize = 5;
+
+/// Largest distance (in RGB space) at which a palette color matches a
+/// `color:` search.
+const COLOR_DISTANCE: u32 = 48;
+
+/// Largest perceptual hash distance (in bits, out of 64) at which two
+/// images count as the same picture.
+const DUPLICATE_DISTANCE: u32 = 6;
+
+/// Images analyzed per lock of the index.
+const ANALYZE_BATCH: usize = 16;
+
+/// Longest a long analysis runs between saves of the index.
+const ANALYZE_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Name of the quarantine directory inside the wallpaper directory.
+const QUARANTINE_DIR: &str = ".quarantine";
+
+/// Result of verifying the library.
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    /// Number of image files checked
+    pub checked: usize,
+    /// Files moved to quarantine, with the reason
+    pub quarantined: Vec<(PathBuf, String)>,
+}
+
+/// Move a bad file into the quarantine directory next to it. Returns the
+/// new path.
+pub fn quarantine(path: &Path) -> Result<PathBuf> {
+    let dir = path
+        .parent()
+        .unwrap_or_else(|| Path::new("."))
+        .join(QUARANTINE_DIR);
+    fs::create_dir_all(&dir)?;
+
+    // Timestamped so repeated failures of the same image don't collide
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let target = dir.join(format!("{}-{}", Utc::now().format("%Y%m%d-%H%M%S"), name));
+    fs::rename(path, &target)
+        .with_context(|| format!("Failed to quarantine {}", path.display()))?;
+    Ok(target)
+}
+
+/// Validate every image in the wallpaper directory, quarantining any that
+/// are corrupt or truncated.
+pub fn verify_library(config: &Config) -> Result<VerifyReport> {
+    let mut report = VerifyReport::default();
+    for path in list_images(&config.wallpaper_dir()) {
+        report.checked += 1;
+        if let Err(e) = validate_image_file(&path) {
+            let reason = format!("{:#}", e);
+            quarantine(&path)?;
+            report.quarantined.push((path, reason));
+        }
+    }
+
+    Ok(report)
+}
+
+/// Parameters a generated image was made with.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Generation {
+    /// Reference image it was generated from
+    pub reference: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// An image file in the library.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LibraryEntry {
+    pub path: PathBuf,
+    /// Source key, "generated" for generated images, or "local" for files
+    /// the app didn't save
+    pub source: String,
+    /// Image ID within the source (for generated images, the reference's)
+    pub id: String,
+    /// Rendition or monitor profile, e.g. "original" or "ultrawide"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendition: Option<String>,
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<(u32, u32)>,
+    /// Size in bytes
+    pub size: u64,
+    /// Dominant colors as `#rrggbb`, most common first. None until analyzed;
+    /// empty if the image couldn't be decoded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<Vec<String>>,
+    /// Perceptual hash, for finding near-duplicates
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub phash: Option<u64>,
+    /// Categories, instruments and object names from the image's record
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generation: Option<Generation>,
+    /// When the file was saved (or first indexed, if it has no sidecar)
+    pub added: DateTime<Utc>,
+    /// File modification time when it was indexed
+    pub modified: DateTime<Utc>,
+}
+
+impl LibraryEntry {
+    /// Whether this is a monitor crop made after download rather than a
+    /// published rendition.
+    pub fn is_variant(&self) -> bool {
+        self.rendition
+            .as_deref()
+            .is_some_and(|r| !RENDITIONS.contains(&r) && SizePreset::parse(r).is_some())
+    }
+
+    /// Whether the palette or hash still need working out. Images that
+    /// couldn't be decoded (empty palette) aren't retried.
+    fn needs_analysis(&self) -> bool {
+        match &self.palette {
+            None => true,
+            Some(palette) => self.phash.is_none() && !palette.is_empty(),
+        }
+    }
+
+    /// Ranking within a duplicate group: published renditions before
+    /// monitor crops, then by resolution and file size.
+    fn quality(&self) -> (bool, u64, u64) {
+        let pixels = self.dimensions.map_or(0, |(w, h)| w as u64 * h as u64);
+        (!self.is_variant(), pixels, self.size)
+    }
+}
+
+/// Copies of the same picture, best first.
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub entries: Vec<LibraryEntry>,
+    /// Hash distance of each entry from the first; None for files of the
+    /// same image that aren't (or can't be) compared by hash
+    pub distances: Vec<Option<u32>>,
+}
+
+impl DuplicateGroup {
+    /// Whether the group holds more than one image, rather than just the
+    /// renditions and crops of one.
+    pub fn spans_images(&self) -> bool {
+        let first = &self.entries[0];
+        self.entries.iter().any(|e| e.source != first.source || e.id != first.id)
+    }
+}
+
+/// A file removed by a dedupe.
+#[derive(Debug)]
+pub struct RemovedCopy {
+    pub path: PathBuf,
+    /// The copy kept in its place
+    pub kept: PathBuf,
+    /// Hash distance from the kept copy, when matched by hash
+    pub distance: Option<u32>,
+}
+
+/// Result of a dedupe.
+#[derive(Debug, Default)]
+pub struct DedupeReport {
+    pub removed: Vec<RemovedCopy>,
+    /// Monitor variants (crops) left alone because variants weren't included
+    pub kept_variants: Vec<PathBuf>,
+    /// Bytes freed
+    pub freed: u64,
+    /// Favorites pointed at a kept copy
+    pub relinked: usize,
+    /// Collection items pointed at a kept copy
+    pub relinked_items: usize,
+    /// Copies that couldn't be removed
+    pub failed: Vec<(PathBuf, io::Error)>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct LibraryFile {
+    #[serde(default)]
+    entries: Vec<LibraryEntry>,
+}
+
+/// `LibraryFile` for writing, without copying the entries.
+#[derive(Serialize)]
+struct LibraryFileRef<'a> {
+    entries: Vec<&'a LibraryEntry>,
+}
+
+/// Result of a rescan.
+#[derive(Debug, Default)]
+pub struct RescanReport {
+    pub added: usize,
+    pub updated: usize,
+    pub removed: usize,
+    /// Files in the index afterwards
+    pub total: usize,
+}
+
+/// Index of the image library, persisted in the data directory.
+pub struct LibraryIndex {
+    path: PathBuf,
+    wallpaper_dir: PathBuf,
+    /// File name prefixes and the source keys they belong to
+    prefixes: Vec<(String, String)>,
+    entries: Mutex<BTreeMap<PathBuf, LibraryEntry>>,
+    /// Whether entries changed since the last save
+    dirty: AtomicBool,
+    /// Held while writing, so saves land in order
+    saving: Mutex<()>,
+    /// False when an existing index couldn't be read or moved aside; it is
+    /// then left alone rather than overwritten
+    writable: bool,
+}
+
+impl LibraryIndex {
+    /// Load the library index. Run `rescan` to pick up changes made while
+    /// the app wasn't running.
+    ///
+    /// An index that doesn't parse is moved aside (as
+    /// `library.json.<time>.bak`) and a new one started, so nothing in it is
+    /// lost to the next save.
+    pub fn new(config: &Config, sources: &[Arc<dyn ImageSource>]) -> Self {
+        let path = config.data_dir().join("library.json");
+
+        let (entries, writable) = match fs::read_to_string(&path) {
+            Ok(content) => match serde_json::from_str::<LibraryFile>(&content) {
+                Ok(file) => (file.entries, true),
+                Err(e) => {
+                    let backup = path.with_extension(format!("json.{}.bak", Utc::now().format("%Y%m%d-%H%M%S")));
+                    match fs::rename(&path, &backup) {
+                        Ok(()) => {
+                            tracing::debug!(
+                                "Library index {} is damaged ({}); moved it to {} and starting a new one",
+                                path.display(),
+                                e,
+                                backup.display()
+                            );
+                            (Vec::new(), true)
+                        }
+                        Err(rename_error) => {
+                            tracing::debug!(
+                                "Library index {} is damaged ({}) and couldn't be moved aside ({}); not saving it",
+                                path.display(),
+                                e,
+                                rename_error
+                            );
+                            (Vec::new(), false)
+                        }
+                    }
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Vec::new(), true),
+            Err(e) => {
+                tracing::debug!("Failed to read library index {} ({}); not saving it", path.display(), e);
+                (Vec::new(), false)
+            }
+        };
+
+        Self {
+            path,
+            wallpaper_dir: config.wallpaper_dir(),
+            prefixes: source_prefixes(sources),
+            entries: Mutex::new(entries.into_iter().map(|entry| (entry.path.clone(), entry)).collect()),
+            dirty: AtomicBool::new(false),
+            saving: Mutex::new(()),
+            writable,
+        }
+    }
+
+    /// Write the index to disk if it changed since the last save.
+    ///
+    /// Entries are serialized under the lock but written without it, so
+    /// lookups from the UI don't wait on the disk.
+    fn save(&self) -> Result<()> {
+        if !self.writable {
+            return Ok(());
+        }
+        let _saving = self.saving.lock().unwrap();
+        let content = {
+            let entries = self.entries.lock().unwrap();
+            if !self.dirty.swap(false, Ordering::SeqCst) {
+                return Ok(());
+            }
+            serde_json::to_string(&LibraryFileRef {
+                entries: entries.values().collect(),
+            })?
+        };
+
+        let result = self.write(&content);
+        if result.is_err() {
+            self.dirty.store(true, Ordering::SeqCst);
+        }
+        result
+    }
+
+    fn write(&self, content: &str) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Write to a temp file first so a crash can't truncate the index
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, content).context("Failed to write library index")?;
+        fs::rename(&tmp, &self.path).context("Failed to save library index")?;
+        Ok(())
+    }
+
+    /// Save from a method that has nothing to return an error in. The
+    /// index stays dirty on failure, so the next save tries again.
+    fn save_or_log(&self) {
+        if let Err(e) = self.save() {
+            tracing::debug!("{:#}", e);
+        }
+    }
+
+    /// The first of `candidates` that is in the library.
+    pub fn first_indexed(&self, candidates: &[PathBuf]) -> Option<PathBuf> {
+        let entries = self.entries.lock().unwrap();
+        candidates.iter().find(|p| entries.contains_key(*p)).cloned()
+    }
+
+    /// Record newly saved files. With the image's record, its source, title
+    /// and tags are taken from it rather than guessed from the file name.
+    pub fn add(&self, paths: &[PathBuf], record: Option<&ImageRecord>) {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            for path in paths {
+                if let Some(entry) = index_file(path, &self.prefixes, record, entries.get(path)) {
+                    entries.insert(path.clone(), entry);
+                    self.dirty.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+        self.save_or_log();
+    }
+
+    /// Forget files that were removed.
+    pub fn remove(&self, paths: &[PathBuf]) {
+        {
+            let mut entries = self.entries.lock().unwrap();
+            for path in paths {
+                if entries.remove(path).is_some() {
+                    self.dirty.store(true, Ordering::SeqCst);
+                }
+            }
+        }
+        self.save_or_log();
+    }
+
+    /// Bring the index up to date with the wallpaper directory: index new
+    /// and changed files and drop missing ones.
+    pub fn rescan(&self) -> RescanReport {
+        // Files are read without holding the lock, so lookups from the UI
+        // aren't blocked behind a large first scan
+        let previous = self.entries.lock().unwrap().clone();
+        let mut report = RescanReport::default();
+        let mut scanned = BTreeMap::new();
+
+        for path in list_images(&self.wallpaper_dir) {
+            let old = previous.get(&path);
+            let unchanged = old.is_some_and(|old| {
+                fs::metadata(&path).is_ok_and(|m| {
+                    m.len() == old.size && m.modified().ok().map(DateTime::<Utc>::from) == Some(old.modified)
+                })
+            });
+            if unchanged {
+                scanned.insert(path, old.unwrap().clone());
+                continue;
+            }
+            if let Some(entry) = index_file(&path, &self.prefixes, None, old) {
+                match old {
+                    Some(_) => report.updated += 1,
+                    None => report.added += 1,
+                }
+                scanned.insert(path, entry);
+            }
+        }
+        report.removed = previous.keys().filter(|p| !scanned.contains_key(*p)).count();
+
+        {
+            let mut entries = self.entries.lock().unwrap();
+            // Keep anything added or analyzed while scanning
+            for (path, entry) in entries.iter() {
+                if previous.get(path) != Some(entry) && path.exists() {
+                    scanned.insert(path.clone(), entry.clone());
+                }
+            }
+            if *entries != scanned {
+                self.dirty.store(true, Ordering::SeqCst);
+            }
+            *entries = scanned;
+            report.total = entries.len();
+        }
+        self.save_or_log();
+
+        tracing::debug!(
+            "Library: {} files ({} added, {} updated, {} removed)",
+            report.total,
+            report.added,
+            report.updated,
+            report.removed
+        );
+        report
+    }
+
+    /// Work out the palette and perceptual hash of every image that hasn't
+    /// been analyzed yet. Returns how many were analyzed.
+    pub fn analyze(&self) -> usize {
+        let pending: Vec<(PathBuf, DateTime<Utc>)> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| e.needs_analysis())
+            .map(|e| (e.path.clone(), e.modified))
+            .collect();
+
+        let mut last_save = Instant::now();
+        for batch in pending.chunks(ANALYZE_BATCH) {
+            let results: Vec<_> = batch
+                .iter()
+                .map(|(path, modified)| (path, modified, analyze_file(path)))
+                .collect();
+
+            let mut entries = self.entries.lock().unwrap();
+            for (path, modified, (palette, phash)) in results {
+                // Skip files replaced while they were being analyzed
+                if let Some(entry) = entries.get_mut(path).filter(|e| e.modified == *modified) {
+                    entry.palette = Some(palette);
+                    entry.phash = phash;
+                    self.dirty.store(true, Ordering::SeqCst);
+                }
+            }
+            drop(entries);
+
+            if last_save.elapsed() >= ANALYZE_SAVE_INTERVAL {
+                self.save_or_log();
+                last_save = Instant::now();
+            }
+        }
+        self.save_or_log();
+
+        pending.len()
+    }
+
+    /// Find copies of the same picture: files of the same image (renditions
+    /// and crops), and files whose perceptual hashes are within
+    /// `DUPLICATE_DISTANCE` bits. Generated images are never duplicates.
+    ///
+    /// Each group is built around its best file, and only files close to
+    /// that one join it, so a chain of slightly different pictures doesn't
+    /// end up as one group.
+    pub fn duplicate_groups(&self) -> Vec<DuplicateGroup> {
+        let mut entries: Vec<LibraryEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| e.generation.is_none())
+            .cloned()
+            .collect();
+        entries.sort_by(|a, b| b.quality().cmp(&a.quality()).then_with(|| a.path.cmp(&b.path)));
+
+        let mut grouped = vec![false; entries.len()];
+        let mut groups = Vec::new();
+        for i in 0..entries.len() {
+            if grouped[i] {
+                continue;
+            }
+            let best = &entries[i];
+            let mut members = vec![(i, Some(0))];
+            for (j, other) in entries.iter().enumerate().skip(i + 1) {
+                if grouped[j] {
+                    continue;
+                }
+                let distance = match (best.phash, other.phash) {
+                    (Some(x), Some(y)) => Some(hash_distance(x, y)),
+                    _ => None,
+                };
+                let same_image = best.source != "local" && best.source == other.source && best.id == other.id;
+                if same_image || distance.is_some_and(|d| d <= DUPLICATE_DISTANCE) {
+                    // Crops of the same image hash differently; their
+                    // distance says nothing
+                    members.push((j, distance.filter(|&d| d <= DUPLICATE_DISTANCE)));
+                }
+            }
+            if members.len() < 2 {
+                continue;
+            }
+            for &(j, _) in &members {
+                grouped[j] = true;
+            }
+            groups.push(DuplicateGroup {
+                entries: members.iter().map(|&(j, _)| entries[j].clone()).collect(),
+                distances: members.iter().map(|&(_, distance)| distance).collect(),
+            });
+        }
+        groups
+    }
+
+    /// Remove every copy but the best of each duplicate group. `keep` (the
+    /// current wallpaper) is never removed, and neither are monitor variants
+    /// unless `include_variants` is set. Favorites and collection items left
+    /// without any file are relinked to the kept copy. Copies that can't be removed are
+    /// reported in `failed`. With `dry_run`, only reports what would happen.
+    pub fn dedupe(
+        &self,
+        favorites: &FavoritesService,
+        collections: &CollectionsService,
+        keep: Option<&Path>,
+        include_variants: bool,
+        dry_run: bool,
+    ) -> Result<DedupeReport> {
+        let keep = keep.map(canonical);
+        let mut report = DedupeReport::default();
+        let mut removed_paths = Vec::new();
+
+        for group in self.duplicate_groups() {
+            let (kept, others) = group.entries.split_first().expect("groups have two or more entries");
+            let mut remaining: Vec<&LibraryEntry> = vec![kept];
+            let mut removed: Vec<&LibraryEntry> = Vec::new();
+
+            for (entry, &distance) in others.iter().zip(&group.distances[1..]) {
+                if keep.as_ref() == Some(&canonical(&entry.path)) {
+                    remaining.push(entry);
+                    continue;
+                }
+                if entry.is_variant() && !include_variants {
+                    report.kept_variants.push(entry.path.clone());
+                    remaining.push(entry);
+                    continue;
+                }
+                if !dry_run {
+                    if let Err(e) = remove_entry(&entry.path) {
+                        report.failed.push((entry.path.clone(), e));
+                        remaining.push(entry);
+                        continue;
+                    }
+                }
+                report.freed += entry.size;
+                report.removed.push(RemovedCopy {
+                    path: entry.path.clone(),
+                    kept: kept.path.clone(),
+                    distance,
+                });
+                removed_paths.push(entry.path.clone());
+                removed.push(entry);
+            }
+
+            // A favorite or collection item may be saved under its source
+            // or, when browsing local files, under its file name
+            let mut orphaned: Vec<(String, String)> = Vec::new();
+            for entry in &removed {
+                let file_name = entry.path.file_name().unwrap_or_default().to_string_lossy().to_string();
+                let identities = [(entry.source.clone(), entry.id.clone()), ("local".to_string(), file_name)];
+                for (source, id) in identities {
+                    let left = remaining.iter().any(|e| e.source == source && e.id == id);
+                    let saved = favorites.is_favorite(&source, &id) || !collections.containing(&source, &id).is_empty();
+                    if !left && saved && !orphaned.contains(&(source.clone(), id.clone())) {
+                        orphaned.push((source, id));
+                    }
+                }
+            }
+
+            if orphaned.is_empty() {
+                continue;
+            }
+            // Prefer the full record the kept copy was saved with
+            let target = favorites
+                .list()
+                .into_iter()
+                .map(|f| f.record)
+                .chain(collections.list().into_iter().flat_map(|c| c.items))
+                .find(|r| r.source == kept.source && r.id == kept.id)
+                .unwrap_or_else(|| local_record(&kept.path));
+            for (source, id) in orphaned {
+                if favorites.is_favorite(&source, &id) {
+                    report.relinked += 1;
+                    if !dry_run {
+                        favorites.relink(&source, &id, &target)?;
+                    }
+                }
+                let items = collections.containing(&source, &id).len();
+                if items > 0 {
+                    report.relinked_items += items;
+                    if !dry_run {
+                        collections.relink(&source, &id, &target)?;
+                    }
+                }
+            }
+        }
+
+        if !dry_run {
+            self.remove(&removed_paths);
+        }
+        Ok(report)
+    }
+
+    /// Entries matching a query, most recently added first.
+    pub fn search(&self, query: &LibraryQuery) -> Vec<LibraryEntry> {
+        let mut found: Vec<LibraryEntry> = self
+            .entries
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|e| query.matches(e))
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| b.added.cmp(&a.added).then_with(|| a.path.cmp(&b.path)));
+        found
+    }
+}
+
+/// A library search.
+///
+/// Words match the title, ID, file name, tags and prompt; `key:value` terms
+/// narrow it further, e.g. `carina src:esa tag:nircam color:#c06030 gen:no`.
+#[derive(Debug, Clone, Default)]
+pub struct LibraryQuery {
+    pub words: Vec<String>,
+    pub source: Option<String>,
+    pub tags: Vec<String>,
+    pub rendition: Option<String>,
+    /// A palette color near this one
+    pub color: Option<[u8; 3]>,
+    /// Generated images only (true) or downloads only (false)
+    pub generated: Option<bool>,
+    /// Minimum width in pixels
+    pub min_width: Option<u32>,
+}
+
+impl LibraryQuery {
+    /// Parse a query. Unknown or malformed terms are reported as errors.
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let mut query = Self::default();
+
+        for term in line.split_whitespace() {
+            let Some((key, value)) = term.split_once(':') else {
+                query.words.push(term.to_lowercase());
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                return Err(format!("Missing value for '{}'", key));
+            }
+
+            match key.to_lowercase().as_str() {
+                "src" | "source" => query.source = Some(value.to_lowercase()),
+                "tag" => query.tags.push(value.to_lowercase()),
+                "rendition" | "size" => query.rendition = Some(value.to_lowercase()),
+                "color" | "colour" => {
+                    query.color = Some(parse_hex(value).ok_or_else(|| format!("color: expects #rrggbb, got '{}'", value))?)
+                }
+                "gen" | "generated" => {
+                    query.generated = match value.to_lowercase().as_str() {
+                        "yes" | "y" | "true" => Some(true),
+                        "no" | "n" | "false" => Some(false),
+                        _ => return Err(format!("gen: expects yes/no, got '{}'", value)),
+                    }
+                }
+                "width" | "minwidth" => {
+                    query.min_width = Some(value.parse().map_err(|_| format!("width: expects pixels, got '{}'", value))?)
+                }
+                _ => return Err(format!("Unknown search term '{}'", key)),
+            }
+        }
+
+        Ok(query)
+    }
+
+    /// Check whether an entry matches every term.
+    pub fn matches(&self, entry: &LibraryEntry) -> bool {
+        if let Some(source) = &self.source {
+            if !entry.source.to_lowercase().contains(source.as_str()) {
+                return false;
+            }
+        }
+        if let Some(generated) = self.generated {
+            if entry.generation.is_some() != generated {
+                return false;
+            }
+        }
+        if let Some(rendition) = &self.rendition {
+            if entry.rendition.as_deref().map(str::to_lowercase).as_ref() != Some(rendition) {
+                return false;
+            }
+        }
+        if let Some(min_width) = self.min_width {
+            if entry.dimensions.is_none_or(|(w, _)| w < min_width) {
+                return false;
+            }
+        }
+        if let Some(color) = self.color {
+            let near = entry
+                .palette
+                .iter()
+                .flatten()
+                .filter_map(|hex| parse_hex(hex))
+                .any(|c| color_distance(c, color) <= COLOR_DISTANCE * COLOR_DISTANCE);
+            if !near {
+                return false;
+            }
+        }
+
+        let tags: Vec<String> = entry.tags.iter().map(|t| t.to_lowercase()).collect();
+        if !self.tags.iter().all(|tag| tags.iter().any(|t| t.contains(tag.as_str()))) {
+            return false;
+        }
+
+        let file_name = entry.path.file_name().unwrap_or_default().to_string_lossy();
+        let prompt = entry.generation.as_ref().and_then(|g| g.prompt.as_deref()).unwrap_or_default();
+        let haystack = format!("{} {} {} {} {}", entry.title, entry.id, file_name, tags.join(" "), prompt).to_lowercase();
+        self.words.iter().all(|word| haystack.contains(word.as_str()))
+    }
+}
+
+/// File name prefixes (`<prefix>-`) of the sources that download files,
+/// longest first so "webb-potm" isn't taken for "webb".
+pub fn source_prefixes(sources: &[Arc<dyn ImageSource>]) -> Vec<(String, String)> {
+    let mut prefixes: Vec<(String, String)> = sources
+        .iter()
+        .filter(|s| s.key() != "local")
+        .map(|s| (format!("{}-", s.file_prefix()), s.key().to_string()))
+        .collect();
+    prefixes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+    prefixes
+}
+
+/// Image files directly inside `dir`, sorted.
+fn list_images(dir: &Path) -> Vec<PathBuf> {
+    let mut paths: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && is_image_file(p))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    paths.sort();
+    paths
+}
+
+/// Build the index entry for a file.
+///
+/// Source, ID and rendition come from the image's record if given, else its
+/// provenance sidecar, else the file name. Tags and the date it was first
+/// indexed carry over from `previous`.
+fn index_file(
+    path: &Path,
+    prefixes: &[(String, String)],
+    record: Option<&ImageRecord>,
+    previous: Option<&LibraryEntry>,
+) -> Option<LibraryEntry> {
+    let metadata = fs::metadata(path).ok()?;
+    let modified = metadata.modified().map(DateTime::<Utc>::from).unwrap_or_else(|_| Utc::now());
+
+    let (mut source, mut id, mut rendition) = parse_file_name(path, prefixes);
+    let mut title = path
+        .file_stem()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let mut tags = previous.map(|e| e.tags.clone()).unwrap_or_default();
+    let mut generation = None;
+    let mut added = previous.map(|e| e.added);
+    // An unchanged file keeps its analysis
+    let unchanged = previous.filter(|e| e.modified == modified && e.size == metadata.len());
+    let palette = unchanged.and_then(|e| e.palette.clone());
+    let phash = unchanged.and_then(|e| e.phash);
+
+    if let Some(provenance) = read_sidecar(path) {
+        id = provenance.id;
+        title = provenance.title;
+        rendition = provenance.rendition.or(rendition);
+        added = added.or(Some(provenance.saved));
+        source = match provenance.generated_from {
+            Some(reference) => {
+                generation = Some(Generation {
+                    reference,
+                    prompt: provenance.prompt,
+                    model: provenance.model,
+                });
+                "generated".to_string()
+            }
+            None => provenance.source,
+        };
+    }
+
+    if let Some(record) = record.filter(|_| generation.is_none()) {
+        source = record.source.clone();
+        id = record.id.clone();
+        title = record.title.clone();
+        tags = record_tags(record);
+    }
+
+    Some(LibraryEntry {
+        path: path.to_path_buf(),
+        source,
+        id,
+        rendition,
+        title,
+        dimensions: image::image_dimensions(path).ok(),
+        size: metadata.len(),
+        palette,
+        phash,
+        tags,
+        generation,
+        added: added.unwrap_or_else(Utc::now),
+        modified,
+    })
+}
+
+/// Guess source, ID and rendition from a file name like
+/// `webb-weic2425a-screen.jpg`. Files without a known prefix are "local",
+/// with the file name as ID.
+fn parse_file_name(path: &Path, prefixes: &[(String, String)]) -> (String, String, Option<String>) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+
+    let Some((prefix, key)) = prefixes.iter().find(|(prefix, _)| stem.starts_with(prefix.as_str())) else {
+        return ("local".to_string(), name.to_string(), None);
+    };
+    let rest = &stem[prefix.len()..];
+    match rest.rsplit_once('-') {
+        Some((id, suffix)) if RENDITIONS.contains(&suffix) || SizePreset::parse(suffix).is_some() => {
+            (key.clone(), id.to_string(), Some(suffix.to_string()))
+        }
+        // Downloads from before file names recorded the rendition
+        _ => (key.clone(), rest.to_string(), None),
+    }
+}
+
+/// Tags from an image's record.
+fn record_tags(record: &ImageRecord) -> Vec<String> {
+    let values = record
+        .categories
+        .iter()
+        .chain(&record.instruments)
+        .map(String::as_str)
+        .chain(TAG_FIELDS.iter().filter_map(|label| record.field(label)));
+    let mut tags: Vec<String> = Vec::new();
+    for value in values {
+        if !tags.iter().any(|t| t.eq_ignore_ascii_case(value)) {
+            tags.push(value.to_string());
+        }
+    }
+    tags
+}
+
+/// Dominant colors and perceptual hash of an image file; an empty palette
+/// and no hash if it can't be decoded.
+fn analyze_file(path: &Path) -> (Vec<String>, Option<u64>) {
+    let too_large = image::image_dimensions(path).is_ok_and(|(w, h)| w as u64 * h as u64 > MAX_SOURCE_PIXELS);
+    if too_large {
+        return (Vec::new(), None);
+    }
+    match image::open(path) {
+        Ok(img) => {
+            let palette = dominant_colors(&img, PALETTE_SIZE)
+                .into_iter()
+                .map(|[r, g, b]| format!("#{:02x}{:02x}{:02x}", r, g, b))
+                .collect();
+            (palette, Some(perceptual_hash(&img)))
+        }
+        Err(e) => {
+            tracing::debug!("Can't analyze {}: {}", path.display(), e);
+            (Vec::new(), None)
+        }
+    }
+}
+
+/// Parse `#rrggbb` (the `#` is optional).
+fn parse_hex(value: &str) -> Option<[u8; 3]> {
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+/// Squared distance between two colors.
+fn color_distance(a: [u8; 3], b: [u8; 3]) -> u32 {
+    a.iter().zip(b).map(|(&x, y)| (x as i32 - y as i32).pow(2) as u32).sum()
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, rendition: &str, (width, height): (u32, u32), phash: u64) -> LibraryEntry {
+        let now = Utc::now();
+        LibraryEntry {
+            path: PathBuf::from(format!("/wallpapers/{}.jpg", name)),
+            source: "esa".to_string(),
+            id: name.split('-').next().unwrap().to_string(),
+            rendition: Some(rendition.to_string()),
+            title: name.to_string(),
+            dimensions: Some((width, height)),
+            size: width as u64 * height as u64 / 4,
+            palette: Some(Vec::new()),
+            phash: Some(phash),
+            tags: Vec::new(),
+            generation: None,
+            added: now,
+            modified: now,
+        }
+    }
+
+    fn index(dir: &Path, entries: Vec<LibraryEntry>) -> LibraryIndex {
+        LibraryIndex {
+            path: dir.join("library.json"),
+            wallpaper_dir: PathBuf::from("/wallpapers"),
+            prefixes: Vec::new(),
+            entries: Mutex::new(entries.into_iter().map(|e| (e.path.clone(), e)).collect()),
+            dirty: AtomicBool::new(false),
+            saving: Mutex::new(()),
+            writable: true,
+        }
+    }
+
+    fn config(dir: &Path) -> Config {
+        let mut config = Config::default();
+        config.jwst.data_dir = dir.to_string_lossy().to_string();
+        config.jwst.wallpaper_dir = dir.join("wallpapers").to_string_lossy().to_string();
+        config
+    }
+
+    fn favorites(dir: &Path) -> FavoritesService {
+        FavoritesService::new(&config(dir))
+    }
+
+    #[test]
+    fn saves_compact_index_once_per_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(dir.path(), Vec::new());
+        let path = dir.path().join("library.json");
+
+        index.save().unwrap();
+        assert!(!path.exists(), "nothing changed, nothing to write");
+
+        index.remove(&[PathBuf::from("/wallpapers/missing.jpg")]);
+        assert!(!path.exists());
+
+        index.entries.lock().unwrap().insert(PathBuf::from("/a.jpg"), entry("a-uhd", "wallpaper_uhd", (10, 10), 0));
+        index.dirty.store(true, Ordering::SeqCst);
+        index.save().unwrap();
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(!content.contains('\n'));
+
+        let reloaded = LibraryIndex::new(&config(dir.path()), &[]);
+        assert_eq!(reloaded.entries.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn damaged_index_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("library.json");
+        fs::write(&path, "{\"entries\": [{\"path\": ").unwrap();
+
+        let index = LibraryIndex::new(&config(dir.path()), &[]);
+        assert!(index.entries.lock().unwrap().is_empty());
+        assert!(!path.exists());
+        let backups: Vec<String> = fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .filter(|name| name.starts_with("library.json.") && name.ends_with(".bak"))
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(
+            fs::read_to_string(dir.path().join(&backups[0])).unwrap(),
+            "{\"entries\": [{\"path\": "
+        );
+    }
+
+    #[test]
+    fn groups_only_files_close_to_the_best_copy() {
+        let dir = tempfile::tempdir().unwrap();
+        // b is 5 bits from a, c is 5 from b but 10 from a
+        let index = index(
+            dir.path(),
+            vec![
+                entry("a-uhd", "wallpaper_uhd", (3840, 2160), 0),
+                entry("b-uhd", "wallpaper_uhd", (1920, 1080), 0b11111),
+                entry("c-uhd", "wallpaper_uhd", (1280, 720), 0b11111_11111),
+            ],
+        );
+
+        let groups = index.duplicate_groups();
+        assert_eq!(groups.len(), 1);
+        let names: Vec<&str> = groups[0].entries.iter().map(|e| e.title.as_str()).collect();
+        assert_eq!(names, ["a-uhd", "b-uhd"]);
+        assert_eq!(groups[0].distances, [Some(0), Some(5)]);
+    }
+
+    #[test]
+    fn dedupe_leaves_variants_unless_included() {
+        let dir = tempfile::tempdir().unwrap();
+        let index = index(
+            dir.path(),
+            vec![
+                entry("x-wallpaper_uhd", "wallpaper_uhd", (3840, 2160), 0),
+                entry("x-screen", "screen", (1280, 720), 1),
+                entry("x-ultrawide", "ultrawide", (3440, 1440), u64::MAX),
+            ],
+        );
+        let favorites = favorites(dir.path());
+        let collections = CollectionsService::new(&config(dir.path()));
+
+        let report = index.dedupe(&favorites, &collections, None, false, true).unwrap();
+        let removed: Vec<&Path> = report.removed.iter().map(|r| r.path.as_path()).collect();
+        assert_eq!(removed, [Path::new("/wallpapers/x-screen.jpg")]);
+        assert_eq!(report.removed[0].kept, Path::new("/wallpapers/x-wallpaper_uhd.jpg"));
+        assert_eq!(report.removed[0].distance, Some(1));
+        assert_eq!(report.kept_variants, [PathBuf::from("/wallpapers/x-ultrawide.jpg")]);
+
+        let report = index.dedupe(&favorites, &collections, None, true, true).unwrap();
+        assert_eq!(report.removed.len(), 2);
+        assert_eq!(report.removed[1].distance, None);
+        assert!(report.kept_variants.is_empty());
+    }
+
+    #[test]
+    fn dedupe_relinks_favorites_and_collection_items() {
+        let dir = tempfile::tempdir().unwrap();
+        let wallpapers = dir.path().join("wallpapers");
+        fs::create_dir_all(&wallpapers).unwrap();
+        let mut entries = vec![
+            entry("x-wallpaper_uhd", "wallpaper_uhd", (3840, 2160), 0),
+            entry("y-screen", "screen", (1280, 720), 1),
+        ];
+        for entry in &mut entries {
+            entry.path = wallpapers.join(entry.path.file_name().unwrap());
+            fs::write(&entry.path, b"image").unwrap();
+        }
+        let (kept, copy) = (entries[0].path.clone(), entries[1].path.clone());
+        let index = index(dir.path(), entries);
+
+        let record = |id: &str| ImageRecord {
+            source: "esa".to_string(),
+            id: id.to_string(),
+            title: format!("Image {}", id),
+            date: None,
+            description: None,
+            credit: None,
+            page_url: None,
+            renditions: Vec::new(),
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: Vec::new(),
+            fields: Vec::new(),
+        };
+        let favorites = favorites(dir.path());
+        favorites.toggle(&record("y")).unwrap();
+        let collections = CollectionsService::new(&config(dir.path()));
+        collections.add("Nebulae", &record("y")).unwrap();
+        collections.add("Both", &record("x")).unwrap();
+        collections.add("Both", &record("y")).unwrap();
+
+        let report = index.dedupe(&favorites, &collections, None, false, true).unwrap();
+        assert_eq!((report.relinked, report.relinked_items), (1, 2));
+        assert!(copy.exists());
+        assert!(collections.get("Nebulae").unwrap().contains("esa", "y"));
+
+        let report = index.dedupe(&favorites, &collections, None, false, false).unwrap();
+        assert_eq!((report.relinked, report.relinked_items), (1, 2));
+        assert!(report.failed.is_empty());
+        assert!(kept.exists() && !copy.exists());
+
+        let ids = |name: &str| -> Vec<String> {
+            collections.get(name).unwrap().items.into_iter().map(|i| i.id).collect()
+        };
+        assert_eq!(ids("Nebulae"), ["x"]);
+        assert_eq!(ids("Both"), ["x"]);
+        // The relinked item is the record the kept copy was saved with
+        assert_eq!(collections.get("Nebulae").unwrap().items[0].title, "Image x");
+        assert!(favorites.is_favorite("esa", "x") && !favorites.is_favorite("esa", "y"));
+
+        // Saved to disk too
+        let reloaded = CollectionsService::new(&config(dir.path()));
+        assert_eq!(reloaded.get("Nebulae").unwrap().items[0].id, "x");
+    }
+
+    #[test]
+    fn tags_come_from_categories_instruments_and_object_fields() {
+        let record = ImageRecord {
+            source: "esa".to_string(),
+            id: "weic2425a".to_string(),
+            title: "Cosmic Cliffs".to_string(),
+            date: None,
+            description: None,
+            credit: None,
+            page_url: None,
+            renditions: Vec::new(),
+            dimensions: Some((14575, 8441)),
+            categories: vec!["Nebulae".to_string(), "Stars".to_string()],
+            instruments: vec!["NIRCam".to_string(), "nircam".to_string()],
+            fields: vec![
+                ("Constellation".to_string(), "Carina".to_string()),
+                ("Credit".to_string(), "NASA, ESA".to_string()),
+            ],
+        };
+        assert_eq!(record_tags(&record), ["Nebulae", "Stars", "NIRCam", "Carina"]);
+    }
+}
@@ -0,0 +1,199 @@
+//! Local image source - images already in the wallpaper directory, including
+//! generated outputs.
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::http::{HttpClient, Service};
+use super::image_source::{ImageRecord, ImageSource, ProgressFn, Rendition, RenditionProbe};
+use super::provenance::read_sidecar;
+use crate::config::Config;
+use crate::utils::create_thumbnail;
+
+/// File extensions treated as images.
+const IMAGE_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "tif", "tiff"];
+
+/// Build a record for a local image file, with the title, credit and
+/// source from its provenance sidecar when it has one.
+pub fn local_record(path: &Path) -> ImageRecord {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let title = path
+        .file_stem()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| file_name.clone());
+    let date = fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .map(DateTime::<Utc>::from);
+
+    let mut record = ImageRecord {
+        source: "local".to_string(),
+        id: file_name,
+        title,
+        date,
+        description: None,
+        credit: None,
+        page_url: None,
+        renditions: vec![Rendition {
+            name: "original".to_string(),
+            url: format!("file://{}", path.display()),
+        }],
+        dimensions: None,
+        categories: Vec::new(),
+        instruments: Vec::new(),
+        fields: vec![("Path".to_string(), path.display().to_string())],
+    };
+
+    if let Some(provenance) = read_sidecar(path) {
+        record.title = provenance.title;
+        record.credit = provenance.credit;
+        record.page_url = provenance.source_url;
+        let mut field = |label: &str, value: Option<String>| {
+            if let Some(value) = value {
+                record.fields.push((label.to_string(), value));
+            }
+        };
+        field("Origin", Some(format!("{} {}", provenance.source, provenance.id)));
+        field("Rendition", provenance.rendition);
+        field("License", provenance.license);
+        field("Generated from", provenance.generated_from.map(|p| p.display().to_string()));
+        field("Prompt", provenance.prompt);
+        field("Model", provenance.model);
+    }
+
+    record
+}
+
+/// Get the file path of a local record.
+pub fn local_path(image: &ImageRecord) -> Option<PathBuf> {
+    image.field("Path").map(PathBuf::from)
+}
+
+/// Check if a path looks like an image file.
+pub fn is_image_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Service listing images in the wallpaper directory.
+pub struct LocalService {
+    config: Arc<Config>,
+    client: HttpClient,
+}
+
+impl LocalService {
+    /// Create a new local image service.
+    pub fn new(config: Arc<Config>) -> Result<Self> {
+        Ok(Self {
+            client: HttpClient::new(&config, Service::Images)?,
+            config,
+        })
+    }
+}
+
+#[async_trait]
+impl ImageSource for LocalService {
+    fn key(&self) -> &str {
+        "local"
+    }
+
+    fn name(&self) -> &str {
+        "Local files"
+    }
+
+    fn file_prefix(&self) -> &str {
+        "local"
+    }
+
+    fn config(&self) -> &Config {
+        &self.config
+    }
+
+    fn client(&self) -> &HttpClient {
+        &self.client
+    }
+
+    async fn list_images(&self, _force_refresh: bool) -> Result<Vec<ImageRecord>> {
+        let wallpaper_dir = self.config.wallpaper_dir();
+        let entries = match fs::read_dir(&wallpaper_dir) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(Vec::new()),
+        };
+
+        let mut images: Vec<ImageRecord> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.is_file() && is_image_file(p))
+            .map(|p| local_record(&p))
+            .collect();
+
+        // Newest first
+        images.sort_by_key(|image| std::cmp::Reverse(image.date));
+
+        Ok(images)
+    }
+
+    fn download_path(&self, image: &ImageRecord, _rendition: &str) -> PathBuf {
+        local_path(image).unwrap_or_else(|| self.config.wallpaper_dir().join(&image.id))
+    }
+
+    fn download_candidates(&self, image: &ImageRecord) -> Vec<PathBuf> {
+        local_path(image).into_iter().collect()
+    }
+
+    fn downloaded_files(&self, image: &ImageRecord) -> Vec<PathBuf> {
+        self.get_downloaded_path(image).into_iter().collect()
+    }
+
+    async fn download_thumbnail(&self, image: &ImageRecord) -> Result<PathBuf> {
+        let thumbnail_dir = self.config.thumbnail_dir();
+        fs::create_dir_all(&thumbnail_dir)?;
+
+        let thumbnail_path = thumbnail_dir.join(format!("local-{}.thumb.jpg", image.id));
+        if thumbnail_path.exists() {
+            return Ok(thumbnail_path);
+        }
+
+        let path = self
+            .get_downloaded_path(image)
+            .context("Local image no longer exists")?;
+        let thumbnail = tokio::task::spawn_blocking(move || create_thumbnail(&path, 400)).await??;
+        thumbnail
+            .to_rgb8()
+            .save(&thumbnail_path)
+            .context("Failed to save thumbnail")?;
+
+        Ok(thumbnail_path)
+    }
+
+    async fn download_image_with_progress(
+        &self,
+        image: &ImageRecord,
+        _rendition: &str,
+        _progress: &ProgressFn<'_>,
+    ) -> Result<PathBuf> {
+        // Already local
+        self.get_downloaded_path(image)
+            .context("Local image no longer exists")
+    }
+
+    async fn probe_renditions(&self, image: &ImageRecord) -> Vec<RenditionProbe> {
+        let path = self.get_downloaded_path(image);
+        vec![RenditionProbe {
+            name: "original".to_string(),
+            available: path.is_some(),
+            size: path.as_ref().and_then(|p| fs::metadata(p).ok()).map(|m| m.len()),
+            dimensions: path.as_ref().and_then(|p| image::image_dimensions(p).ok()),
+            error: path.is_none().then(|| "File no longer exists".to_string()),
+        }]
+    }
+}
@@ -1,15 +1,54 @@
 //! Backend services for JWST Cosmos.
 
+pub mod apod;
+pub mod cache;
+pub mod collections;
+pub mod downloads;
+pub mod esa_metadata;
+pub mod favorites;
+pub mod http;
+pub mod image_source;
 pub mod jwst_esa;
 pub mod jwst_api;
+pub mod library;
+pub mod local;
+pub mod provenance;
 pub mod ssh_tunnel;
 pub mod ollama;
 pub mod comfyui;
+pub mod variants;
 pub mod wallust;
 
-pub use jwst_esa::{EsaService, EsaImage};
+#[cfg(test)]
+mod test_server;
+
+pub use apod::ApodService;
+pub use collections::CollectionsService;
+pub use downloads::DownloadManager;
+pub use favorites::FavoritesService;
+pub use image_source::{ImageRecord, ImageSource, DEFAULT_RENDITION};
+pub use jwst_esa::EsaService;
 pub use jwst_api::{JwstApiService, JwstImage};
+pub use library::LibraryIndex;
+pub use local::LocalService;
 pub use ssh_tunnel::{SshTunnel, TunnelManager};
 pub use ollama::{OllamaService, OllamaModel, PullProgress};
 pub use comfyui::{ComfyUiService, GenerationProgress, GenerationResult};
 pub use wallust::{WallustService, WallustColors};
+
+use anyhow::Result;
+use std::sync::Arc;
+
+use crate::config::Config;
+
+/// Build every configured image source, in browser order.
+pub fn build_sources(config: Arc<Config>) -> Result<Vec<Arc<dyn ImageSource>>> {
+    let mut sources: Vec<Arc<dyn ImageSource>> = Vec::new();
+    for feed in config.jwst.esa_feeds() {
+        sources.push(Arc::new(EsaService::new(config.clone(), feed)?));
+    }
+    sources.push(Arc::new(JwstApiService::new(config.clone())?));
+    sources.push(Arc::new(ApodService::new(config.clone())?));
+    sources.push(Arc::new(LocalService::new(config)?));
+    Ok(sources)
+}
@@ -1,11 +1,13 @@
 //! Ollama API client for vision models and model management.
 
 use anyhow::{Context, Result};
-use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+use super::http::{HttpClient, Service};
+use crate::config::Config;
+
 /// Represents an Ollama model.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OllamaModel {
@@ -100,20 +102,17 @@ pub struct PullProgress {
 
 /// Service for interacting with Ollama API.
 pub struct OllamaService {
-    client: Client,
+    client: HttpClient,
     base_url: Arc<RwLock<Option<String>>>,
 }
 
 impl OllamaService {
     /// Create a new Ollama service.
-    pub fn new() -> Self {
-        Self {
-            client: Client::builder()
-                .timeout(std::time::Duration::from_secs(300))
-                .build()
-                .expect("Failed to create HTTP client"),
+    pub fn new(config: &Config) -> Result<Self> {
+        Ok(Self {
+            client: HttpClient::new(config, Service::Ollama)?,
             base_url: Arc::new(RwLock::new(None)),
-        }
+        })
     }
 
     /// Set the base URL (from SSH tunnel).
@@ -323,9 +322,3 @@ impl OllamaService {
         Ok(model)
     }
 }
-
-impl Default for OllamaService {
-    fn default() -> Self {
-        Self::new()
-    }
-}
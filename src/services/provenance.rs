@@ -0,0 +1,541 @@
+//! Credit and provenance metadata for saved images.
+//!
+//! Most gallery imagery is CC BY 4.0 and needs attribution wherever it ends
+//! up, so every downloaded file gets its title, credit, source and license
+//! twice: embedded in the file (EXIF and XMP for JPEG, text chunks for PNG)
+//! and in a JSON sidecar `<file>.json` next to it. Other formats only get
+//! the sidecar.
+//!
+//! Generated images don't get one yet, since the generator screen doesn't
+//! run generations. The library still reads `generated_from`, `prompt` and
+//! `model` back from any sidecar that has them.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use super::image_source::ImageRecord;
+
+/// Name written into the EXIF Software tag.
+const SOFTWARE: &str = "jwst-cosmos";
+
+/// Longest string embedded in one EXIF tag or text chunk. Keeps the EXIF
+/// segment well under the 64 KB JPEG segment limit.
+const MAX_FIELD_BYTES: usize = 4096;
+
+/// XMP namespace header of a JPEG APP1 segment.
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+
+/// PNG file signature.
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+
+/// Where a saved image came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Provenance {
+    pub title: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub credit: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub license: Option<String>,
+    /// Key of the image source
+    pub source: String,
+    /// Image ID within the source
+    pub id: String,
+    /// Gallery page for the image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_url: Option<String>,
+    /// URL the file was downloaded from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rendition: Option<String>,
+    /// For generated images: the reference image they were made from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub generated_from: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub saved: DateTime<Utc>,
+}
+
+impl Provenance {
+    /// Provenance of a downloaded rendition of an image.
+    pub fn for_download(image: &ImageRecord, rendition: &str) -> Self {
+        Self {
+            title: image.title.clone(),
+            credit: image.credit.clone(),
+            license: image.field("License").map(str::to_string),
+            source: image.source.clone(),
+            id: image.id.clone(),
+            source_url: image.page_url.clone(),
+            image_url: image.rendition(rendition).map(|r| r.url.clone()),
+            rendition: Some(rendition.to_string()),
+            generated_from: None,
+            prompt: None,
+            model: None,
+            saved: Utc::now(),
+        }
+    }
+
+    /// Copyright notice, e.g. "© ESA/Webb, NASA & CSA (CC BY 4.0)".
+    pub fn rights(&self) -> Option<String> {
+        match (&self.credit, &self.license) {
+            (Some(credit), Some(license)) => Some(format!("© {} ({})", credit, license)),
+            (Some(credit), None) => Some(format!("© {}", credit)),
+            (None, Some(license)) => Some(license.clone()),
+            (None, None) => None,
+        }
+    }
+}
+
+/// Path of the JSON sidecar for an image file.
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".json");
+    path.with_file_name(name)
+}
+
+/// Read the provenance sidecar of an image file, if it has one.
+pub fn read_sidecar(path: &Path) -> Option<Provenance> {
+    let content = fs::read_to_string(sidecar_path(path)).ok()?;
+    serde_json::from_str(&content)
+        .map_err(|e| tracing::debug!("Invalid sidecar for {}: {}", path.display(), e))
+        .ok()
+}
+
+/// Write the provenance sidecar of an image file.
+pub fn write_sidecar(path: &Path, provenance: &Provenance) -> Result<()> {
+    let sidecar = sidecar_path(path);
+    let content = serde_json::to_string_pretty(provenance)?;
+    fs::write(&sidecar, content).with_context(|| format!("Failed to write {}", sidecar.display()))
+}
+
+/// Embed provenance in an image file and write its sidecar.
+pub fn save(path: &Path, provenance: &Provenance) -> Result<()> {
+    if let Err(e) = embed(path, provenance) {
+        tracing::warn!("Failed to embed metadata in {}: {:#}", path.display(), e);
+    }
+    write_sidecar(path, provenance)
+}
+
+/// Embed provenance in a JPEG or PNG file, replacing any EXIF/XMP or text
+/// chunks it already has for the same fields. Returns false for formats
+/// that don't get embedded metadata.
+pub fn embed(path: &Path, provenance: &Provenance) -> Result<bool> {
+    let bytes = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let updated = if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        embed_jpeg(&bytes, provenance)?
+    } else if bytes.starts_with(PNG_SIGNATURE) {
+        embed_png(&bytes, provenance)?
+    } else {
+        return Ok(false);
+    };
+
+    let tmp = path.with_extension("meta.tmp");
+    fs::write(&tmp, updated).with_context(|| format!("Failed to write {}", tmp.display()))?;
+    fs::rename(&tmp, path).with_context(|| format!("Failed to update {}", path.display()))?;
+    Ok(true)
+}
+
+/// Rebuild a JPEG with our EXIF and XMP segments after any APP0 (JFIF)
+/// segments, dropping the EXIF and XMP segments it had.
+fn embed_jpeg(bytes: &[u8], provenance: &Provenance) -> Result<Vec<u8>> {
+    let mut app0 = Vec::new();
+    let mut kept = Vec::new();
+    let mut pos = 2;
+
+    // Walk the APPn segments at the start of the file
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF && (0xE0..=0xEF).contains(&bytes[pos + 1]) {
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let end = pos + 2 + len;
+        if len < 2 || end > bytes.len() {
+            anyhow::bail!("Malformed JPEG segment");
+        }
+        let segment = &bytes[pos..end];
+        let payload = &segment[4..];
+        match bytes[pos + 1] {
+            0xE0 => app0.push(segment),
+            0xE1 if payload.starts_with(b"Exif\0\0") || payload.starts_with(XMP_HEADER) => {}
+            _ => kept.push(segment),
+        }
+        pos = end;
+    }
+
+    let mut out = Vec::with_capacity(bytes.len() + 8192);
+    out.extend_from_slice(&bytes[..2]);
+    for segment in app0 {
+        out.extend_from_slice(segment);
+    }
+    out.extend(app1_segment(&exif_payload(provenance))?);
+    let mut xmp = XMP_HEADER.to_vec();
+    xmp.extend_from_slice(xmp_packet(provenance).as_bytes());
+    out.extend(app1_segment(&xmp)?);
+    for segment in kept {
+        out.extend_from_slice(segment);
+    }
+    out.extend_from_slice(&bytes[pos..]);
+    Ok(out)
+}
+
+/// Wrap a payload in a JPEG APP1 segment.
+fn app1_segment(payload: &[u8]) -> Result<Vec<u8>> {
+    let len = u16::try_from(payload.len() + 2).context("Metadata too large for a JPEG segment")?;
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&len.to_be_bytes());
+    segment.extend_from_slice(payload);
+    Ok(segment)
+}
+
+/// Build an EXIF payload (little-endian TIFF with one IFD of ASCII tags).
+fn exif_payload(provenance: &Provenance) -> Vec<u8> {
+    // Tags must be in ascending order
+    let mut tags: Vec<(u16, String)> = vec![(0x010E, provenance.title.clone()), (0x0131, SOFTWARE.to_string())];
+    if let Some(credit) = &provenance.credit {
+        tags.push((0x013B, credit.clone()));
+    }
+    if let Some(rights) = provenance.rights() {
+        tags.push((0x8298, rights));
+    }
+
+    let ifd_len = 2 + tags.len() * 12 + 4;
+    let mut entries = Vec::new();
+    let mut data = Vec::new();
+    for (tag, value) in &tags {
+        let mut value = truncate(value, MAX_FIELD_BYTES).as_bytes().to_vec();
+        value.push(0);
+        entries.extend_from_slice(&tag.to_le_bytes());
+        entries.extend_from_slice(&2u16.to_le_bytes()); // ASCII
+        entries.extend_from_slice(&(value.len() as u32).to_le_bytes());
+        if value.len() <= 4 {
+            value.resize(4, 0);
+            entries.extend_from_slice(&value);
+        } else {
+            let offset = 8 + ifd_len + data.len();
+            entries.extend_from_slice(&(offset as u32).to_le_bytes());
+            data.extend_from_slice(&value);
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+        }
+    }
+
+    let mut payload = b"Exif\0\0II\x2A\0\x08\0\0\0".to_vec();
+    payload.extend_from_slice(&(tags.len() as u16).to_le_bytes());
+    payload.extend_from_slice(&entries);
+    payload.extend_from_slice(&0u32.to_le_bytes());
+    payload.extend_from_slice(&data);
+    payload
+}
+
+/// Build an XMP packet with Dublin Core, rights and Photoshop credit fields.
+fn xmp_packet(provenance: &Provenance) -> String {
+    let alt = |value: &str| {
+        format!(
+            "<rdf:Alt><rdf:li xml:lang=\"x-default\">{}</rdf:li></rdf:Alt>",
+            xml_escape(value)
+        )
+    };
+
+    let mut fields = vec![
+        format!("<dc:title>{}</dc:title>", alt(&provenance.title)),
+        format!(
+            "<dc:identifier>{}:{}</dc:identifier>",
+            xml_escape(&provenance.source),
+            xml_escape(&provenance.id)
+        ),
+    ];
+    if let Some(credit) = &provenance.credit {
+        fields.push(format!(
+            "<dc:creator><rdf:Seq><rdf:li>{}</rdf:li></rdf:Seq></dc:creator>",
+            xml_escape(credit)
+        ));
+        fields.push(format!("<photoshop:Credit>{}</photoshop:Credit>", xml_escape(credit)));
+    }
+    if let Some(rights) = provenance.rights() {
+        fields.push(format!("<dc:rights>{}</dc:rights>", alt(&rights)));
+    }
+    if let Some(license) = &provenance.license {
+        fields.push(format!("<xmpRights:UsageTerms>{}</xmpRights:UsageTerms>", alt(license)));
+    }
+    if let Some(url) = &provenance.source_url {
+        fields.push(format!("<dc:source>{}</dc:source>", xml_escape(url)));
+        fields.push(format!("<xmpRights:WebStatement>{}</xmpRights:WebStatement>", xml_escape(url)));
+    }
+
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+         <x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+         <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+         <rdf:Description rdf:about=\"\"\n \
+         xmlns:dc=\"http://purl.org/dc/elements/1.1/\"\n \
+         xmlns:xmpRights=\"http://ns.adobe.com/xap/1.0/rights/\"\n \
+         xmlns:photoshop=\"http://ns.adobe.com/photoshop/1.0/\">\n\
+         {}\n\
+         </rdf:Description>\n\
+         </rdf:RDF>\n\
+         </x:xmpmeta>\n\
+         <?xpacket end=\"w\"?>",
+        fields.join("\n")
+    )
+}
+
+/// Rebuild a PNG with our text chunks after IHDR, dropping text chunks it
+/// had for the same keywords.
+fn embed_png(bytes: &[u8], provenance: &Provenance) -> Result<Vec<u8>> {
+    let mut text = vec![
+        ("Title", provenance.title.clone()),
+        ("Software", SOFTWARE.to_string()),
+        ("Comment", format!("{}:{}", provenance.source, provenance.id)),
+    ];
+    if let Some(credit) = &provenance.credit {
+        text.push(("Author", credit.clone()));
+    }
+    if let Some(rights) = provenance.rights() {
+        text.push(("Copyright", rights));
+    }
+    if let Some(license) = &provenance.license {
+        text.push(("License", license.clone()));
+    }
+    if let Some(url) = &provenance.source_url {
+        text.push(("Source", url.clone()));
+    }
+
+    let mut out = PNG_SIGNATURE.to_vec();
+    let mut pos = PNG_SIGNATURE.len();
+    while pos + 12 <= bytes.len() {
+        let len = u32::from_be_bytes(bytes[pos..pos + 4].try_into().unwrap()) as usize;
+        let end = pos + 12 + len;
+        if end > bytes.len() {
+            anyhow::bail!("Malformed PNG chunk");
+        }
+        let kind = &bytes[pos + 4..pos + 8];
+        let data = &bytes[pos + 8..pos + 8 + len];
+
+        let replaced = (kind == b"tEXt" || kind == b"iTXt")
+            && text.iter().any(|(keyword, _)| {
+                data.starts_with(keyword.as_bytes()) && data.get(keyword.len()) == Some(&0)
+            });
+        if !replaced {
+            out.extend_from_slice(&bytes[pos..end]);
+        }
+        if kind == b"IHDR" {
+            for (keyword, value) in &text {
+                out.extend(text_chunk(keyword, truncate(value, MAX_FIELD_BYTES)));
+            }
+        }
+        pos = end;
+    }
+    Ok(out)
+}
+
+/// Build a tEXt chunk, or an iTXt chunk when the value isn't Latin-1.
+fn text_chunk(keyword: &str, value: &str) -> Vec<u8> {
+    let mut data = keyword.as_bytes().to_vec();
+    data.push(0);
+    let kind: &[u8; 4] = if value.chars().all(|c| (c as u32) < 256) {
+        data.extend(value.chars().map(|c| c as u8));
+        b"tEXt"
+    } else {
+        // Uncompressed, no language tag or translated keyword
+        data.extend_from_slice(&[0, 0, 0, 0]);
+        data.extend_from_slice(value.as_bytes());
+        b"iTXt"
+    };
+
+    let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+    chunk.extend_from_slice(kind);
+    chunk.extend_from_slice(&data);
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+    chunk
+}
+
+/// CRC-32 (IEEE) as used by PNG chunks.
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+/// Cut a string to at most `max` bytes on a character boundary.
+fn truncate(value: &str, max: usize) -> &str {
+    if value.len() <= max {
+        return value;
+    }
+    let mut end = max;
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::codecs::jpeg::JpegDecoder;
+    use image::ImageDecoder;
+    use std::io::Cursor;
+
+    fn provenance() -> Provenance {
+        Provenance {
+            title: "Cosmic Cliffs".to_string(),
+            credit: Some("ESA/Webb, NASA & CSA".to_string()),
+            license: Some("CC BY 4.0".to_string()),
+            source: "esa".to_string(),
+            id: "weic2205a".to_string(),
+            source_url: Some("https://esawebb.org/images/weic2205a/".to_string()),
+            image_url: None,
+            rendition: Some("screen".to_string()),
+            generated_from: None,
+            prompt: None,
+            model: None,
+            saved: Utc::now(),
+        }
+    }
+
+    fn encode(format: image::ImageFormat) -> Vec<u8> {
+        let image = image::RgbImage::from_fn(32, 16, |x, y| image::Rgb([(x * 8) as u8, (y * 16) as u8, 128]));
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    /// ASCII tags of the first IFD of a little-endian EXIF payload.
+    fn exif_tags(exif: &[u8]) -> Vec<(u16, String)> {
+        let tiff = exif.strip_prefix(b"Exif\0\0").unwrap_or(exif);
+        assert_eq!(&tiff[..4], b"II\x2A\0");
+        let u16_at = |pos: usize| u16::from_le_bytes([tiff[pos], tiff[pos + 1]]);
+        let u32_at = |pos: usize| u32::from_le_bytes(tiff[pos..pos + 4].try_into().unwrap()) as usize;
+
+        let ifd = u32_at(4);
+        (0..u16_at(ifd) as usize)
+            .map(|i| {
+                let entry = ifd + 2 + i * 12;
+                assert_eq!(u16_at(entry + 2), 2, "ASCII type");
+                let len = u32_at(entry + 4);
+                let start = if len <= 4 { entry + 8 } else { u32_at(entry + 8) };
+                let value = &tiff[start..start + len];
+                assert_eq!(value.last(), Some(&0), "NUL-terminated");
+                (u16_at(entry), String::from_utf8(value[..len - 1].to_vec()).unwrap())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn jpeg_roundtrip_keeps_image_and_adds_exif_and_xmp() {
+        let original = encode(image::ImageFormat::Jpeg);
+        let embedded = embed_jpeg(&original, &provenance()).unwrap();
+
+        let decoded = image::load_from_memory(&embedded).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (32, 16));
+
+        let mut decoder = JpegDecoder::new(Cursor::new(&embedded)).unwrap();
+        let tags = exif_tags(&decoder.exif_metadata().unwrap().expect("EXIF segment"));
+        assert_eq!(
+            tags,
+            [
+                (0x010E, "Cosmic Cliffs".to_string()),
+                (0x0131, SOFTWARE.to_string()),
+                (0x013B, "ESA/Webb, NASA & CSA".to_string()),
+                (0x8298, "© ESA/Webb, NASA & CSA (CC BY 4.0)".to_string()),
+            ]
+        );
+
+        let xmp = String::from_utf8(decoder.xmp_metadata().unwrap().expect("XMP segment")).unwrap();
+        assert!(xmp.contains("<rdf:li xml:lang=\"x-default\">Cosmic Cliffs</rdf:li>"));
+        assert!(xmp.contains("<dc:identifier>esa:weic2205a</dc:identifier>"));
+        assert!(xmp.contains("<photoshop:Credit>ESA/Webb, NASA &amp; CSA</photoshop:Credit>"));
+        assert!(xmp.contains("<dc:source>https://esawebb.org/images/weic2205a/</dc:source>"));
+    }
+
+    #[test]
+    fn jpeg_reembedding_replaces_old_segments() {
+        let once = embed_jpeg(&encode(image::ImageFormat::Jpeg), &provenance()).unwrap();
+        let mut renamed = provenance();
+        renamed.title = "Pillars".to_string();
+        let twice = embed_jpeg(&once, &renamed).unwrap();
+
+        let count = |needle: &[u8]| twice.windows(needle.len()).filter(|w| *w == needle).count();
+        assert_eq!(count(b"Exif\0\0"), 1);
+        assert_eq!(count(XMP_HEADER), 1);
+        let mut decoder = JpegDecoder::new(Cursor::new(&twice)).unwrap();
+        let tags = exif_tags(&decoder.exif_metadata().unwrap().unwrap());
+        assert_eq!(tags[0], (0x010E, "Pillars".to_string()));
+    }
+
+    #[test]
+    fn exif_keeps_short_values_inline() {
+        let mut short = provenance();
+        short.title = "M1".to_string();
+        short.credit = None;
+        short.license = None;
+        let tags = exif_tags(&exif_payload(&short));
+        assert_eq!(tags, [(0x010E, "M1".to_string()), (0x0131, SOFTWARE.to_string())]);
+    }
+
+    #[test]
+    fn png_roundtrip_writes_text_chunks() {
+        let mut provenance = provenance();
+        provenance.credit = Some("ESA/Webb, NASA & CSA, J. Müller, 小林".to_string());
+        let embedded = embed_png(&encode(image::ImageFormat::Png), &provenance).unwrap();
+
+        let decoded = image::load_from_memory(&embedded).unwrap();
+        assert_eq!((decoded.width(), decoded.height()), (32, 16));
+
+        // The png decoder checks every chunk's CRC
+        let reader = png::Decoder::new(Cursor::new(&embedded)).read_info().unwrap();
+        let info = reader.info();
+        let latin1: Vec<(&str, &str)> = info
+            .uncompressed_latin1_text
+            .iter()
+            .map(|c| (c.keyword.as_str(), c.text.as_str()))
+            .collect();
+        assert!(latin1.contains(&("Title", "Cosmic Cliffs")));
+        assert!(latin1.contains(&("Comment", "esa:weic2205a")));
+        assert!(latin1.contains(&("License", "CC BY 4.0")));
+        assert!(latin1.contains(&("Source", "https://esawebb.org/images/weic2205a/")));
+
+        // Not Latin-1, so these go in iTXt
+        let utf8: Vec<(String, String)> = info
+            .utf8_text
+            .iter()
+            .map(|c| (c.keyword.clone(), c.get_text().unwrap()))
+            .collect();
+        assert!(utf8.contains(&("Author".to_string(), "ESA/Webb, NASA & CSA, J. Müller, 小林".to_string())));
+        assert!(utf8.contains(&(
+            "Copyright".to_string(),
+            "© ESA/Webb, NASA & CSA, J. Müller, 小林 (CC BY 4.0)".to_string()
+        )));
+    }
+
+    #[test]
+    fn png_reembedding_replaces_old_chunks() {
+        let once = embed_png(&encode(image::ImageFormat::Png), &provenance()).unwrap();
+        let twice = embed_png(&once, &provenance()).unwrap();
+        assert_eq!(once, twice);
+    }
+
+    #[test]
+    fn crc32_matches_the_standard_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+        // The IEND chunk's well-known CRC
+        assert_eq!(crc32(b"IEND"), 0xAE42_6082);
+    }
+}
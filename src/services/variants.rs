@@ -0,0 +1,184 @@
+//! Monitor-sized variants of downloaded images.
+//!
+//! After a download, the image is cropped (or letterboxed) to each configured
+//! monitor profile and saved next to it as `<prefix>-<id>-<profile>.jpg`,
+//! which is where `get_downloaded_path` looks first.
+
+use anyhow::{Context, Result};
+use image::codecs::jpeg::JpegEncoder;
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+use crate::config::Config;
+use crate::utils::{fit_to_aspect, validate_image_file, FitMode, SizePreset};
+
+/// JPEG quality for written variants.
+const VARIANT_QUALITY: u8 = 92;
+
+/// Largest source image (in pixels) decoded for variants. Full-resolution
+/// TIFFs can run to hundreds of megapixels.
+pub const MAX_SOURCE_PIXELS: u64 = 150_000_000;
+
+/// Get the configured monitor profiles. Names are checked when the config
+/// is loaded.
+pub fn monitor_profiles(config: &Config) -> Vec<SizePreset> {
+    config
+        .jwst
+        .monitor_profiles
+        .iter()
+        .filter_map(|name| SizePreset::parse(name))
+        .collect()
+}
+
+/// Get the configured variant mode (crop unless set to "fit").
+pub fn variant_mode(config: &Config) -> FitMode {
+    FitMode::from_config(&config.jwst.variant_mode).unwrap_or(FitMode::Crop)
+}
+
+/// Write a variant of `source` for each profile. Returns the paths written.
+pub fn write_variants(source: &Path, targets: &[(SizePreset, PathBuf)], mode: FitMode) -> Result<Vec<PathBuf>> {
+    if targets.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (width, height) = validate_image_file(source)?;
+    if width as u64 * height as u64 > MAX_SOURCE_PIXELS {
+        anyhow::bail!("{}x{} is too large to make variants from", width, height);
+    }
+    let img = image::open(source).context("Failed to open image")?;
+
+    let mut written = Vec::new();
+    for (preset, path) in targets {
+        let (width, height) = preset.dimensions();
+        let variant = fit_to_aspect(&img, width, height, mode).to_rgb8();
+
+        let tmp = path.with_extension("jpg.tmp");
+        let file = File::create(&tmp).with_context(|| format!("Failed to create {}", tmp.display()))?;
+        variant
+            .write_with_encoder(JpegEncoder::new_with_quality(BufWriter::new(file), VARIANT_QUALITY))
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        fs::rename(&tmp, path).with_context(|| format!("Failed to save {}", path.display()))?;
+        written.push(path.clone());
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::JwstConfig;
+    use crate::services::image_source::Rendition;
+    use crate::services::{EsaService, ImageRecord, ImageSource};
+    use std::sync::Arc;
+
+    /// Dark sky with a bright, textured patch spanning columns `x` and rows `y`.
+    fn sky(width: u32, height: u32, x: std::ops::Range<u32>, y: std::ops::Range<u32>) -> image::RgbImage {
+        image::RgbImage::from_fn(width, height, |px, py| {
+            if x.contains(&px) && y.contains(&py) {
+                let v = if (px / 4 + py / 4) % 2 == 0 { 255 } else { 200 };
+                image::Rgb([v, v, v])
+            } else {
+                image::Rgb([8, 8, 12])
+            }
+        })
+    }
+
+    /// Columns of `image` holding any bright pixel.
+    fn bright_columns(image: &image::RgbImage) -> Vec<u32> {
+        (0..image.width())
+            .filter(|&x| (0..image.height()).any(|y| image.get_pixel(x, y)[0] > 150))
+            .collect()
+    }
+
+    #[test]
+    fn crop_keeps_an_off_centre_bright_region() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("webb-test-large.png");
+        sky(2000, 1000, 1600..1900, 400..600).save(&source).unwrap();
+        let target = dir.path().join("webb-test-1000x1000.jpg");
+
+        let written = write_variants(&source, &[(SizePreset::Custom(1000, 1000), target.clone())], FitMode::Crop).unwrap();
+        assert_eq!(written, std::slice::from_ref(&target));
+
+        let variant = image::open(&target).unwrap().to_rgb8();
+        assert_eq!(variant.dimensions(), (1000, 1000));
+        // A centre crop (500..1500) would miss the patch entirely
+        let columns = bright_columns(&variant);
+        assert!(!columns.is_empty(), "bright region cropped away");
+        let (first, last) = (columns[0], *columns.last().unwrap());
+        assert!(last - first >= 290, "bright region cut: columns {}..{}", first, last);
+    }
+
+    #[test]
+    fn fit_keeps_the_whole_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("webb-test-large.png");
+        sky(400, 400, 0..40, 0..400).save(&source).unwrap();
+        let target = dir.path().join("webb-test-800x400.jpg");
+
+        write_variants(&source, &[(SizePreset::Custom(800, 400), target.clone())], FitMode::Fit).unwrap();
+        let variant = image::open(&target).unwrap().to_rgb8();
+        assert_eq!(variant.dimensions(), (800, 400));
+        // Letterboxed in the middle, left edge included
+        let columns = bright_columns(&variant);
+        assert!(columns[0] >= 195 && columns[0] <= 205, "starts at {}", columns[0]);
+    }
+
+    #[test]
+    fn refuses_sources_over_the_pixel_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("webb-huge-original.png");
+        // Just the header of a 20000x8000 (160 MP) PNG; never decoded
+        {
+            let file = File::create(&source).unwrap();
+            let mut writer = png::Encoder::new(file, 20_000, 8_000).write_header().unwrap();
+            writer.write_chunk(png::chunk::IDAT, &[]).unwrap();
+        }
+        let target = dir.path().join("webb-huge-ultrawide.jpg");
+
+        let error = write_variants(&source, &[(SizePreset::Ultrawide, target.clone())], FitMode::Crop).unwrap_err();
+        assert!(error.to_string().contains("too large"), "{:#}", error);
+        assert!(!target.exists());
+    }
+
+    #[tokio::test]
+    async fn variants_are_written_where_downloads_are_looked_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = Config::default();
+        config.jwst.wallpaper_dir = dir.path().display().to_string();
+        config.jwst.monitor_profiles = vec!["ultrawide".to_string(), "1920x1200".to_string()];
+        let feed = JwstConfig::default().esa_feeds().remove(0);
+        let esa = EsaService::new(Arc::new(config), feed).unwrap();
+
+        let image = ImageRecord {
+            source: "esa".to_string(),
+            id: "weic2425a".to_string(),
+            title: "Cosmic Cliffs".to_string(),
+            date: None,
+            description: None,
+            credit: None,
+            page_url: None,
+            renditions: vec![Rendition {
+                name: "large".to_string(),
+                url: "https://cdn.esawebb.org/archives/images/large/weic2425a.jpg".to_string(),
+            }],
+            dimensions: None,
+            categories: Vec::new(),
+            instruments: Vec::new(),
+            fields: Vec::new(),
+        };
+        let download = esa.download_path(&image, "large");
+        sky(1200, 800, 500..700, 300..500).save_with_format(&download, image::ImageFormat::Jpeg).unwrap();
+
+        let written = esa.write_variants(&image, &download).await;
+        assert_eq!(
+            written,
+            [dir.path().join("webb-weic2425a-ultrawide.jpg"), dir.path().join("webb-weic2425a-1920x1200.jpg")]
+        );
+        // The first profile's variant is preferred over the download itself
+        assert_eq!(esa.get_downloaded_path(&image), Some(written[0].clone()));
+        assert!(esa.downloaded_files(&image).contains(&written[1]));
+    }
+}
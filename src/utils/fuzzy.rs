@@ -0,0 +1,164 @@
+//! Fuzzy subsequence and word matching for incremental search.
+
+/// Result of a successful fuzzy match.
+#[derive(Debug, Clone, Default)]
+pub struct FuzzyMatch {
+    /// Higher is better
+    pub score: i64,
+    /// Character (not byte) indices of the matched characters in the text
+    pub positions: Vec<usize>,
+}
+
+/// Match `pattern` against `text` as a case-insensitive subsequence.
+///
+/// Consecutive matches and matches at the start of a word score higher, gaps
+/// are penalized. Whitespace in the pattern is ignored.
+pub fn fuzzy_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let pattern: Vec<char> = pattern
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+        .collect();
+    if pattern.is_empty() {
+        return Some(FuzzyMatch::default());
+    }
+
+    let text: Vec<char> = text.chars().collect();
+    let mut positions = Vec::with_capacity(pattern.len());
+    let mut score = 0i64;
+    let mut pi = 0;
+    let mut last_match: Option<usize> = None;
+
+    for (ti, &c) in text.iter().enumerate() {
+        if pi == pattern.len() {
+            break;
+        }
+        if !c.to_lowercase().eq(std::iter::once(pattern[pi])) {
+            continue;
+        }
+
+        score += 10;
+        match last_match {
+            Some(last) if last + 1 == ti => score += 15,
+            Some(last) => score -= (ti - last - 1).min(10) as i64,
+            None => score -= ti.min(10) as i64,
+        }
+        let word_start = ti == 0 || !text[ti - 1].is_alphanumeric();
+        if word_start {
+            score += 10;
+        }
+
+        positions.push(ti);
+        last_match = Some(ti);
+        pi += 1;
+    }
+
+    if pi == pattern.len() {
+        Some(FuzzyMatch { score, positions })
+    } else {
+        None
+    }
+}
+
+/// Match each whitespace-separated word of `pattern` as a case-insensitive
+/// substring of `text`, in any order.
+///
+/// Meant for long text such as descriptions, where a subsequence would match
+/// almost anything. Scores like a run of consecutive fuzzy matches, so
+/// matches at the start of a word rank higher.
+pub fn word_match(pattern: &str, text: &str) -> Option<FuzzyMatch> {
+    let fold = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let text: Vec<char> = text.chars().collect();
+    let folded: Vec<char> = text.iter().copied().map(fold).collect();
+
+    let mut result = FuzzyMatch::default();
+    for word in pattern.split_whitespace() {
+        let word: Vec<char> = word.chars().map(fold).collect();
+        let word_start = |start: usize| start == 0 || !text[start - 1].is_alphanumeric();
+        let starts: Vec<usize> = folded
+            .windows(word.len())
+            .enumerate()
+            .filter(|(_, window)| *window == word.as_slice())
+            .map(|(start, _)| start)
+            .collect();
+        let start = starts
+            .iter()
+            .copied()
+            .find(|&start| word_start(start))
+            .or_else(|| starts.first().copied())?;
+
+        result.score += 10 * word.len() as i64 + 15 * (word.len() as i64 - 1) - start.min(10) as i64;
+        if word_start(start) {
+            result.score += 10;
+        }
+        result.positions.extend(start..start + word.len());
+    }
+
+    result.positions.sort_unstable();
+    result.positions.dedup();
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(pattern: &str, text: &str) -> i64 {
+        fuzzy_match(pattern, text).unwrap().score
+    }
+
+    #[test]
+    fn fuzzy_ranks_consecutive_and_word_start_matches_higher() {
+        assert!(score("crab", "Crab Nebula") > score("crab", "Cosmic ring around black hole"));
+        assert!(score("neb", "Crab Nebula") > score("neb", "Pinebranch"));
+        assert!(score("ngc", "NGC 3324") > score("ngc", "a NGC 3324"));
+    }
+
+    #[test]
+    fn fuzzy_folds_case_and_ignores_pattern_whitespace() {
+        assert_eq!(fuzzy_match("CRAB", "crab nebula").unwrap().positions, [0, 1, 2, 3]);
+        assert_eq!(fuzzy_match("cr ab", "Crab").unwrap().positions, [0, 1, 2, 3]);
+        assert!(fuzzy_match("ÉTOILE", "étoile").is_some());
+        assert!(fuzzy_match("  ", "anything").unwrap().positions.is_empty());
+    }
+
+    #[test]
+    fn fuzzy_rejects_out_of_order_or_missing_characters() {
+        assert!(fuzzy_match("ba", "ab").is_none());
+        assert!(fuzzy_match("xyz", "Carina Nebula").is_none());
+        assert!(fuzzy_match("nebulae", "nebula").is_none());
+    }
+
+    #[test]
+    fn fuzzy_positions_are_character_indices() {
+        assert_eq!(fuzzy_match("ß", "Weiß").unwrap().positions, [3]);
+        assert_eq!(fuzzy_match("μm", "1.87 μm").unwrap().positions, [5, 6]);
+        assert_eq!(fuzzy_match("星雲", "オリオン大星雲").unwrap().positions, [5, 6]);
+    }
+
+    #[test]
+    fn word_match_needs_every_word_as_a_substring() {
+        let text = "The Carina Nebula, 7600 light-years away";
+        assert!(word_match("nebula carina", text).is_some());
+        assert!(word_match("ebul", text).is_some());
+        assert!(word_match("carina orion", text).is_none());
+        // A subsequence is not enough
+        assert!(word_match("cnb", text).is_none());
+        assert!(word_match("", text).unwrap().positions.is_empty());
+    }
+
+    #[test]
+    fn word_match_prefers_word_starts() {
+        assert_eq!(word_match("light", "Twilight light").unwrap().positions, [9, 10, 11, 12, 13]);
+        let prefix = word_match("neb", "A nebula").unwrap().score;
+        let inner = word_match("neb", "Pinebranch").unwrap().score;
+        assert!(prefix > inner);
+    }
+
+    #[test]
+    fn word_match_folds_case_in_multibyte_text() {
+        assert_eq!(word_match("ÉTOILE", "Une étoile").unwrap().positions, [4, 5, 6, 7, 8, 9]);
+        assert_eq!(word_match("μm", "1.87 ΜM band").unwrap().positions, [5, 6]);
+        assert!(word_match("星雲", "オリオン大星雲").is_some());
+    }
+}
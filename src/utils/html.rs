@@ -0,0 +1,165 @@
+//! Minimal HTML helpers for scraping gallery pages.
+
+/// Decode named and numeric HTML entities.
+pub fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+
+        let decoded = rest.find(';').filter(|&end| end <= 10).and_then(|end| {
+            let entity = &rest[1..end];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                "nbsp" => Some(' '),
+                _ if entity.starts_with("#x") || entity.starts_with("#X") => {
+                    u32::from_str_radix(&entity[2..], 16).ok().and_then(char::from_u32)
+                }
+                _ if entity.starts_with('#') => {
+                    entity[1..].parse().ok().and_then(char::from_u32)
+                }
+                _ => None,
+            };
+            c.map(|c| (c, end))
+        });
+
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Strip tags and decode entities, turning `<br>` and `</p>` into newlines.
+///
+/// Line breaks in the source are whitespace like any other; runs of
+/// whitespace within a line are collapsed and blank lines dropped.
+pub fn to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut rest = html;
+    let push_source = |text: &mut String, source: &str| {
+        text.extend(source.chars().map(|c| if c == '\n' || c == '\r' { ' ' } else { c }));
+    };
+
+    while let Some(lt) = rest.find('<') {
+        push_source(&mut text, &rest[..lt]);
+        let end = match rest[lt..].find('>') {
+            Some(end) => lt + end,
+            None => break,
+        };
+        let tag = rest[lt + 1..end].trim().to_lowercase();
+        if tag.starts_with("br") || tag == "/p" || tag == "/div" || tag == "/li" {
+            text.push('\n');
+        }
+        rest = &rest[end + 1..];
+    }
+    if !rest.contains('<') {
+        push_source(&mut text, rest);
+    }
+
+    unescape(&text)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Iterate over the inner HTML of every `<tag ...>...</tag>` element.
+///
+/// Nested elements of the same tag are not supported, which is fine for the
+/// table rows and cells this is used on.
+pub fn elements<'a>(html: &'a str, tag: &str) -> Vec<&'a str> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+    let mut found = Vec::new();
+    let mut rest = html;
+
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        // Make sure we matched the whole tag name (<td> not <tdx>)
+        if !after.starts_with(['>', ' ', '\t', '\n', '/']) {
+            rest = after;
+            continue;
+        }
+        let body_start = match after.find('>') {
+            Some(pos) => pos + 1,
+            None => break,
+        };
+        let body = &after[body_start..];
+        match body.find(&close) {
+            Some(end) => {
+                found.push(&body[..end]);
+                rest = &body[end + close.len()..];
+            }
+            None => break,
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unescapes_named_and_numeric_entities() {
+        assert_eq!(unescape("Webb &amp; Hubble &lt;3 &quot;M16&quot; &apos;s"), "Webb & Hubble <3 \"M16\" 's");
+        assert_eq!(unescape("NGC&nbsp;3324"), "NGC 3324");
+        assert_eq!(unescape("1.87 &#956;m &#x201C;cliffs&#X201D;"), "1.87 μm “cliffs”");
+    }
+
+    #[test]
+    fn leaves_unknown_or_unterminated_entities_alone() {
+        assert_eq!(unescape("A & B"), "A & B");
+        assert_eq!(unescape("&bogus; &#xZZ;"), "&bogus; &#xZZ;");
+        assert_eq!(unescape("&amp"), "&amp");
+        // A semicolon too far away isn't the end of an entity
+        assert_eq!(unescape("&notanentityatall;"), "&notanentityatall;");
+        assert_eq!(unescape("&#1114112;"), "&#1114112;");
+    }
+
+    #[test]
+    fn to_text_breaks_lines_on_block_tags_only() {
+        let html = "<p>What looks like\n   craggy <em>mountains</em></p><p>Line one<br/>line   two</p>\n<div>  </div>";
+        assert_eq!(to_text(html), "What looks like craggy mountains\nLine one\nline two");
+        assert_eq!(to_text("<li>One</li><li>Two &amp; three</li>"), "One\nTwo & three");
+        assert_eq!(to_text("No tags at all"), "No tags at all");
+    }
+
+    #[test]
+    fn to_text_stops_at_an_unclosed_tag() {
+        assert_eq!(to_text("Before <a href="), "Before");
+    }
+
+    #[test]
+    fn elements_match_whole_tag_names() {
+        let html = "<table><thead><tr><th>Band</th></tr></thead>\
+                    <tr class=\"odd\"><td>F090W</td><td\n>900 nm</td></tr><tdx>no</tdx></table>";
+        assert_eq!(elements(html, "tr").len(), 2);
+        assert_eq!(elements(html, "td"), ["F090W", "900 nm"]);
+        assert_eq!(elements(html, "th"), ["Band"]);
+    }
+
+    #[test]
+    fn elements_ignore_unclosed_trailing_element() {
+        assert_eq!(elements("<p>one</p><p>two", "p"), ["one"]);
+        assert!(elements("", "p").is_empty());
+    }
+}
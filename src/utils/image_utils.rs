@@ -2,6 +2,10 @@
 
 use anyhow::{Context, Result};
 use image::{DynamicImage, GenericImageView};
+use image::ImageFormat;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
 
 /// Get image dimensions without loading the full image.
@@ -10,6 +14,40 @@ pub fn get_image_dimensions(path: &Path) -> Result<(u32, u32)> {
     Ok(img.dimensions())
 }
 
+/// Check that a file is a complete image: a recognised format, a valid
+/// header, and (for JPEG and PNG) the end-of-image marker. Returns the image
+/// dimensions.
+///
+/// Only the header and trailer are read, so this is cheap even for large
+/// files.
+pub fn validate_image_file(path: &Path) -> Result<(u32, u32)> {
+    let reader = image::ImageReader::open(path)
+        .context("Failed to open image")?
+        .with_guessed_format()
+        .context("Failed to read image")?;
+    let format = reader.format().context("Not an image file")?;
+    let dimensions = reader.into_dimensions().context("Invalid image header")?;
+
+    // A truncated file still has a valid header, so check how it ends
+    let mut file = File::open(path).context("Failed to open image")?;
+    let len = file.metadata()?.len();
+    let mut tail = vec![0; len.min(32) as usize];
+    file.seek(SeekFrom::End(-(tail.len() as i64)))?;
+    file.read_exact(&mut tail)?;
+
+    let complete = match format {
+        // Some encoders pad after the EOI marker
+        ImageFormat::Jpeg => tail.windows(2).any(|w| w == [0xFF, 0xD9]),
+        ImageFormat::Png => tail.ends_with(b"IEND\xAE\x42\x60\x82"),
+        _ => true,
+    };
+    if !complete {
+        anyhow::bail!("Image is truncated");
+    }
+
+    Ok(dimensions)
+}
+
 /// Resize an image to fit within max dimensions while maintaining aspect ratio.
 pub fn resize_to_fit(img: DynamicImage, max_width: u32, max_height: u32) -> DynamicImage {
     let (width, height) = img.dimensions();
@@ -27,12 +65,180 @@ pub fn resize_to_fit(img: DynamicImage, max_width: u32, max_height: u32) -> Dyna
     img.resize(new_width, new_height, image::imageops::FilterType::Lanczos3)
 }
 
+/// How to fit an image to a different aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FitMode {
+    /// Crop to the target aspect, keeping the most salient region
+    Crop,
+    /// Scale to fit and letterbox on black
+    Fit,
+}
+
+impl FitMode {
+    /// Parse a config value ("crop" or "fit").
+    pub fn from_config(value: &str) -> Option<Self> {
+        match value.to_lowercase().as_str() {
+            "crop" => Some(Self::Crop),
+            "fit" => Some(Self::Fit),
+            _ => None,
+        }
+    }
+}
+
+/// Crop or letterbox an image to the aspect ratio of `width`x`height`.
+///
+/// The result is scaled down to the target size but never scaled up; the
+/// wallpaper setter can do that without baking interpolation into the file.
+pub fn fit_to_aspect(img: &DynamicImage, width: u32, height: u32, mode: FitMode) -> DynamicImage {
+    let (src_w, src_h) = img.dimensions();
+    let target = width as f64 / height as f64;
+
+    match mode {
+        FitMode::Crop => {
+            // Largest window of the target aspect that fits in the image
+            let (crop_w, crop_h) = if src_w as f64 / src_h as f64 > target {
+                (((src_h as f64 * target).round() as u32).clamp(1, src_w), src_h)
+            } else {
+                (src_w, ((src_w as f64 / target).round() as u32).clamp(1, src_h))
+            };
+            let (x, y) = salient_crop(img, crop_w, crop_h);
+            let cropped = img.crop_imm(x, y, crop_w, crop_h);
+            if crop_w > width {
+                cropped.resize_exact(width, height, image::imageops::FilterType::Lanczos3)
+            } else {
+                cropped
+            }
+        }
+        FitMode::Fit => {
+            // Canvas of the target aspect that holds the whole image
+            let (canvas_w, canvas_h) = if src_w as f64 / src_h as f64 > target {
+                (src_w, (src_w as f64 / target).round() as u32)
+            } else {
+                ((src_h as f64 * target).round() as u32, src_h)
+            };
+            let scale = (width as f64 / canvas_w as f64).min(1.0);
+            let canvas_w = (canvas_w as f64 * scale).round().max(1.0) as u32;
+            let canvas_h = (canvas_h as f64 * scale).round().max(1.0) as u32;
+            let scaled = resize_to_fit(img.clone(), canvas_w, canvas_h).to_rgb8();
+
+            let mut canvas = image::RgbImage::new(canvas_w, canvas_h);
+            let x = (canvas_w - scaled.width().min(canvas_w)) / 2;
+            let y = (canvas_h - scaled.height().min(canvas_h)) / 2;
+            image::imageops::overlay(&mut canvas, &scaled, x as i64, y as i64);
+            DynamicImage::ImageRgb8(canvas)
+        }
+    }
+}
+
+/// Find the top-left corner of the `crop_w`x`crop_h` window holding the most
+/// visual weight.
+///
+/// Weight is brightness above the image mean plus local contrast, measured
+/// on a small copy of the image, so nebulae and galaxies count and the dark
+/// sky around them doesn't. The window only slides along one axis (it spans
+/// the other), and near-ties go to the most central position.
+fn salient_crop(img: &DynamicImage, crop_w: u32, crop_h: u32) -> (u32, u32) {
+    let (src_w, src_h) = img.dimensions();
+    let horizontal = crop_w < src_w;
+    if crop_w >= src_w && crop_h >= src_h {
+        return (0, 0);
+    }
+
+    let small = img.thumbnail(256, 256).to_luma8();
+    let (w, h) = small.dimensions();
+    let mean = small.pixels().map(|p| p[0] as f64).sum::<f64>() / (w * h).max(1) as f64;
+    let luma = |x: u32, y: u32| small.get_pixel(x, y)[0] as f64;
+
+    // Weight summed across the axis the window spans
+    let len = if horizontal { w } else { h } as usize;
+    let mut profile = vec![0.0; len];
+    for y in 0..h {
+        for x in 0..w {
+            let value = luma(x, y);
+            let contrast = (value - luma(x.saturating_sub(1), y)).abs()
+                + (value - luma(x, y.saturating_sub(1))).abs();
+            let weight = (value - mean).max(0.0) + contrast;
+            profile[if horizontal { x } else { y } as usize] += weight;
+        }
+    }
+
+    let (src_len, crop_len) = if horizontal { (src_w, crop_w) } else { (src_h, crop_h) };
+    let scale = src_len as f64 / len as f64;
+    let window = ((crop_len as f64 / scale).round() as usize).clamp(1, len);
+
+    let mut prefix = vec![0.0; len + 1];
+    for (i, weight) in profile.iter().enumerate() {
+        prefix[i + 1] = prefix[i] + weight;
+    }
+    let sums: Vec<f64> = (0..=len - window).map(|s| prefix[s + window] - prefix[s]).collect();
+    let best = sums.iter().cloned().fold(0.0, f64::max);
+    let center = (len - window) as f64 / 2.0;
+    let start = (0..sums.len())
+        .filter(|&s| sums[s] >= best * 0.98)
+        .min_by(|&a, &b| (a as f64 - center).abs().total_cmp(&(b as f64 - center).abs()))
+        .unwrap_or(0);
+
+    let offset = ((start as f64 * scale).round() as u32).min(src_len - crop_len);
+    if horizontal {
+        (offset, 0)
+    } else {
+        (0, offset)
+    }
+}
+
 /// Create a thumbnail from an image.
 pub fn create_thumbnail(path: &Path, thumb_size: u32) -> Result<DynamicImage> {
     let img = image::open(path).context("Failed to open image")?;
     Ok(img.thumbnail(thumb_size, thumb_size))
 }
 
+/// Most common colors of an image, most common first.
+///
+/// Pixels are bucketed at 4 bits per channel on a small thumbnail, and each
+/// color is the average of its bucket.
+pub fn dominant_colors(img: &DynamicImage, count: usize) -> Vec<[u8; 3]> {
+    let sample = img.thumbnail(64, 64).to_rgb8();
+    let mut buckets: HashMap<[u8; 3], ([u32; 3], u32)> = HashMap::new();
+    for pixel in sample.pixels() {
+        let [r, g, b] = pixel.0;
+        let (sum, n) = buckets.entry([r >> 4, g >> 4, b >> 4]).or_default();
+        sum[0] += r as u32;
+        sum[1] += g as u32;
+        sum[2] += b as u32;
+        *n += 1;
+    }
+
+    let mut buckets: Vec<_> = buckets.into_iter().collect();
+    buckets.sort_by_key(|(bucket, (_, n))| (std::cmp::Reverse(*n), *bucket));
+    buckets
+        .into_iter()
+        .take(count)
+        .map(|(_, (sum, n))| sum.map(|c| (c / n) as u8))
+        .collect()
+}
+
+/// Perceptual (difference) hash of an image.
+///
+/// Each bit says whether a pixel of a 9x8 grayscale thumbnail is brighter
+/// than its right neighbour, so resized and recompressed copies of a picture
+/// hash within a few bits of each other.
+pub fn perceptual_hash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, image::imageops::FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let bit = small.get_pixel(x, y).0[0] > small.get_pixel(x + 1, y).0[0];
+            hash = (hash << 1) | bit as u64;
+        }
+    }
+    hash
+}
+
+/// Number of differing bits between two perceptual hashes.
+pub fn hash_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
+
 /// Calculate aspect ratio as a string (e.g., "16:9", "21:9").
 pub fn aspect_ratio_str(width: u32, height: u32) -> String {
     let gcd = gcd(width, height);
@@ -76,6 +282,23 @@ pub fn human_size(bytes: u64) -> String {
     }
 }
 
+/// Parse a human-readable size like "500M", "2G" or "1.5 GB". A bare
+/// number is taken as megabytes.
+pub fn parse_size(s: &str) -> Option<u64> {
+    let s = s.trim().to_lowercase();
+    let split = s.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(s.len());
+    let (number, unit) = s.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier: u64 = match unit.trim() {
+        "b" => 1,
+        "k" | "kb" | "kib" => 1024,
+        "" | "m" | "mb" | "mib" => 1024 * 1024,
+        "g" | "gb" | "gib" => 1024 * 1024 * 1024,
+        _ => return None,
+    };
+    Some((number * multiplier as f64) as u64)
+}
+
 /// Size presets for image generation.
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SizePreset {
@@ -88,6 +311,40 @@ pub enum SizePreset {
 }
 
 impl SizePreset {
+    /// Parse a preset name ("hd", "qhd", "laptop", "4k", "ultrawide") or a
+    /// `WIDTHxHEIGHT` size.
+    pub fn parse(value: &str) -> Option<Self> {
+        let preset = match value.trim().to_lowercase().as_str() {
+            "hd" => Self::Hd,
+            "qhd" => Self::Qhd,
+            "laptop" => Self::Laptop,
+            "4k" | "uhd" => Self::Uhd4k,
+            "ultrawide" => Self::Ultrawide,
+            other => {
+                let (w, h) = other.split_once('x')?;
+                let (w, h) = (w.parse().ok()?, h.parse().ok()?);
+                if w == 0 || h == 0 {
+                    return None;
+                }
+                Self::Custom(w, h)
+            }
+        };
+        Some(preset)
+    }
+
+    /// File name suffix for images sized to this preset, e.g. `ultrawide`
+    /// in `webb-<id>-ultrawide.jpg`.
+    pub fn suffix(&self) -> String {
+        match self {
+            Self::Hd => "hd".to_string(),
+            Self::Qhd => "qhd".to_string(),
+            Self::Laptop => "laptop".to_string(),
+            Self::Uhd4k => "4k".to_string(),
+            Self::Ultrawide => "ultrawide".to_string(),
+            Self::Custom(w, h) => format!("{}x{}", w, h),
+        }
+    }
+
     pub fn dimensions(&self) -> (u32, u32) {
         match self {
             Self::Hd => (1920, 1080),
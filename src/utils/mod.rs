@@ -1,5 +1,7 @@
 //! Utility modules.
 
+pub mod fuzzy;
+pub mod html;
 pub mod image_utils;
 
 pub use image_utils::*;